@@ -0,0 +1,436 @@
+//! Optimal reciprocal collision avoidance (ORCA) on XZ plane, ported from RVO2 library. Every neighbour forbids
+//! a half-plane of velocities that would collide with it within time horizon, new velocity is the allowed one
+//! closest to preferred velocity. When there is no such velocity the one that violates half-planes the least is picked.
+//! Navmesh boundary edges forbid velocities the same way, their half-planes are never violated even when units are too dense
+
+use bevy :: prelude :: *;
+
+/// Another unit as seen by the one choosing its velocity
+#[derive(Clone, Copy, Debug)]
+pub struct AvoidanceNeighbour {
+	pub position		: Vec2,
+	pub velocity		: Vec2,
+	pub radius			: f32,
+	/// share of avoidance taken by the unit choosing its velocity, half if neighbour avoids it too and all of it if neighbour stands still
+	pub responsibility	: f32,
+}
+
+/// Edge of navmesh boundary, walkable side is on the right looking from `point` to `next_point`. Boundary edges make
+/// closed loops, directions of the edges before and after this one tell whether its ends are convex corners of the obstacle
+#[derive(Clone, Copy, Debug)]
+pub struct AvoidanceObstacle {
+	pub point				: Vec2,
+	pub next_point			: Vec2,
+	/// direction of the boundary edge ending at `point`
+	pub previous_direction	: Vec2,
+	/// direction of the boundary edge starting at `next_point`
+	pub next_direction		: Vec2,
+}
+
+impl AvoidanceObstacle {
+	pub fn direction(&self) -> Vec2 {
+		(self.next_point - self.point).normalize_or_zero()
+	}
+
+	pub fn distance_to(&self, point: Vec2) -> f32 {
+		let edge = self.next_point - self.point;
+		let t = ((point - self.point).dot(edge) / edge.length_squared().max(f32::EPSILON)).clamp(0.0, 1.0);
+
+		point.distance(self.point + t * edge)
+	}
+}
+
+/// Velocity the unit should move with, at most `max_speed` long. Obstacles are expected closest first, the ones
+/// already covered by closer ones are skipped. Unit keeps `obstacle_radius` away from them instead of its own radius,
+/// navmesh could be already shrunk by some of it
+pub fn orca_velocity(
+	position			: Vec2,
+	velocity			: Vec2,
+	radius				: f32,
+	preferred_velocity	: Vec2,
+	max_speed			: f32,
+	neighbours			: &[AvoidanceNeighbour],
+	obstacles			: &[AvoidanceObstacle],
+	obstacle_radius		: f32,
+	time_horizon		: f32,
+	time_step			: f32,
+) -> Vec2 {
+	let mut lines : Vec<OrcaLine> = Vec::new();
+
+	for obstacle in obstacles.iter() {
+		if let Some(line) = obstacle_line(position, velocity, obstacle_radius, obstacle, &lines, time_horizon) {
+			lines.push(line);
+		}
+	}
+
+	let obstacle_lines = lines.len();
+
+	lines.extend(neighbours.iter().map(|neighbour| orca_line(position, velocity, radius, neighbour, time_horizon, time_step)));
+
+	let mut result = Vec2::ZERO;
+
+	let line_fail = linear_program2(&lines, max_speed, preferred_velocity, false, &mut result);
+
+	if line_fail < lines.len() {
+		linear_program3(&lines, obstacle_lines, line_fail, max_speed, &mut result);
+	}
+
+	result
+}
+
+/// Velocities on the left side of the line (looking along direction) are allowed
+#[derive(Clone, Copy, Debug)]
+struct OrcaLine {
+	point		: Vec2,
+	direction	: Vec2,
+}
+
+fn orca_line(
+	position	: Vec2,
+	velocity	: Vec2,
+	radius		: f32,
+	neighbour	: &AvoidanceNeighbour,
+	time_horizon: f32,
+	time_step	: f32,
+) -> OrcaLine {
+	let relative_position = neighbour.position - position;
+	let relative_velocity = velocity - neighbour.velocity;
+
+	let distance_sq = relative_position.length_squared();
+
+	let combined_radius = radius + neighbour.radius;
+	let combined_radius_sq = combined_radius * combined_radius;
+
+	let (direction, u) = if distance_sq > combined_radius_sq {
+		// vector from cutoff center to relative velocity
+		let w = relative_velocity - relative_position / time_horizon;
+		let w_length_sq = w.length_squared();
+
+		let dot = w.dot(relative_position);
+
+		if dot < 0.0 && dot * dot > combined_radius_sq * w_length_sq {
+			// closest point is on the cutoff circle
+			let w_length = w_length_sq.sqrt();
+			let unit_w = w / w_length;
+
+			(Vec2::new(unit_w.y, -unit_w.x), (combined_radius / time_horizon - w_length) * unit_w)
+		} else {
+			// closest point is on one of the legs of velocity obstacle
+			let leg = (distance_sq - combined_radius_sq).sqrt();
+
+			let direction = if relative_position.perp_dot(w) > 0.0 {
+				Vec2::new(
+					relative_position.x * leg - relative_position.y * combined_radius,
+					relative_position.x * combined_radius + relative_position.y * leg,
+				) / distance_sq
+			} else {
+				-Vec2::new(
+					relative_position.x * leg + relative_position.y * combined_radius,
+					-relative_position.x * combined_radius + relative_position.y * leg,
+				) / distance_sq
+			};
+
+			(direction, relative_velocity.dot(direction) * direction - relative_velocity)
+		}
+	} else {
+		// already colliding, get apart within one time step
+		let w = relative_velocity - relative_position / time_step;
+		let w_length = w.length();
+		let unit_w = w.normalize_or_zero();
+
+		(Vec2::new(unit_w.y, -unit_w.x), (combined_radius / time_step - w_length) * unit_w)
+	};
+
+	OrcaLine { point : velocity + neighbour.responsibility * u, direction }
+}
+
+/// End of an obstacle edge: whether it's a convex corner and directions of boundary edges ending and starting at it
+#[derive(Clone, Copy, Debug)]
+struct ObstacleCorner {
+	point				: Vec2,
+	convex				: bool,
+	previous_direction	: Vec2,
+	direction			: Vec2,
+}
+
+/// Line forbidding velocities that reach the obstacle edge within time horizon, `None` if the unit is behind the edge
+/// or the edge is already covered by `previous_lines`
+fn obstacle_line(
+	position		: Vec2,
+	velocity		: Vec2,
+	radius			: f32,
+	obstacle		: &AvoidanceObstacle,
+	previous_lines	: &[OrcaLine],
+	time_horizon	: f32,
+) -> Option<OrcaLine> {
+	let direction = obstacle.direction();
+
+	let relative_position1 = obstacle.point - position;
+	let relative_position2 = obstacle.next_point - position;
+
+	// edges are seen only from their walkable side
+	if relative_position1.perp_dot(direction) >= 0.0 { return None }
+
+	let inverse_time_horizon = 1.0 / time_horizon;
+
+	let covered = previous_lines.iter().any(|line| {
+		(inverse_time_horizon * relative_position1 - line.point).perp_dot(line.direction) - inverse_time_horizon * radius >= -f32::EPSILON
+		&& (inverse_time_horizon * relative_position2 - line.point).perp_dot(line.direction) - inverse_time_horizon * radius >= -f32::EPSILON
+	});
+
+	if covered { return None }
+
+	let distance_sq1 = relative_position1.length_squared();
+	let distance_sq2 = relative_position2.length_squared();
+
+	let radius_sq = radius * radius;
+
+	let edge = obstacle.next_point - obstacle.point;
+	let s = (-relative_position1).dot(edge) / edge.length_squared();
+	let distance_sq_line = (-relative_position1 - s * edge).length_squared();
+
+	let start = ObstacleCorner {
+		point				: obstacle.point,
+		convex				: obstacle.previous_direction.perp_dot(direction) >= 0.0,
+		previous_direction	: obstacle.previous_direction,
+		direction,
+	};
+
+	let end = ObstacleCorner {
+		point				: obstacle.next_point,
+		convex				: direction.perp_dot(obstacle.next_direction) >= 0.0,
+		previous_direction	: direction,
+		direction			: obstacle.next_direction,
+	};
+
+	// already colliding, only velocities leading away from the edge are allowed
+	if s < 0.0 && distance_sq1 <= radius_sq {
+		return start.convex.then(|| OrcaLine { point : Vec2::ZERO, direction : Vec2::new(-relative_position1.y, relative_position1.x).normalize_or_zero() })
+	}
+
+	if s > 1.0 && distance_sq2 <= radius_sq {
+		// the next edge takes care of its start unless it's seen from this edge's side
+		let seen = end.convex && relative_position2.perp_dot(end.direction) >= 0.0;
+
+		return seen.then(|| OrcaLine { point : Vec2::ZERO, direction : Vec2::new(-relative_position2.y, relative_position2.x).normalize_or_zero() })
+	}
+
+	if (0.0 .. 1.0).contains(&s) && distance_sq_line <= radius_sq {
+		return Some(OrcaLine { point : Vec2::ZERO, direction : -direction })
+	}
+
+	// legs of velocity obstacle are tangents from the unit to the corners
+	let left_tangent = |relative_position: Vec2, distance_sq: f32| {
+		let leg = (distance_sq - radius_sq).sqrt();
+
+		Vec2::new(relative_position.x * leg - relative_position.y * radius, relative_position.x * radius + relative_position.y * leg) / distance_sq
+	};
+
+	let right_tangent = |relative_position: Vec2, distance_sq: f32| {
+		let leg = (distance_sq - radius_sq).sqrt();
+
+		Vec2::new(relative_position.x * leg + relative_position.y * radius, -relative_position.x * radius + relative_position.y * leg) / distance_sq
+	};
+
+	let (left, right, mut left_leg, mut right_leg, single_corner) = if s < 0.0 && distance_sq_line <= radius_sq {
+		// edge is seen obliquely, so its start alone makes velocity obstacle
+		if !start.convex { return None }
+
+		(start, start, left_tangent(relative_position1, distance_sq1), right_tangent(relative_position1, distance_sq1), true)
+	} else if s > 1.0 && distance_sq_line <= radius_sq {
+		if !end.convex { return None }
+
+		(end, end, left_tangent(relative_position2, distance_sq2), right_tangent(relative_position2, distance_sq2), true)
+	} else {
+		// leg at a concave corner goes along the edge
+		let left_leg = if start.convex { left_tangent(relative_position1, distance_sq1) } else { -direction };
+		let right_leg = if end.convex { right_tangent(relative_position2, distance_sq2) } else { direction };
+
+		(start, end, left_leg, right_leg, false)
+	};
+
+	// leg can't point into the neighbouring edge at a convex corner, neighbour's own line is used then.
+	// Velocity closest to such "foreign" leg is left for the neighbouring edge to constrain
+	let left_foreign = left.convex && left_leg.perp_dot(-left.previous_direction) >= 0.0;
+
+	if left_foreign {
+		left_leg = -left.previous_direction;
+	}
+
+	let right_foreign = right.convex && right_leg.perp_dot(right.direction) <= 0.0;
+
+	if right_foreign {
+		right_leg = right.direction;
+	}
+
+	let left_cutoff = inverse_time_horizon * (left.point - position);
+	let right_cutoff = inverse_time_horizon * (right.point - position);
+	let cutoff_vector = right_cutoff - left_cutoff;
+
+	let t = if single_corner { 0.5 } else { (velocity - left_cutoff).dot(cutoff_vector) / cutoff_vector.length_squared() };
+	let t_left = (velocity - left_cutoff).dot(left_leg);
+	let t_right = (velocity - right_cutoff).dot(right_leg);
+
+	// closest point is on the cutoff circle around one of the corners
+	if (t < 0.0 && t_left < 0.0) || (single_corner && t_left < 0.0 && t_right < 0.0) {
+		let unit_w = (velocity - left_cutoff).normalize_or_zero();
+
+		return Some(OrcaLine { point : left_cutoff + radius * inverse_time_horizon * unit_w, direction : Vec2::new(unit_w.y, -unit_w.x) })
+	}
+
+	if t > 1.0 && t_right < 0.0 {
+		let unit_w = (velocity - right_cutoff).normalize_or_zero();
+
+		return Some(OrcaLine { point : right_cutoff + radius * inverse_time_horizon * unit_w, direction : Vec2::new(unit_w.y, -unit_w.x) })
+	}
+
+	// otherwise it's on the cutoff line or one of the legs, whichever is closest to velocity
+	let distance_sq_cutoff = if t < 0.0 || t > 1.0 || single_corner { f32::INFINITY } else { (velocity - (left_cutoff + t * cutoff_vector)).length_squared() };
+	let distance_sq_left = if t_left < 0.0 { f32::INFINITY } else { (velocity - (left_cutoff + t_left * left_leg)).length_squared() };
+	let distance_sq_right = if t_right < 0.0 { f32::INFINITY } else { (velocity - (right_cutoff + t_right * right_leg)).length_squared() };
+
+	// line through cutoff point moved away from the obstacle by radius
+	let line = |cutoff: Vec2, direction: Vec2| OrcaLine { point : cutoff + radius * inverse_time_horizon * Vec2::new(-direction.y, direction.x), direction };
+
+	if distance_sq_cutoff <= distance_sq_left && distance_sq_cutoff <= distance_sq_right {
+		Some(line(left_cutoff, -left.direction))
+	} else if distance_sq_left <= distance_sq_right {
+		(!left_foreign).then(|| line(left_cutoff, left_leg))
+	} else {
+		(!right_foreign).then(|| line(right_cutoff, -right_leg))
+	}
+}
+
+/// Best velocity on the line `line_index` that satisfies all lines before it
+fn linear_program1(
+	lines				: &[OrcaLine],
+	line_index			: usize,
+	radius				: f32,
+	optimal_velocity	: Vec2,
+	direction_optimal	: bool,
+	result				: &mut Vec2,
+) -> bool {
+	let line = lines[line_index];
+
+	let dot = line.point.dot(line.direction);
+	let discriminant = dot * dot + radius * radius - line.point.length_squared();
+
+	// max speed circle fully invalidates this line
+	if discriminant < 0.0 { return false }
+
+	let discriminant_sqrt = discriminant.sqrt();
+
+	let mut t_left = -dot - discriminant_sqrt;
+	let mut t_right = -dot + discriminant_sqrt;
+
+	for other in lines[.. line_index].iter() {
+		let denominator = line.direction.perp_dot(other.direction);
+		let numerator = other.direction.perp_dot(line.point - other.point);
+
+		if denominator.abs() <= f32::EPSILON {
+			// lines are parallel
+			if numerator < 0.0 { return false }
+
+			continue
+		}
+
+		let t = numerator / denominator;
+
+		if denominator >= 0.0 {
+			t_right = t_right.min(t);
+		} else {
+			t_left = t_left.max(t);
+		}
+
+		if t_left > t_right { return false }
+	}
+
+	let t = if direction_optimal {
+		if optimal_velocity.dot(line.direction) > 0.0 { t_right } else { t_left }
+	} else {
+		line.direction.dot(optimal_velocity - line.point).max(t_left).min(t_right)
+	};
+
+	*result = line.point + t * line.direction;
+
+	true
+}
+
+/// Velocity closest to optimal one that satisfies all lines. Returns index of the first line that couldn't be satisfied
+/// or number of lines if all of them are
+fn linear_program2(
+	lines				: &[OrcaLine],
+	radius				: f32,
+	optimal_velocity	: Vec2,
+	direction_optimal	: bool,
+	result				: &mut Vec2,
+) -> usize {
+	*result = if direction_optimal {
+		// optimal velocity is a unit direction in this case
+		optimal_velocity * radius
+	} else if optimal_velocity.length_squared() > radius * radius {
+		optimal_velocity.normalize() * radius
+	} else {
+		optimal_velocity
+	};
+
+	for (line_index, line) in lines.iter().enumerate() {
+		if line.direction.perp_dot(line.point - *result) <= 0.0 { continue }
+
+		// result doesn't satisfy this line, so best velocity is on it
+		let previous_result = *result;
+
+		if !linear_program1(lines, line_index, radius, optimal_velocity, direction_optimal, result) {
+			*result = previous_result;
+
+			return line_index;
+		}
+	}
+
+	lines.len()
+}
+
+/// Velocity that minimizes the largest violation of lines starting from `begin_line`, used when units are too dense.
+/// First `obstacle_lines` lines are never violated
+fn linear_program3(
+	lines			: &[OrcaLine],
+	obstacle_lines	: usize,
+	begin_line		: usize,
+	radius			: f32,
+	result			: &mut Vec2,
+) {
+	let mut distance = 0.0;
+
+	for line_index in begin_line .. lines.len() {
+		let line = lines[line_index];
+
+		if line.direction.perp_dot(line.point - *result) <= distance { continue }
+
+		// lines in between this one and each previous one, velocity on the right side of all of them violates this line the least
+		let projected_lines = lines[.. obstacle_lines].iter().copied()
+			.chain(lines[obstacle_lines .. line_index.max(obstacle_lines)].iter().filter_map(|other| {
+				let determinant = line.direction.perp_dot(other.direction);
+
+				let point = if determinant.abs() <= f32::EPSILON {
+					// parallel lines pointing the same way don't limit anything
+					if line.direction.dot(other.direction) > 0.0 { return None }
+
+					(line.point + other.point) / 2.0
+				} else {
+					line.point + (other.direction.perp_dot(line.point - other.point) / determinant) * line.direction
+				};
+
+				Some(OrcaLine { point, direction : (other.direction - line.direction).normalize_or_zero() })
+			}))
+			.collect::<Vec<OrcaLine>>();
+
+		let previous_result = *result;
+
+		if linear_program2(&projected_lines, radius, Vec2::new(-line.direction.y, line.direction.x), true, result) < projected_lines.len() {
+			// should never happen, result is already in the feasible region of this program
+			*result = previous_result;
+		}
+
+		distance = line.direction.perp_dot(line.point - *result);
+	}
+}
@@ -0,0 +1,203 @@
+use bevy :: prelude :: *;
+
+use polyanya :: Mesh as PolyanyaMesh;
+
+use super :: PolyanyaResource;
+
+use super :: navmesh :: { NAVMESH_UNIT_RADIUS, level_navmesh, boundary_obstacles };
+
+use super :: avoidance :: AvoidanceObstacle;
+
+use crate :: map :: { Level, Terrain };
+
+/// Point this far below level's deck or ramp still stands on it, raypick and npc positions are never exactly on the surface
+pub const LEVEL_SURFACE_TOLERANCE : f32 = 0.5;
+
+/// Ramp links are put this far from the foot of the ramp, so that both ends are inside of their navmeshes and not on the edge
+const RAMP_LINK_MARGIN : f32 = 0.1;
+
+/// Navmesh layer the point is on, levels are indexed the same as `PolyanyaResource::levels`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NavLayer {
+	Ground,
+	Level(usize),
+}
+
+/// Navmesh of level's deck and ramps with links to the ground navmesh at the foot of every ramp
+pub struct LevelNavmesh {
+	pub level		: Level,
+	pub mesh		: PolyanyaMesh,
+	pub boundary	: Vec<AvoidanceObstacle>,
+	pub links		: Vec<RampLink>,
+}
+
+/// Pair of points on both sides of the foot of the ramp, npc walks straight from one to the other
+#[derive(Clone, Copy, Debug)]
+pub struct RampLink {
+	pub on_level	: Vec2,
+	/// ground under ramp is blocked and inflated by unit radius, so this point is just outside of it
+	pub on_ground	: Vec2,
+}
+
+/// Path that can go through several layers, starting point is not included same as in polyanya paths
+#[derive(Clone, Default, Debug)]
+pub struct NavPath {
+	pub path		: Vec<Vec2>,
+	pub length		: f32,
+}
+
+impl LevelNavmesh {
+	pub fn new(level: Level) -> Option<Self> {
+		let mesh = level_navmesh(&level)?;
+
+		let links = level.ramps.iter()
+			.map(|ramp| RampLink {
+				on_level	: ramp.foot + ramp.direction() * RAMP_LINK_MARGIN,
+				on_ground	: ramp.foot - ramp.direction() * (NAVMESH_UNIT_RADIUS + RAMP_LINK_MARGIN),
+			})
+			.collect();
+
+		let boundary = boundary_obstacles(&mesh);
+
+		Some(Self { level, mesh, boundary, links })
+	}
+}
+
+impl PolyanyaResource {
+	pub fn new(mesh: PolyanyaMesh, levels: Vec<LevelNavmesh>) -> Self {
+		let boundary = boundary_obstacles(&mesh);
+
+		Self { mesh, boundary, levels }
+	}
+
+	/// Replaces ground navmesh with rebuilt one, its boundary goes with it
+	pub fn set_ground_mesh(&mut self, mesh: PolyanyaMesh) {
+		self.boundary = boundary_obstacles(&mesh);
+		self.mesh = mesh;
+	}
+
+	/// First level whose deck or ramp is under the point, ground otherwise
+	pub fn layer_at(&self, point: Vec3) -> NavLayer {
+		self.levels.iter()
+			.position(|level_navmesh| {
+				level_navmesh.level.surface_height(point.xz()).is_some_and(|height| point.y > height - LEVEL_SURFACE_TOLERANCE)
+			})
+			.map_or(NavLayer::Ground, NavLayer::Level)
+	}
+
+	pub fn layer_mesh(&self, layer: NavLayer) -> &PolyanyaMesh {
+		match layer {
+			NavLayer::Ground => &self.mesh,
+			NavLayer::Level(level_index) => &self.levels[level_index].mesh,
+		}
+	}
+
+	pub fn layer_boundary(&self, layer: NavLayer) -> &[AvoidanceObstacle] {
+		match layer {
+			NavLayer::Ground => &self.boundary,
+			NavLayer::Level(level_index) => &self.levels[level_index].boundary,
+		}
+	}
+
+	/// Ground navmesh is shrunk by unit radius already, level navmeshes reach the very edges of decks and ramps
+	pub fn layer_inflation(&self, layer: NavLayer) -> f32 {
+		match layer {
+			NavLayer::Ground => NAVMESH_UNIT_RADIUS,
+			NavLayer::Level(_) => 0.0,
+		}
+	}
+
+	/// Whether point is on navmesh of the layer it's on, so points under a plateau aren't walkable while the deck above them is
+	pub fn point_in_mesh(&self, point: Vec3) -> bool {
+		self.layer_mesh(self.layer_at(point)).point_in_mesh(point.xz())
+	}
+
+	/// Height of whatever npc at point walks on, level's deck or ramp or terrain
+	pub fn ground_height(&self, point: Vec3, terrain: &Terrain) -> f32 {
+		let NavLayer::Level(level_index) = self.layer_at(point) else { return terrain.height_at(point.xz()) };
+
+		self.levels[level_index].level.surface_height(point.xz()).unwrap_or_else(|| terrain.height_at(point.xz()))
+	}
+
+	/// Height of the highest surface at point on XZ plane, the one that is seen from above
+	pub fn top_height(&self, point: Vec2, terrain: &Terrain) -> f32 {
+		self.levels.iter()
+			.filter_map(|level_navmesh| level_navmesh.level.surface_height(point))
+			.fold(terrain.height_at(point), f32::max)
+	}
+
+	/// Shortest path between points on any layers. Levels are connected to the ground only through their ramps,
+	/// so path from one layer to another goes down a ramp to the ground, across it and up a ramp of the other level
+	pub fn path(&self, from: Vec3, to: Vec3) -> Option<NavPath> {
+		let (from_layer, to_layer) = (self.layer_at(from), self.layer_at(to));
+
+		if from_layer == to_layer {
+			return self.layer_path(from_layer, from.xz(), to.xz())
+		}
+
+		let ways_down = self.ways_down(from_layer, from.xz());
+		let ways_up = self.ways_up(to_layer, to.xz());
+
+		let mut shortest : Option<NavPath> = None;
+
+		for (down_point, down) in ways_down.iter() {
+			for (up_point, up) in ways_up.iter() {
+				let Some(across) = self.mesh.path(*down_point, *up_point) else { continue };
+
+				let length = down.length + across.length + up.length;
+
+				if shortest.as_ref().is_some_and(|shortest| shortest.length <= length) { continue }
+
+				shortest = Some(NavPath {
+					path : down.path.iter().chain(across.path.iter()).chain(up.path.iter()).copied().collect(),
+					length,
+				});
+			}
+		}
+
+		shortest
+	}
+
+	fn layer_path(&self, layer: NavLayer, from: Vec2, to: Vec2) -> Option<NavPath> {
+		let path = self.layer_mesh(layer).path(from, to)?;
+
+		Some(NavPath { path : path.path, length : path.length })
+	}
+
+	/// Every way from point down to the ground: point on the ground where it ends and path to it
+	fn ways_down(&self, layer: NavLayer, point: Vec2) -> Vec<(Vec2, NavPath)> {
+		let NavLayer::Level(level_index) = layer else { return vec![(point, NavPath::default())] };
+
+		self.levels[level_index].links.iter()
+			.filter_map(|link| {
+				let mut down = self.layer_path(layer, point, link.on_level)?;
+
+				down.path.push(link.on_ground);
+				down.length += link.on_level.distance(link.on_ground);
+
+				Some((link.on_ground, down))
+			})
+			.collect()
+	}
+
+	/// Every way from the ground up to point: point on the ground where it starts and path from it
+	fn ways_up(&self, layer: NavLayer, point: Vec2) -> Vec<(Vec2, NavPath)> {
+		let NavLayer::Level(level_index) = layer else { return vec![(point, NavPath::default())] };
+
+		self.levels[level_index].links.iter()
+			.filter_map(|link| {
+				let on_level = self.layer_path(layer, link.on_level, point)?;
+
+				let mut up = NavPath {
+					path : vec![link.on_level],
+					length : link.on_ground.distance(link.on_level),
+				};
+
+				up.path.extend(on_level.path);
+				up.length += on_level.length;
+
+				Some((link.on_ground, up))
+			})
+			.collect()
+	}
+}
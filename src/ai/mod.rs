@@ -2,34 +2,80 @@ use bevy :: prelude :: *;
 
 use polyanya :: Mesh as PolyanyaMesh;
 
-use super :: game :: GameState;
+use std :: collections :: VecDeque;
 
-use super :: resource_collection :: ResourceCollectionStage;
+use super :: game :: {
+	GameState,
+	InputContext,
+	MainEntities,
+};
 
+use super :: resource_collection :: { self, ResourceCollectionStage };
+
+pub mod avoidance;
+pub mod navmesh;
+pub mod layers;
 pub mod systems;
 
 mod utils;
 use utils :: *;
 
+#[cfg(test)]
+mod tests;
+
 pub struct AiPlugin;
 
 impl Plugin for AiPlugin {
 	fn build(&self, app: &mut App) {
 		app
+			.register_type::<NpcMovable>()
+			.register_type::<NpcTaskMove>()
+			.register_type::<MovePath>()
+			.register_type::<NpcTaskResourceCollection>()
+			.register_type::<NpcTaskConstruction>()
+			.register_type::<RallyPoint>()
+			.register_type::<NpcCommand>()
+			.register_type::<NpcCommandQueue>()
+
+			.add_event::<NpcCommandTarget>()
+
 			.add_systems(Update, (
 				systems::update_navmesh_obstacles,
+				systems::movable_steering.before(systems::movable_update),
 				systems::movable_update,
-				systems::movable_collision_avoidance,
-				systems::give_path_on_click,
-				systems::click_point_draw,
-				systems::selected_path_draw,
-				systems::spawn_svin_at_raypicked_base,
 				systems::spawn_stresstest,
 				systems::spawn_task_resource_collection,
+				systems::retarget_task_resource_collection
+					.after(resource_collection::systems::resource_collecting_control)
+					.before(systems::update_task_resource_collection),
 				systems::update_task_resource_collection,
-				// display_path,
+				systems::update_task_construction,
+				systems::update_task_return_cargo,
+				systems::update_task_patrol,
+				systems::update_task_follow,
+				systems::update_command_queue.before(systems::start_task_rally),
+				systems::start_task_rally,
 			).run_if(in_state(GameState::Main)))
 
+			// player commands and visualization, not available in headless mode
+			.add_systems(Update, (
+				systems::update_navmesh_wireframe.after(systems::update_navmesh_obstacles),
+				// orders aren't given while build menu is open or building is being placed
+				systems::command_target_on_click
+					.before(systems::give_path_to_command_target)
+					.before(systems::rally_point_to_command_target)
+					.run_if(in_state(InputContext::Gameplay)),
+				systems::give_path_to_command_target,
+				systems::npc_command_on_key.run_if(in_state(InputContext::Gameplay)),
+				systems::click_point_draw,
+				systems::selected_path_draw,
+				systems::command_queue_draw,
+				systems::spawn_svin_at_raypicked_base,
+				systems::rally_point_to_command_target,
+				systems::rally_point_draw,
+				// display_path,
+			).run_if(in_state(GameState::Main)).run_if(resource_exists::<MainEntities>))
+
 			.add_systems(Update, (
 				systems::collect_navmesh_obstacles,
 				systems::collect_spawn_locators,
@@ -39,19 +85,24 @@ impl Plugin for AiPlugin {
 	}
 }
 
+/// Ground navmesh rebuilt as obstacles change and one navmesh per map level that never changes
 #[derive(Resource)]
 pub struct PolyanyaResource {
-	pub mesh : PolyanyaMesh,
+	pub mesh	: PolyanyaMesh,
+	/// boundary of ground navmesh for local avoidance, see `set_ground_mesh`
+	pub boundary: Vec<avoidance::AvoidanceObstacle>,
+	pub levels	: Vec<layers::LevelNavmesh>,
 }
 
 #[derive(Component)]
 pub struct NavmeshWireframe;
 
+/// Every collider of this entity and its descendants blocks navmesh with its footprint on the ground
 #[derive(Component)]
 pub struct NavmeshObstacleContainer;
 
 #[derive(Component)]
-pub struct NavmeshObstacleAabb;
+pub struct NavmeshObstacleCollider;
 
 #[derive(Component)]
 pub struct Occupied;
@@ -79,10 +130,12 @@ pub struct NpcSpawner;
 #[derive(Component)]
 pub struct NpcInteractable;
 
-#[derive(Component)]
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct NpcMovable;
 
-#[derive(Component, Default)]
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct NpcTaskMove {
 	speed : f32,
 }
@@ -90,7 +143,18 @@ pub struct NpcTaskMove {
 #[derive(Component)]
 pub struct NpcTaskMoveFinished;
 
+/// Velocity on XZ plane picked by local avoidance for this frame. `movable_update` moves npc with it
+/// and other npcs expect npc to keep moving with it when they choose their own
 #[derive(Component, Default)]
+pub struct Steering {
+	pub velocity	: Vec2,
+}
+
+/// Npc steered away from its path by avoidance counts waypoint as reached this close to it, final waypoint still has to be reached exactly
+pub const WAYPOINT_TOLERANCE : f32 = 0.25;
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct MovePath {
 	current			: Vec3,
 	next			: Vec<Vec3>,
@@ -110,14 +174,114 @@ impl MovePath {
 			..default()
 		}
 	}
+
+	/// Locators are recreated with their scene after loading a save, so restored path can't point at the old one
+	pub fn forget_target_entity(&mut self) {
+		self.target_entity = None;
+	}
 }
 
-#[derive(Component, Default)]
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct NpcTaskResourceCollection {
 	pub stage					: ResourceCollectionStage,
 	pub resource_entity			: Option<Entity>,
 }
 
+/// Worker walks to an interact locator of construction site and keeps drilling it until building is finished
+#[derive(Component, Reflect)]
+#[reflect(Component)]
+pub struct NpcTaskConstruction {
+	pub building_entity			: Entity,
+}
+
+/// Where units produced by a base building go. Set by right clicking with the base selected
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct RallyPoint {
+	pub position		: Vec3,
+	/// deposit to start mining at or construction site to start building right away
+	pub target_entity	: Option<Entity>,
+}
+
+/// Order to go to a rally point. Freshly spawned units get it from their base and commander gives it to any of its units,
+/// on the next update it turns into a path and, if rally point targets a deposit or a construction site, into a task
+#[derive(Component, Deref)]
+pub struct NpcTaskRally(pub RallyPoint);
+
+/// What player right clicked with units or bases selected, in the world or on minimap. Selected units get a path or a task
+/// from it and selected bases get a rally point
+#[derive(Event, Clone, Copy, Debug)]
+pub struct NpcCommandTarget {
+	/// `None` when clicked on minimap
+	pub entity		: Option<Entity>,
+	pub position	: Vec3,
+}
+
+/// One order from `NpcCommandQueue`
+#[derive(Reflect, PartialEq, Clone, Copy, Debug)]
+pub enum NpcCommand {
+	Move(Vec3),
+	/// keeps mining until deposit is depleted, or only until the next delivery if more commands are queued after it
+	Mine(Entity),
+	/// drills construction site until it's finished
+	Build(Entity),
+	ReturnCargo,
+	/// patrol between where unit is when command starts and this point, consecutive patrol commands make one route
+	Patrol(Vec3),
+	Follow(Entity),
+}
+
+/// Orders queued with shift + right click. Next one is started once unit has no task and no path left,
+/// plain right click clears the queue
+#[derive(Component, Reflect, Default, Deref, DerefMut)]
+#[reflect(Component)]
+pub struct NpcCommandQueue(pub VecDeque<NpcCommand>);
+
+/// Unit carries whatever it has collected to the nearest base of its faction and stays there once it's delivered
+#[derive(Component)]
+pub struct NpcTaskReturnCargo;
+
+/// Unit keeps walking after target entity (another unit or player) until given another order
+#[derive(Component)]
+pub struct NpcTaskFollow {
+	pub target_entity	: Entity,
+	/// where target was when current path was made
+	pub planned_for		: Option<Vec3>,
+}
+
+impl NpcTaskFollow {
+	pub fn new(target_entity: Entity) -> Self {
+		Self { target_entity, planned_for : None }
+	}
+}
+
+/// Follower stops this close to its target
+pub const FOLLOW_DISTANCE : f32 = 3.0;
+
+/// Follower's path is made again only after target moved this far from where path was made for
+pub const FOLLOW_REPLAN_DISTANCE : f32 = 1.5;
+
+/// Unit was told to hold position: it stands still and doesn't start queued commands until player gives it a new order.
+/// Collision avoidance only ever moves npcs that walk somewhere, so others step around it
+#[derive(Component)]
+pub struct HoldPosition;
+
+/// Unit walks route points one after another and starts over from the first one after the last, until given another order
+#[derive(Component)]
+pub struct NpcTaskPatrol {
+	pub points		: Vec<Vec3>,
+	/// index of the point unit walks to now
+	pub next		: usize,
+}
+
+impl NpcTaskPatrol {
+	/// First point is where unit is, so it starts with the second one
+	pub fn new(points: Vec<Vec3>) -> Self {
+		Self { next : if points.len() > 1 { 1 } else { 0 }, points }
+	}
+}
+
 #[derive(Component)]
 pub struct NpcSpawnTaskResourceCollection {
 	pub queue		: Vec::<(usize, Entity)>,
@@ -0,0 +1,596 @@
+use bevy :: {
+	prelude :: *,
+	utils :: { HashMap, HashSet },
+	tasks :: {
+		AsyncComputeTaskPool,
+		Task,
+		block_on,
+		futures_lite :: future,
+	},
+};
+
+use polyanya :: {
+	Mesh as PolyanyaMesh,
+	Polygon as PolyanyaPolygon,
+	Vertex as PolyanyaVertex,
+	Triangulation,
+};
+
+use bevy_rapier3d :: {
+	utils :: transform_to_iso,
+	parry :: {
+		math :: { Isometry, Point },
+		shape :: { Shape, TypedShape },
+	},
+};
+
+use crate :: map :: { GroundBounds, Level };
+
+use super :: avoidance :: AvoidanceObstacle;
+
+/// Side of a square navmesh tile in meters, tiles at the far edges of the ground can be smaller
+pub const NAVMESH_TILE_SIZE : f32 = 100.0;
+
+/// Obstacles are inflated by this much so that paths keep units' colliders off them
+pub const NAVMESH_UNIT_RADIUS : f32 = 0.4;
+
+/// Rebuild starts only once obstacles stopped changing for this many seconds,
+/// so a batch of buildings whose scenes are loaded one by one causes only one rebuild
+pub const NAVMESH_REBUILD_DELAY : f32 = 0.25;
+
+/// Footprint vertex is dropped if it's closer than this to the line through its neighbours, and so is every vertex that
+/// doesn't fit into `NAVMESH_FOOTPRINT_MAX_VERTICES`, dense meshes like crystals end up with a handful of edges
+pub const NAVMESH_FOOTPRINT_TOLERANCE : f32 = 0.15;
+
+pub const NAVMESH_FOOTPRINT_MAX_VERTICES : usize = 12;
+
+/// Vertices closer than this are treated as the same vertex when tiles are stitched
+const STITCH_PRECISION : f32 = 0.001;
+
+/// Triangulated tile, polygons index into its own vertices
+#[derive(Clone, Default, Debug)]
+pub struct TileMesh {
+	pub vertices	: Vec<Vec2>,
+	pub polygons	: Vec<Vec<u32>>,
+}
+
+/// Ground split into square tiles that are triangulated separately and stitched into one polyanya mesh. Obstacle change
+/// marks only tiles under its old and new footprint as dirty, `update_navmesh_obstacles` rebuilds them in background
+#[derive(Resource)]
+pub struct NavmeshTiles {
+	ground			: GroundBounds,
+	tile_size		: f32,
+	columns			: usize,
+	rows			: usize,
+	/// footprint of every obstacle on the ground, already inflated by unit radius
+	obstacles		: HashMap<Entity, Vec<Vec2>>,
+	/// areas that never change like steep terrain, inflated by unit radius too
+	blocked_areas	: Vec<Vec<Vec2>>,
+	/// last built mesh of every tile, row by row
+	meshes			: Vec<TileMesh>,
+	dirty			: HashSet<usize>,
+	/// seconds since obstacles changed last time
+	quiet_time		: f32,
+	rebuild			: Option<Task<RebuildResult>>,
+}
+
+/// Rebuilt tiles and all tiles stitched together, `None` if stitching failed
+type RebuildResult = (Vec<(usize, TileMesh)>, Option<PolyanyaMesh>);
+
+/// Everything a rebuild needs, moved into a background task so that it doesn't borrow `NavmeshTiles`
+struct TileRebuild {
+	/// tile index, its bounds and obstacles clipped to them
+	jobs			: Vec<(usize, Rect, Vec<Vec<Vec2>>)>,
+	meshes			: Vec<TileMesh>,
+}
+
+impl TileRebuild {
+	fn run(mut self) -> RebuildResult {
+		let rebuilt = self.jobs.into_iter()
+			.map(|(tile_index, bounds, obstacles)| (tile_index, build_tile(bounds, obstacles)))
+			.collect::<Vec<(usize, TileMesh)>>();
+
+		for (tile_index, tile_mesh) in rebuilt.iter() {
+			self.meshes[*tile_index] = tile_mesh.clone();
+		}
+
+		let navmesh = stitch_tiles(&self.meshes);
+
+		(rebuilt, navmesh)
+	}
+}
+
+impl NavmeshTiles {
+	/// All tiles start dirty, the first rebuild triangulates the whole ground
+	pub fn new(ground: &GroundBounds, tile_size: f32) -> Self {
+		let size = ground.size();
+
+		let columns = (size.x / tile_size).ceil().max(1.0) as usize;
+		let rows = (size.y / tile_size).ceil().max(1.0) as usize;
+
+		Self {
+			ground			: *ground,
+			tile_size,
+			columns,
+			rows,
+			obstacles		: HashMap::new(),
+			blocked_areas	: Vec::new(),
+			meshes			: vec![TileMesh::default(); columns * rows],
+			dirty			: (0 .. columns * rows).collect(),
+			quiet_time		: 0.0,
+			rebuild			: None,
+		}
+	}
+
+	pub fn tile_bounds(&self, tile_index: usize) -> Rect {
+		let column = tile_index % self.columns;
+		let row = tile_index / self.columns;
+
+		let min = self.ground.min + Vec2::new(column as f32, row as f32) * self.tile_size;
+		let max = (min + Vec2::splat(self.tile_size)).min(self.ground.max);
+
+		Rect::from_corners(min, max)
+	}
+
+	/// Tiles that bounding box of the footprint overlaps
+	fn tiles_under(&self, footprint: &[Vec2]) -> Vec<usize> {
+		if footprint.is_empty() { return Vec::new() }
+
+		let min = footprint.iter().fold(Vec2::MAX, |min, point| min.min(*point));
+		let max = footprint.iter().fold(Vec2::MIN, |max, point| max.max(*point));
+
+		let to_cell = |point: Vec2| ((point - self.ground.min) / self.tile_size).floor();
+
+		let (min_cell, max_cell) = (to_cell(min), to_cell(max));
+
+		let columns = (min_cell.x.max(0.0) as usize) ..= (max_cell.x.max(0.0) as usize).min(self.columns - 1);
+		let rows = (min_cell.y.max(0.0) as usize) ..= (max_cell.y.max(0.0) as usize).min(self.rows - 1);
+
+		rows.flat_map(|row| columns.clone().map(move |column| row * self.columns + column)).collect()
+	}
+
+	/// Area that stays blocked for the whole game, e.g. slope too steep to walk
+	pub fn block_area(&mut self, area: Rect) {
+		let footprint = vec![
+			Vec2::new(area.min.x - NAVMESH_UNIT_RADIUS, area.min.y - NAVMESH_UNIT_RADIUS),
+			Vec2::new(area.min.x - NAVMESH_UNIT_RADIUS, area.max.y + NAVMESH_UNIT_RADIUS),
+			Vec2::new(area.max.x + NAVMESH_UNIT_RADIUS, area.max.y + NAVMESH_UNIT_RADIUS),
+			Vec2::new(area.max.x + NAVMESH_UNIT_RADIUS, area.min.y - NAVMESH_UNIT_RADIUS),
+		];
+
+		self.dirty.extend(self.tiles_under(&footprint));
+
+		self.blocked_areas.push(footprint);
+	}
+
+	pub fn set_obstacle(&mut self, obstacle_entity: Entity, footprint: Vec<Vec2>) {
+		self.remove_obstacle(obstacle_entity);
+
+		self.dirty.extend(self.tiles_under(&footprint));
+
+		self.obstacles.insert(obstacle_entity, footprint);
+
+		self.quiet_time = 0.0;
+	}
+
+	pub fn remove_obstacle(&mut self, obstacle_entity: Entity) {
+		let Some(footprint) = self.obstacles.remove(&obstacle_entity) else { return };
+
+		self.dirty.extend(self.tiles_under(&footprint));
+
+		self.quiet_time = 0.0;
+	}
+
+	pub fn wait(&mut self, seconds: f32) {
+		self.quiet_time += seconds;
+	}
+
+	/// Rebuild of dirty tiles in background once obstacles stopped changing and previous rebuild is done
+	pub fn start_rebuild(&mut self) {
+		if self.rebuild.is_some() || self.dirty.is_empty() || self.quiet_time < NAVMESH_REBUILD_DELAY { return }
+
+		let rebuild = self.take_dirty();
+
+		self.rebuild = Some(AsyncComputeTaskPool::get().spawn(async move { rebuild.run() }));
+	}
+
+	/// Stitched navmesh once background rebuild is finished
+	pub fn poll_rebuild(&mut self) -> Option<PolyanyaMesh> {
+		let task = self.rebuild.as_mut()?;
+
+		let (rebuilt, navmesh) = block_on(future::poll_once(task))?;
+
+		self.rebuild = None;
+
+		self.apply(rebuilt, navmesh)
+	}
+
+	/// Rebuilds dirty tiles right away, doesn't wait for background rebuild if one is running
+	pub fn rebuild_now(&mut self) -> Option<PolyanyaMesh> {
+		let (rebuilt, navmesh) = self.take_dirty().run();
+
+		self.apply(rebuilt, navmesh)
+	}
+
+	fn take_dirty(&mut self) -> TileRebuild {
+		let dirty = std::mem::take(&mut self.dirty);
+
+		let jobs = dirty.into_iter()
+			.map(|tile_index| {
+				let bounds = self.tile_bounds(tile_index);
+
+				let obstacles = self.obstacles.values()
+					.chain(self.blocked_areas.iter())
+					.filter_map(|footprint| clip_to_rect(footprint, bounds))
+					.collect();
+
+				(tile_index, bounds, obstacles)
+			})
+			.collect();
+
+		TileRebuild { jobs, meshes : self.meshes.clone() }
+	}
+
+	fn apply(&mut self, rebuilt: Vec<(usize, TileMesh)>, navmesh: Option<PolyanyaMesh>) -> Option<PolyanyaMesh> {
+		for (tile_index, tile_mesh) in rebuilt {
+			self.meshes[tile_index] = tile_mesh;
+		}
+
+		if navmesh.is_none() {
+			println!("Error: stitching navmesh tiles failed! keeping previous navmesh");
+		}
+
+		navmesh
+	}
+}
+
+/// Navmesh of one tile with obstacles already clipped to its bounds. Tile that is fully blocked has no polygons
+fn build_tile(bounds: Rect, obstacles: Vec<Vec<Vec2>>) -> TileMesh {
+	triangulate(&[
+		Vec2::new(bounds.min.x, bounds.min.y),
+		Vec2::new(bounds.min.x, bounds.max.y),
+		Vec2::new(bounds.max.x, bounds.max.y),
+		Vec2::new(bounds.max.x, bounds.min.y),
+	], obstacles)
+}
+
+/// Navmesh of level's deck and every ramp, triangulated separately and stitched where ramps touch the deck.
+/// Level navmesh has no obstacles, nothing can be built on levels
+pub fn level_navmesh(level: &Level) -> Option<PolyanyaMesh> {
+	let tiles = std::iter::once(level.deck_outline())
+		.chain(level.ramps.iter().map(|ramp| ramp.outline()))
+		.map(|outline| triangulate(&outline, Vec::new()))
+		.collect::<Vec<TileMesh>>();
+
+	stitch_tiles(&tiles)
+}
+
+fn triangulate(outer_edges: &[Vec2], obstacles: Vec<Vec<Vec2>>) -> TileMesh {
+	let mut triangulation = Triangulation::from_outer_edges(outer_edges);
+
+	triangulation.add_obstacles(obstacles);
+
+	triangulation.merge_overlapping_obstacles();
+
+	let Some(navmesh) = triangulation.as_navmesh() else { return TileMesh::default() };
+
+	TileMesh {
+		vertices : navmesh.vertices.iter().map(|vertex| vertex.coords).collect(),
+		polygons : navmesh.polygons.iter().map(|polygon| polygon.vertices.clone()).collect(),
+	}
+}
+
+/// Merges tiles into one mesh. Neighbouring tiles get the same vertices on their shared border because obstacles are clipped
+/// to both of them the same way, so polygons on both sides of the border are connected through these shared vertices
+pub fn stitch_tiles(tiles: &[TileMesh]) -> Option<PolyanyaMesh> {
+	let mut vertices : Vec<Vec2> = Vec::new();
+	let mut vertex_indices : HashMap<(i64, i64), u32> = HashMap::new();
+	let mut polygons : Vec<Vec<u32>> = Vec::new();
+
+	for tile in tiles.iter() {
+		let remap = tile.vertices.iter()
+			.map(|coords| {
+				let key = ((coords.x / STITCH_PRECISION).round() as i64, (coords.y / STITCH_PRECISION).round() as i64);
+
+				*vertex_indices.entry(key).or_insert_with(|| {
+					vertices.push(*coords);
+					(vertices.len() - 1) as u32
+				})
+			})
+			.collect::<Vec<u32>>();
+
+		polygons.extend(tile.polygons.iter().map(|polygon| polygon.iter().map(|index| remap[*index as usize]).collect::<Vec<u32>>()));
+	}
+
+	if polygons.is_empty() { return None }
+
+	// polygon on the other side of every edge is the one that has the same edge in opposite direction
+	let edges = polygons.iter().enumerate()
+		.flat_map(|(polygon_index, polygon)| {
+			(0 .. polygon.len()).map(move |i| ((polygon[i], polygon[(i + 1) % polygon.len()]), polygon_index))
+		})
+		.collect::<HashMap<(u32, u32), usize>>();
+
+	// polygons are all wound the same way, polyanya expects polygons around every vertex in that order too
+	let counterclockwise = signed_area(&polygons[0].iter().map(|index| vertices[*index as usize]).collect::<Vec<Vec2>>()) > 0.0;
+
+	// every polygon around a vertex with the previous and the next vertex of that polygon
+	let mut around_vertex : Vec<Vec<(usize, u32, u32)>> = vec![Vec::new(); vertices.len()];
+
+	for (polygon_index, polygon) in polygons.iter().enumerate() {
+		for (i, vertex_index) in polygon.iter().enumerate() {
+			let previous = polygon[(i + polygon.len() - 1) % polygon.len()];
+			let next = polygon[(i + 1) % polygon.len()];
+
+			around_vertex[*vertex_index as usize].push((polygon_index, previous, next));
+		}
+	}
+
+	let mesh_vertices = vertices.iter().zip(around_vertex.iter_mut())
+		.map(|(coords, around)| {
+			// polygon covers angle from its edge to the next vertex to its edge to the previous one
+			let angle = |vertex_index: u32| {
+				let direction = vertices[vertex_index as usize] - *coords;
+				let angle = direction.y.atan2(direction.x);
+
+				if counterclockwise { angle } else { -angle }
+			};
+
+			around.sort_by(|(_, _, next_a), (_, _, next_b)| angle(*next_a).total_cmp(&angle(*next_b)));
+
+			let mut polygons_around = Vec::new();
+
+			for (i, (polygon_index, previous, _)) in around.iter().enumerate() {
+				polygons_around.push(*polygon_index as isize);
+
+				// gap between this polygon and the next one is outside of the mesh
+				let (_, _, following_next) = around[(i + 1) % around.len()];
+
+				if *previous != following_next {
+					polygons_around.push(-1);
+				}
+			}
+
+			PolyanyaVertex::new(*coords, polygons_around)
+		})
+		.collect::<Vec<PolyanyaVertex>>();
+
+	let mesh_polygons = polygons.iter()
+		.map(|polygon| {
+			let neighbours = (0 .. polygon.len())
+				.filter(|i| edges.contains_key(&(polygon[(i + 1) % polygon.len()], polygon[*i])))
+				.count();
+
+			// polygons with a single neighbour are dead ends, search doesn't go through them
+			PolyanyaPolygon::new(polygon.clone(), neighbours <= 1)
+		})
+		.collect::<Vec<PolyanyaPolygon>>();
+
+	let Ok(mut navmesh) = PolyanyaMesh::new(mesh_vertices, mesh_polygons) else { return None };
+
+	navmesh.bake();
+
+	Some(navmesh)
+}
+
+/// Edges of navmesh that have no polygon on the other side, turned so that walkable side is on their right as avoidance expects
+pub fn boundary_obstacles(navmesh: &PolyanyaMesh) -> Vec<AvoidanceObstacle> {
+	let Some(first_polygon) = navmesh.polygons.first() else { return Vec::new() };
+
+	let edges = navmesh.polygons.iter()
+		.flat_map(|polygon| {
+			let vertices = &polygon.vertices;
+
+			(0 .. vertices.len()).map(move |i| (vertices[i], vertices[(i + 1) % vertices.len()]))
+		})
+		.collect::<HashSet<(u32, u32)>>();
+
+	let counterclockwise = signed_area(&first_polygon.vertices.iter().map(|index| navmesh.vertices[*index as usize].coords).collect::<Vec<Vec2>>()) > 0.0;
+
+	let boundary = edges.iter()
+		.filter(|(a, b)| !edges.contains(&(*b, *a)))
+		.map(|(a, b)| if counterclockwise { (*b, *a) } else { (*a, *b) })
+		.collect::<Vec<(u32, u32)>>();
+
+	// boundary edges make loops, every vertex on it has one edge ending and one starting at it
+	let next_vertex = boundary.iter().map(|(a, b)| (*a, *b)).collect::<HashMap<u32, u32>>();
+	let previous_vertex = boundary.iter().map(|(a, b)| (*b, *a)).collect::<HashMap<u32, u32>>();
+
+	let coords = |index: u32| navmesh.vertices[index as usize].coords;
+
+	boundary.iter()
+		.map(|(a, b)| {
+			let direction = (coords(*b) - coords(*a)).normalize_or_zero();
+
+			AvoidanceObstacle {
+				point				: coords(*a),
+				next_point			: coords(*b),
+				previous_direction	: previous_vertex.get(a).map_or(direction, |previous| (coords(*a) - coords(*previous)).normalize_or_zero()),
+				next_direction		: next_vertex.get(b).map_or(direction, |after_next| (coords(*after_next) - coords(*b)).normalize_or_zero()),
+			}
+		})
+		.collect()
+}
+
+/// Part of convex polygon inside of rect, `None` if they don't overlap or only touch
+pub fn clip_to_rect(polygon: &[Vec2], rect: Rect) -> Option<Vec<Vec2>> {
+	// axis, bound and whether points below the bound are kept, for every side of the rect
+	let sides = [
+		(0, rect.min.x, false),
+		(0, rect.max.x, true),
+		(1, rect.min.y, false),
+		(1, rect.max.y, true),
+	];
+
+	let mut clipped = polygon.to_vec();
+
+	for (axis, bound, keep_below) in sides {
+		let inside = |point: Vec2| if keep_below { point[axis] <= bound } else { point[axis] >= bound };
+
+		let input = std::mem::take(&mut clipped);
+
+		for (i, current) in input.iter().enumerate() {
+			let previous = input[(i + input.len() - 1) % input.len()];
+
+			// intersection is computed from the edge alone, so tiles on both sides of the bound get the same point
+			let intersection = || {
+				let mut point = previous.lerp(*current, (bound - previous[axis]) / (current[axis] - previous[axis]));
+				point[axis] = bound;
+				point
+			};
+
+			if inside(*current) {
+				if !inside(previous) {
+					clipped.push(intersection());
+				}
+
+				clipped.push(*current);
+			} else if inside(previous) {
+				clipped.push(intersection());
+			}
+		}
+
+		if clipped.is_empty() { return None }
+	}
+
+	clipped.dedup_by(|a, b| a.distance_squared(*b) < STITCH_PRECISION * STITCH_PRECISION);
+
+	if clipped.len() > 1 && clipped[0].distance_squared(clipped[clipped.len() - 1]) < STITCH_PRECISION * STITCH_PRECISION {
+		clipped.pop();
+	}
+
+	if clipped.len() < 3 || signed_area(&clipped).abs() < STITCH_PRECISION { return None }
+
+	Some(clipped)
+}
+
+fn signed_area(polygon: &[Vec2]) -> f32 {
+	(0 .. polygon.len())
+		.map(|i| polygon[i].perp_dot(polygon[(i + 1) % polygon.len()]))
+		.sum::<f32>() / 2.0
+}
+
+/// Convex footprint of collider on the ground, simplified and inflated by unit radius. Rapier keeps collider's shape already
+/// scaled by its transform, so only rotation and translation are applied here
+pub fn collider_footprint(shape: &dyn Shape, transform: &GlobalTransform, unit_radius: f32) -> Vec<Vec2> {
+	let isometry = transform_to_iso(&transform.compute_transform());
+
+	let mut points = Vec::new();
+
+	shape_points(shape, &isometry, &mut points);
+
+	let hull = simplify_hull(convex_hull(points), NAVMESH_FOOTPRINT_TOLERANCE, NAVMESH_FOOTPRINT_MAX_VERTICES);
+
+	inflate_hull(&hull, unit_radius)
+}
+
+/// World space points of the shape projected on the ground. Shapes without vertices like balls or capsules give corners of their bounding box
+fn shape_points(shape: &dyn Shape, isometry: &Isometry<f32>, points: &mut Vec<Vec2>) {
+	let project = |point: &Point<f32>| {
+		let world_point = isometry * point;
+
+		Vec2::new(world_point.x, world_point.z)
+	};
+
+	match shape.as_typed_shape() {
+		TypedShape::TriMesh(trimesh) => points.extend(trimesh.vertices().iter().map(project)),
+		TypedShape::ConvexPolyhedron(polyhedron) => points.extend(polyhedron.points().iter().map(project)),
+		TypedShape::Compound(compound) => {
+			for (part_isometry, part_shape) in compound.shapes() {
+				shape_points(part_shape.as_ref(), &(isometry * part_isometry), points);
+			}
+		},
+		_ => points.extend(shape.compute_local_aabb().vertices().iter().map(project)),
+	}
+}
+
+/// Counterclockwise convex hull, monotone chain
+pub fn convex_hull(mut points: Vec<Vec2>) -> Vec<Vec2> {
+	points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+	points.dedup_by(|a, b| a.distance_squared(*b) < STITCH_PRECISION * STITCH_PRECISION);
+
+	if points.len() < 3 { return points }
+
+	let turns_left = |hull: &Vec<Vec2>, point: Vec2| {
+		let (a, b) = (hull[hull.len() - 2], hull[hull.len() - 1]);
+
+		(b - a).perp_dot(point - b) > 0.0
+	};
+
+	let mut lower : Vec<Vec2> = Vec::new();
+
+	for point in points.iter() {
+		while lower.len() >= 2 && !turns_left(&lower, *point) { lower.pop(); }
+
+		lower.push(*point);
+	}
+
+	let mut upper : Vec<Vec2> = Vec::new();
+
+	for point in points.iter().rev() {
+		while upper.len() >= 2 && !turns_left(&upper, *point) { upper.pop(); }
+
+		upper.push(*point);
+	}
+
+	// last point of each chain is the first one of the other
+	lower.pop();
+	upper.pop();
+
+	lower.extend(upper);
+
+	lower
+}
+
+/// Removes hull vertices that barely change its shape, least significant first, until the rest are all above tolerance and fit into max_vertices
+pub fn simplify_hull(mut hull: Vec<Vec2>, tolerance: f32, max_vertices: usize) -> Vec<Vec2> {
+	// distance from vertex to the line through its neighbours
+	let significance = |hull: &Vec<Vec2>, i: usize| {
+		let previous = hull[(i + hull.len() - 1) % hull.len()];
+		let next = hull[(i + 1) % hull.len()];
+
+		let base = next - previous;
+
+		if base.length_squared() < f32::EPSILON { return 0.0 }
+
+		(hull[i] - previous).perp_dot(base).abs() / base.length()
+	};
+
+	while hull.len() > 3 {
+		let Some((least_index, least_significance)) = (0 .. hull.len())
+			.map(|i| (i, significance(&hull, i)))
+			.min_by(|(_, a), (_, b)| a.total_cmp(b))
+		else { break };
+
+		if least_significance >= tolerance && hull.len() <= max_vertices { break }
+
+		hull.remove(least_index);
+	}
+
+	hull
+}
+
+/// Moves every edge of counterclockwise convex hull outwards by radius. Corners are mitered so the result stays convex
+pub fn inflate_hull(hull: &[Vec2], radius: f32) -> Vec<Vec2> {
+	if hull.len() < 3 { return hull.to_vec() }
+
+	// outward normal of edge from vertex i to the next one
+	let normal = |i: usize| {
+		let edge = hull[(i + 1) % hull.len()] - hull[i];
+
+		Vec2::new(edge.y, -edge.x).normalize_or_zero()
+	};
+
+	(0 .. hull.len())
+		.map(|i| {
+			let (normal_before, normal_after) = (normal((i + hull.len() - 1) % hull.len()), normal(i));
+
+			let miter = (normal_before + normal_after).normalize_or_zero();
+
+			// sharp corners would stick out too far, hull has a few vertices after simplification so they are rare anyway
+			let cos_half_angle = miter.dot(normal_after).max(0.5);
+
+			hull[i] + miter * radius / cos_half_angle
+		})
+		.collect()
+}
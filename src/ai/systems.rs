@@ -1,47 +1,60 @@
 use bevy :: {
 	prelude :: *,
 	pbr :: NotShadowCaster,
-	render :: primitives :: Aabb,
+	utils :: HashMap,
 };
 
 use bevy_rapier3d :: prelude :: *;
-use polyanya :: Triangulation;
 use bevy_vector_shapes :: prelude :: *;
 
 use super :: *;
 
+use super :: navmesh :: { NavmeshTiles, NAVMESH_UNIT_RADIUS, collider_footprint };
+
+use super :: avoidance :: { AvoidanceNeighbour, AvoidanceObstacle, orca_velocity };
+
 use crate :: game :: {
 	BaseBuilding,
 	MainEntities,
+	CameraMode,
 	Raypick,
 	Selected,
 	spawn,
-	SVIN_CARRYING_CAPACITY
 };
 
-use crate :: setup :: GROUND_HSIZE;
+use crate :: map :: Terrain;
 
 use crate :: assets :: GameAssets;
 
+use crate :: units :: { UnitDefinitions, UnitType };
+
+use crate :: buildings :: UnderConstruction;
+
 use crate :: handheld :: { Handheld, HandheldOwner };
 
+use crate :: faction :: Faction;
+
 use crate :: resource_collection :: {
 	ResourceCollector,
 	CollectableResource,
+	ResourceDeposit,
 	ActiveCollecting,
+	DEPOSIT_DEFAULT_AMOUNT,
 };
 
 pub fn collect_navmesh_obstacles(
-		q_obstacle	: Query<Entity, Added<NavmeshObstacleContainer>>,
-		q_aabb		: Query<&Aabb>,
-		q_children	: Query<&Children>,
+		q_collider	: Query<Entity, Added<Collider>>,
+		q_container	: Query<(), With<NavmeshObstacleContainer>>,
+		q_parent	: Query<&Parent>,
 	mut commands	: Commands,
 ) {
-	for obstacle_entity in q_obstacle.iter() {
-		for descendant in q_children.iter_descendants(obstacle_entity) {
-			if let Ok(_) = q_aabb.get(descendant) {
-				commands.entity(descendant).insert(NavmeshObstacleAabb);
-			}
+	// colliders from AsyncSceneCollider show up only once container's scene is spawned, so containers are looked up from colliders
+	for collider_entity in q_collider.iter() {
+		let in_container = q_container.contains(collider_entity)
+			|| q_parent.iter_ancestors(collider_entity).any(|ancestor_entity| q_container.contains(ancestor_entity));
+
+		if in_container {
+			commands.entity(collider_entity).insert(NavmeshObstacleCollider);
 		}
 	}
 }
@@ -69,109 +82,230 @@ pub fn collect_interact_locators(
 }
 
 pub fn update_navmesh_obstacles(
-		q_navmesh_obstacle_aabb	: Query<(&GlobalTransform, &Aabb), With<NavmeshObstacleAabb>>,
-		q_navmesh_obstacle_aabb_changed	: Query<Entity, Or<(Added<NavmeshObstacleAabb>, Changed<NavmeshObstacleAabb>)>>,
-		q_navmesh_obstacle_aabb_removed : RemovedComponents<NavmeshObstacleAabb>,
-		q_navmesh_wireframe		: Query<Entity, With<NavmeshWireframe>>,
-	mut meshes					: ResMut<Assets<Mesh>>,
-	mut materials				: ResMut<Assets<StandardMaterial>>,
-	mut commands				: Commands
+		time					: Res<Time>,
+		q_navmesh_obstacle		: Query<(Entity, &GlobalTransform, &Collider), (With<NavmeshObstacleCollider>, Or<(Added<NavmeshObstacleCollider>, Changed<GlobalTransform>, Changed<Collider>)>)>,
+	mut q_navmesh_obstacle_removed : RemovedComponents<NavmeshObstacleCollider>,
+	mut navmesh_tiles			: ResMut<NavmeshTiles>,
+	mut polyanya				: ResMut<PolyanyaResource>,
 ) {
-	// nothing changed so nothing to do further
-	if q_navmesh_obstacle_aabb_changed.is_empty() && q_navmesh_obstacle_aabb_removed.is_empty() { return }
-
-	let mut polyanya_triangulation = Triangulation::from_outer_edges(&[
-		Vec2::new(-GROUND_HSIZE, -GROUND_HSIZE),
-		Vec2::new(-GROUND_HSIZE,  GROUND_HSIZE),
-		Vec2::new( GROUND_HSIZE,  GROUND_HSIZE),
-		Vec2::new( GROUND_HSIZE, -GROUND_HSIZE),
-	]);
+	let mut changed = false;
 
-	let mut navmesh_obstacles_aabb = Vec::new();
+	for (obstacle_entity, transform, collider) in q_navmesh_obstacle.iter() {
+		let footprint = collider_footprint(&*collider.raw, transform, NAVMESH_UNIT_RADIUS);
 
-	for (transform, aabb) in q_navmesh_obstacle_aabb.iter() {
-		let min = transform.transform_point(aabb.min().into());
-		let max = transform.transform_point(aabb.max().into());
+		navmesh_tiles.set_obstacle(obstacle_entity, footprint);
 
-		let v0 = Vec3::new(min.x, 0.0, min.z);
-		let v1 = Vec3::new(min.x, 0.0, max.z);
-		let v2 = Vec3::new(max.x, 0.0, max.z);
-		let v3 = Vec3::new(max.x, 0.0, min.z);
+		changed = true;
+	}
 
-		navmesh_obstacles_aabb.push([
-			Vec2::new(v0.x, v0.z),
-			Vec2::new(v1.x, v1.z),
-			Vec2::new(v2.x, v2.z),
-			Vec2::new(v3.x, v3.z),
-		].to_vec());
-	};
+	for obstacle_entity in q_navmesh_obstacle_removed.read() {
+		navmesh_tiles.remove_obstacle(obstacle_entity);
 
-	polyanya_triangulation.set_unit_radius(0.4);
+		changed = true;
+	}
 
-	polyanya_triangulation.add_obstacles(navmesh_obstacles_aabb);
+	if !changed {
+		navmesh_tiles.wait(time.delta_seconds());
+	}
 
-	polyanya_triangulation.merge_overlapping_obstacles();
+	// previous navmesh stays in use until rebuilt tiles are stitched, so paths never see a half-built one
+	// level navmeshes have no obstacles and stay as they are
+	if let Some(navmesh) = navmesh_tiles.poll_rebuild() {
+		polyanya.set_ground_mesh(navmesh);
+	}
 
-	let Some(mut navmesh) = polyanya_triangulation.as_navmesh() else { panic!("navmesh building failed!") };
+	navmesh_tiles.start_rebuild();
+}
 
-	navmesh.bake();
+pub fn update_navmesh_wireframe(
+		polyanya				: Res<PolyanyaResource>,
+		q_navmesh_wireframe		: Query<Entity, With<NavmeshWireframe>>,
+	mut meshes					: ResMut<Assets<Mesh>>,
+	mut materials				: ResMut<Assets<StandardMaterial>>,
+	mut commands				: Commands
+) {
+	// navmesh wasn't rebuilt so wireframe is still valid
+	if !polyanya.is_changed() { return }
 
-	if let Ok(entity) = q_navmesh_wireframe.get_single() {
+	for entity in q_navmesh_wireframe.iter() {
 		commands.entity(entity).despawn_recursive();
 	}
 
-	commands.spawn((
-		PbrBundle {
-			mesh: meshes.add(navmesh_to_wireframe(&navmesh)),
-			transform: Transform::from_translation(Vec3::new(0., 0.01, 0.)),
-			material: materials.add(StandardMaterial {
-				base_color: Color::GREEN,
-				unlit: true,
-				depth_bias: 100.0,
+	let material = materials.add(StandardMaterial {
+		base_color: Color::GREEN,
+		unlit: true,
+		depth_bias: 100.0,
+		..default()
+	});
+
+	// one wireframe per layer, levels are drawn on their decks and ramps
+	let wireframes = std::iter::once(navmesh_to_wireframe(&polyanya.mesh, |_| 0.0))
+		.chain(polyanya.levels.iter().map(|level_navmesh| {
+			navmesh_to_wireframe(&level_navmesh.mesh, |point| level_navmesh.level.surface_height(point).unwrap_or(level_navmesh.level.height))
+		}))
+		.collect::<Vec<Mesh>>();
+
+	for wireframe in wireframes {
+		commands.spawn((
+			PbrBundle {
+				mesh: meshes.add(wireframe),
+				transform: Transform::from_translation(Vec3::new(0., 0.01, 0.)),
+				material: material.clone(),
+				visibility: Visibility::Hidden,
 				..default()
-			}),
-			visibility: Visibility::Hidden,
-			..default()
-		},
-		NotShadowCaster,
-		NavmeshWireframe,
-	));
-
-	commands.insert_resource(PolyanyaResource { mesh: navmesh });
+			},
+			NotShadowCaster,
+			NavmeshWireframe,
+		));
+	}
 }
 
-pub fn give_path_on_click(
+/// Right click with cursor or crosshair on something, turned into `NpcCommandTarget` for selected units and bases
+pub fn command_target_on_click(
 		mouse_button	: Res<ButtonInput<MouseButton>>,
+		main_entities	: Res<MainEntities>,
+		camera_mode		: Res<State<CameraMode>>,
+		q_raypick		: Query<&Raypick>,
+	mut command_targets	: EventWriter<NpcCommandTarget>,
+) {
+	if !mouse_button.just_pressed(MouseButton::Right) { return }
+
+	let Ok(raypick) = q_raypick.get(main_entities.raypick_camera(*camera_mode.get())) else { panic!("camera has no raypick!") };
+
+	let Some(raypicked_entity) = raypick.entity else { return };
+
+	command_targets.send(NpcCommandTarget {
+		entity		: Some(raypicked_entity),
+		position	: raypick.pos,
+	});
+}
+
+pub fn give_path_to_command_target(
+		unit_definitions	: Res<UnitDefinitions>,
+		key				: Res<ButtonInput<KeyCode>>,
 		time			: Res<Time>,
 		polyanya		: Res<PolyanyaResource>,
 		main_entities	: Res<MainEntities>,
-		q_raypick		: Query<&Raypick>,
-		q_movable		: Query<(Entity, &Transform, &HandheldOwner), (With<NpcMovable>, With<Selected>, Without<Locator>)>,
+		q_faction		: Query<&Faction>,
+		q_movable		: Query<(Entity, &UnitType, &Faction, &Transform, Option<&HandheldOwner>), (With<NpcMovable>, With<Selected>, Without<Locator>)>,
 		q_locators_container : Query<Entity, (With<LocatorsContainer>, With<NpcInteractable>)>,
 		q_collectable	: Query<&CollectableResource>,
+		q_construction	: Query<&Faction, With<UnderConstruction>>,
 		q_children		: Query<&Children>,
 		q_locator		: Query<(&Locator, &GlobalTransform, Option<&Occupied>)>,
+		q_base			: Query<&Faction, With<BaseBuilding>>,
+		q_npc			: Query<(), With<NpcMovable>>,
 	mut q_handheld		: Query<&mut Handheld>,
+	mut q_command_queue	: Query<&mut NpcCommandQueue>,
+	mut q_patrol		: Query<&mut NpcTaskPatrol>,
+	mut command_targets	: EventReader<NpcCommandTarget>,
 	mut commands		: Commands,
 ) {
-	let navmesh = &polyanya.mesh;
+	// only the last click of the frame counts
+	let Some(command_target) = command_targets.read().last().copied() else { return };
 
-	let Ok(raypick) = q_raypick.get(main_entities.player_camera) else { panic!("player camera has no raypick!") };
+	let Ok(player_faction) = q_faction.get(main_entities.player) else { panic!("player entity has no Faction component!") };
 
-	let Some(raypicked_entity) = raypick.entity else { return };
+	let target_entity_opt = command_target.entity;
+	let target_pos = command_target.position;
 
-	if !mouse_button.just_pressed(MouseButton::Right) { return }
+	// minimap click has no target entity, it's always a move or patrol
+	let locators_container = target_entity_opt.and_then(|entity| q_locators_container.get(entity).ok());
+
+	let collectable = target_entity_opt.is_some_and(|entity| q_collectable.contains(entity));
+
+	// clicking another unit makes npc follow it, holding `p` makes it patrol between where it is and clicked point
+	let follow = target_entity_opt.filter(|entity| q_npc.contains(*entity));
+	let patrol = key.pressed(KeyCode::KeyP) && follow.is_none();
+
+	for (movable_entity, unit_type, faction, movable_transform, handheld_owner) in q_movable.iter() {
+		if Some(movable_entity) == target_entity_opt { continue }
+
+		// player commands only units of own faction
+		if faction != player_faction { continue }
+
+		// units that somehow didn't get a queue on spawn get it with the first queued command
+		let mut command_queue = q_command_queue.get_mut(movable_entity).ok();
+
+		let own_site = target_entity_opt.is_some_and(|entity| q_construction.get(entity).is_ok_and(|site_faction| site_faction == faction));
+		let own_base = target_entity_opt.is_some_and(|entity| q_base.get(entity).is_ok_and(|base_faction| base_faction == faction));
+
+		// with shift command is carried out after everything unit was told to do before
+		if key.pressed(KeyCode::ShiftLeft) {
+			// point added to route unit is already patrolling
+			let extended_patrol = match q_patrol.get_mut(movable_entity) {
+				Ok(mut patrol_task) if patrol => {
+					patrol_task.points.push(target_pos);
+					true
+				},
+				_ => false,
+			};
+
+			let command = match locators_container {
+				_ if extended_patrol => None,
+				_ if follow.is_some() => follow.map(NpcCommand::Follow),
+				_ if patrol => Some(NpcCommand::Patrol(target_pos)),
+				Some(container_entity) if handheld_owner.is_some() && collectable => Some(NpcCommand::Mine(container_entity)),
+				Some(container_entity) if handheld_owner.is_some() && own_site => Some(NpcCommand::Build(container_entity)),
+				Some(_) if handheld_owner.is_some() && own_base => Some(NpcCommand::ReturnCargo),
+				Some(container_entity) => pick_nearest_locator(Locator::Interact, &container_entity, movable_transform, &q_children, &q_locator).map(|(pos, _, _)| NpcCommand::Move(pos)),
+				None => Some(NpcCommand::Move(target_pos)),
+			};
+
+			match (command, command_queue.as_mut()) {
+				(Some(command), Some(command_queue)) => command_queue.push_back(command),
+				(Some(command), None) => { commands.entity(movable_entity).insert(NpcCommandQueue(VecDeque::from([command]))); },
+				(None, _) if !extended_patrol => continue,
+				(None, _) => {},
+			}
+
+			commands.spawn((
+				SpatialBundle {
+					transform : Transform::from_translation(target_pos),
+					..default()
+				},
+				ClickPoint::new(time.elapsed_seconds(), 0.7)
+			));
+
+			continue;
+		}
+
+		if let Some(command_queue) = command_queue.as_mut() {
+			command_queue.clear();
+		}
+
+		if follow.is_some() || patrol {
+			remove_tasks(movable_entity, &mut commands);
+
+			if let Some(handheld_owner) = handheld_owner {
+				if let Ok(mut handheld) = q_handheld.get_mut(handheld_owner.handheld_entity) {
+					if handheld.activated() {
+						handheld.deactivate();
+					}
+				}
+			}
 
-	let locators_container_result = q_locators_container.get(raypicked_entity);
+			if let Some(follow_entity) = follow {
+				commands.entity(movable_entity).insert(NpcTaskFollow::new(follow_entity));
+			} else {
+				commands.entity(movable_entity).insert(NpcTaskPatrol::new(vec![movable_transform.translation, target_pos]));
+			}
 
-	for (movable_entity, movable_transform, handheld_owner) in q_movable.iter() {
-		if q_movable.get(raypicked_entity).is_ok() { continue }
+			continue;
+		}
+
+		commands.entity(movable_entity)
+			.remove::<HoldPosition>()
+			.remove::<NpcTaskReturnCargo>()
+			.remove::<NpcTaskPatrol>()
+			.remove::<NpcTaskFollow>()
+		;
 
 		let target_position;
 		let mut target_rotation = None;
 		let mut target_entity = None;
 
-		if let Ok(container_entity) = locators_container_result {
+		if let Some(container_entity) = locators_container {
 			let Some((pos, rot, entity)) = pick_nearest_locator(
 				Locator::Interact,
 				&container_entity,
@@ -184,36 +318,54 @@ pub fn give_path_on_click(
 			target_rotation = Some(rot);
 			target_entity = Some(entity);
 
-			// start resource collection task if raypicked entity is collectable
-			if q_collectable.get(raypicked_entity).is_ok() {
-				commands.entity(movable_entity).insert(NpcTaskResourceCollection {
-					resource_entity : Some(raypicked_entity),
-					..default()
-				});
-			}
-		} else {
-			target_position = raypick.pos;
+			// previous task is replaced, new one activates handheld again once npc arrives
+			commands.entity(movable_entity)
+				.remove::<NpcTaskResourceCollection>()
+				.remove::<NpcTaskConstruction>()
+			;
+
+			// start resource collection or construction task if unit has something to do it with
+			if let Some(handheld_owner) = handheld_owner {
+				if collectable {
+					commands.entity(movable_entity).insert(NpcTaskResourceCollection {
+						resource_entity : Some(container_entity),
+						..default()
+					});
+				} else if own_site {
+					commands.entity(movable_entity).insert(NpcTaskConstruction {
+						building_entity : container_entity,
+					});
+				}
 
-			// cancel current resource collection task and deactive handheld
-			commands.entity(movable_entity).remove::<NpcTaskResourceCollection>();
-			if let Ok(mut handheld) = q_handheld.get_mut(handheld_owner.handheld_entity) {
-				if handheld.activated() {
+				if let Ok(mut handheld) = q_handheld.get_mut(handheld_owner.handheld_entity) {
 					handheld.deactivate();
 				}
 			}
+		} else {
+			target_position = target_pos;
+
+			// cancel current task and deactive handheld
+			commands.entity(movable_entity)
+				.remove::<NpcTaskResourceCollection>()
+				.remove::<NpcTaskConstruction>()
+			;
+			if let Some(handheld_owner) = handheld_owner {
+				if let Ok(mut handheld) = q_handheld.get_mut(handheld_owner.handheld_entity) {
+					if handheld.activated() {
+						handheld.deactivate();
+					}
+				}
+			}
 		}
 
-		let mut from3	= movable_transform.translation; from3.y = 0.0;
-		let 	from2	= from3.xz();
-		let 	to2		= target_position.xz();
-
-		if !navmesh.point_in_mesh(to2) {
-			println!("Error: can't create path to {:?}! clicked point is not on navmesh!", to2);
+		// clicked point keeps its height, so click on a deck and on the ground under it are on different layers
+		if !polyanya.point_in_mesh(target_position) {
+			println!("Error: can't create path to {:?}! clicked point is not on navmesh!", target_position);
 			continue;
 		}
 
-		let Some(path_wlen) = navmesh.path(from2, to2) else {
-			println!("Error: can't create path from {:?} to {:?}! navmesh.path returned None!", from3, target_position);
+		let Some(path_wlen) = polyanya.path(movable_transform.translation, target_position) else {
+			println!("Error: can't create path from {:?} to {:?}! navmesh.path returned None!", movable_transform.translation, target_position);
 			continue;
 		};
 
@@ -223,6 +375,9 @@ pub fn give_path_on_click(
 
 			let current = Vec3::new(first.x, 0.0, first.y);
 
+			// arrival from previous path must not count as arrival for the new one
+			commands.entity(movable_entity).remove::<NpcTaskMoveFinished>();
+
 			commands.entity(movable_entity).insert((
 				MovePath {
 					current,
@@ -232,7 +387,7 @@ pub fn give_path_on_click(
 					..default()
 				},
 				NpcTaskMove {
-					speed : 2.0
+					speed : unit_definitions.get(*unit_type).speed
 				}
 			));
 
@@ -276,203 +431,415 @@ pub fn click_point_draw(
 
 pub fn selected_path_draw(
 		time			: Res<Time>,
+		terrain			: Res<Terrain>,
+		polyanya		: Res<PolyanyaResource>,
 		q_path			: Query<&MovePath, With<Selected>>,
 	mut painter			: ShapePainter,
 ) {
 	let seconds = time.elapsed_seconds();
 
 	for path in q_path.iter() {
-		let target_pos = if let Some(first) = path.next.first() { first } else { &path.current };
+		let mut target_pos = if let Some(first) = path.next.first() { *first } else { path.current };
+
+		// ground under plateaus and ramps isn't walkable so target there is on the level, under bridge deck it's drawn on the ground
+		let level_height = polyanya.levels.iter()
+			.filter_map(|level_navmesh| level_navmesh.level.surface_height(target_pos.xz()).map(|height| (&level_navmesh.level, height)))
+			.find(|(level, _)| !level.bridge || !level.deck.contains(target_pos.xz()))
+			.map(|(_, height)| height);
+
+		target_pos.y = level_height.unwrap_or_else(|| terrain.height_at(target_pos.xz()));
 		let color = (Color::GREEN + Color::WHITE * 0.25).with_a(1.0);
 		let ease_function = interpolation::EaseFunction::QuadraticInOut;
 		let duration = 2.5;
 		let progress_offset = -0.5;
 
-		draw_floor_circle(0.7, 0.15, progress_offset, target_pos, seconds, duration, ease_function, color, &mut painter);
+		draw_floor_circle(0.7, 0.15, progress_offset, &target_pos, seconds, duration, ease_function, color, &mut painter);
 	}
 }
 
-pub fn movable_update(
+/// Queued commands of selected units as a chain of floor circles, each one a bit behind the previous in animation
+pub fn command_queue_draw(
 		time			: Res<Time>,
-		q_locator		: Query<&Locator>,
-		q_occupies		: Query<&Occupies>,
-	mut q_transform		: Query<&mut Transform>,
-	mut q_movable		: Query<(Entity, &NpcTaskMove, &mut MovePath), With<NpcMovable>>,
-	mut commands		: Commands,
+		q_transform		: Query<&GlobalTransform>,
+		q_queue			: Query<&NpcCommandQueue, With<Selected>>,
+		q_patrol		: Query<&NpcTaskPatrol, With<Selected>>,
+	mut painter			: ShapePainter,
 ) {
-	for (movable_entity, move_task, mut path) in q_movable.iter_mut() {
-		if let Ok(occupies) = q_occupies.get(movable_entity) {
-			commands.entity(occupies.whom()).remove::<Occupied>();
-			commands.entity(movable_entity).remove::<Occupies>();
-		}
+	let seconds = time.elapsed_seconds();
 
-		let Ok(mut movable_transform) = q_transform.get_mut(movable_entity) else { panic!("entity with NpcTaskMove has not Transform component!") };
+	// patrol route is drawn a bit bigger since unit keeps walking it
+	for patrol in q_patrol.iter() {
+		for (i, point) in patrol.points.iter().enumerate() {
+			let color = (Color::CYAN + Color::WHITE * 0.25).with_a(0.6);
+			let ease_function = interpolation::EaseFunction::QuadraticInOut;
+			let duration = 2.5;
+			let progress_offset = -0.5;
 
-		let mut movable_position_navmesh = Vec3::new(movable_transform.translation.x, 0.0, movable_transform.translation.z);
+			draw_floor_circle(0.7, 0.15, progress_offset, point, seconds - 0.25 * i as f32, duration, ease_function, color, &mut painter);
+		}
+	}
 
-		let move_direction = (path.current - movable_position_navmesh).normalize_or_zero();
+	for command_queue in q_queue.iter() {
+		for (i, command) in command_queue.iter().enumerate() {
+			let target_pos = match command {
+				NpcCommand::Move(position) | NpcCommand::Patrol(position) => *position,
+				NpcCommand::Mine(entity) | NpcCommand::Build(entity) | NpcCommand::Follow(entity) => {
+					let Ok(transform) = q_transform.get(*entity) else { continue };
 
-		let delta = move_direction * time.delta_seconds() * move_task.speed;
+					transform.translation()
+				},
+				NpcCommand::ReturnCargo => continue,
+			};
 
-		movable_transform.translation += delta;
+			let color = (Color::GREEN + Color::WHITE * 0.25).with_a(0.6);
+			let ease_function = interpolation::EaseFunction::QuadraticInOut;
+			let duration = 2.5;
+			let progress_offset = -0.5;
 
-		if !path.altered {
-			movable_transform.rotation = Quat::from_rotation_arc(Vec3::Z, move_direction);
+			draw_floor_circle(0.5, 0.15, progress_offset, &target_pos, seconds - 0.25 * (i + 1) as f32, duration, ease_function, color, &mut painter);
 		}
+	}
+}
 
-		movable_position_navmesh += delta;
+/// `x` stops selected npcs, `h` stops them and makes them hold position, `c` sends them to deliver what they carry,
+/// `f` makes them follow player. With `Shift` returning cargo and following are queued instead
+pub fn npc_command_on_key(
+		key				: Res<ButtonInput<KeyCode>>,
+		main_entities	: Res<MainEntities>,
+		q_faction		: Query<&Faction>,
+		q_selected		: Query<(Entity, &Faction, Option<&HandheldOwner>), (With<NpcMovable>, With<Selected>)>,
+	mut q_handheld		: Query<&mut Handheld>,
+	mut q_command_queue	: Query<&mut NpcCommandQueue>,
+	mut commands		: Commands,
+) {
+	let stop			= key.just_pressed(KeyCode::KeyX);
+	let hold			= key.just_pressed(KeyCode::KeyH);
+	let return_cargo	= key.just_pressed(KeyCode::KeyC);
+	let follow_player	= key.just_pressed(KeyCode::KeyF);
 
-		let distance_to_target = movable_position_navmesh.distance(path.current);
+	if !stop && !hold && !return_cargo && !follow_player { return }
 
-		let distance_margin = 0.01;
+	let Ok(player_faction) = q_faction.get(main_entities.player) else { panic!("player entity has no Faction component!") };
 
-		if distance_to_target < distance_margin {
-			if let Some(next) = path.next.pop() {
-				path.current = next;
-			} else {
-				commands.entity(movable_entity)
-					.insert(NpcTaskMoveFinished)
-					.remove::<MovePath>()
-					.remove::<NpcTaskMove>()
-				;
+	for (npc_entity, faction, handheld_owner) in q_selected.iter() {
+		if faction != player_faction { continue }
 
-				movable_transform.translation.x = path.current.x;
-				movable_transform.translation.z = path.current.z;
+		let mut command_queue = q_command_queue.get_mut(npc_entity).ok();
 
-				if let Some(target_rotation) = path.target_rotation {
-					movable_transform.rotation = target_rotation;
-				}
+		if key.pressed(KeyCode::ShiftLeft) && (return_cargo || follow_player) {
+			let command = if return_cargo { NpcCommand::ReturnCargo } else { NpcCommand::Follow(main_entities.player) };
 
-				if let Some(target_entity) = path.target_entity {
-					if let Ok(_) = q_locator.get(target_entity) {
-						commands.entity(movable_entity).insert(Occupies { 0: target_entity });
-						commands.entity(target_entity).insert(Occupied);
-					}
+			match command_queue.as_mut() {
+				Some(command_queue) => command_queue.push_back(command),
+				None => { commands.entity(npc_entity).insert(NpcCommandQueue(VecDeque::from([command]))); },
+			}
+
+			continue;
+		}
+
+		if let Some(command_queue) = command_queue.as_mut() {
+			command_queue.clear();
+		}
+
+		remove_tasks(npc_entity, &mut commands);
+
+		if let Some(handheld_owner) = handheld_owner {
+			if let Ok(mut handheld) = q_handheld.get_mut(handheld_owner.handheld_entity) {
+				if handheld.activated() {
+					handheld.deactivate();
 				}
 			}
 		}
+
+		if hold {
+			commands.entity(npc_entity).insert(HoldPosition);
+		} else if return_cargo {
+			commands.entity(npc_entity).insert(NpcTaskReturnCargo);
+		} else if follow_player {
+			commands.entity(npc_entity).insert(NpcTaskFollow::new(main_entities.player));
+		}
 	}
 }
 
-pub fn movable_collision_avoidance(
-		rapier_context	: Res<RapierContext>,
+pub fn rally_point_to_command_target(
+		q_collectable	: Query<&CollectableResource>,
+		q_base			: Query<Entity, (With<BaseBuilding>, With<Selected>)>,
+	mut command_targets	: EventReader<NpcCommandTarget>,
+	mut commands		: Commands,
+) {
+	let Some(command_target) = command_targets.read().last().copied() else { return };
+
+	for base_entity in q_base.iter() {
+		// clicking base itself clears rally point
+		if command_target.entity == Some(base_entity) {
+			commands.entity(base_entity).remove::<RallyPoint>();
+			continue;
+		}
+
+		let target_entity = command_target.entity.filter(|entity| q_collectable.contains(*entity));
+
+		commands.entity(base_entity).insert(RallyPoint {
+			position : command_target.position,
+			target_entity,
+		});
+	}
+}
+
+pub fn rally_point_draw(
+		time			: Res<Time>,
+		q_rally_point	: Query<&RallyPoint, (With<BaseBuilding>, With<Selected>)>,
+	mut painter			: ShapePainter,
+) {
+	let seconds = time.elapsed_seconds();
+
+	for rally_point in q_rally_point.iter() {
+		let target_pos = rally_point.position;
+		let color = (Color::YELLOW + Color::WHITE * 0.25).with_a(1.0);
+		let ease_function = interpolation::EaseFunction::QuadraticInOut;
+		let duration = 2.0;
+		let progress_offset = 0.0;
+
+		draw_floor_circle(0.9, 0.15, progress_offset, &target_pos, seconds - 0.2, duration, ease_function, color, &mut painter);
+		draw_floor_circle(0.5, 0.16, progress_offset, &target_pos, seconds - 0.0, duration, ease_function, color, &mut painter);
+	}
+}
+
+/// ORCA local avoidance, see `avoidance` module. Every moving npc picks velocity closest to the one along its path that doesn't
+/// run into other npcs or off navmesh, moving npcs share avoidance between each other while standing ones are walked around. Npc whose final
+/// waypoint is taken by a standing npc stops next to it, or reports obstructed path when it walks to a locator so its task can pick another one
+pub fn movable_steering(
+		time			: Res<Time>,
+		unit_definitions: Res<UnitDefinitions>,
 		polyanya		: Res<PolyanyaResource>,
-		q_transform		: Query<&GlobalTransform>,
-		q_movable		: Query<(Entity, &Collider), With<NpcTaskMove>>,
+	mut q_npc			: Query<(Entity, &Transform, &UnitType, &mut Steering, Option<&NpcTaskMove>), With<NpcMovable>>,
 	mut q_path			: Query<&mut MovePath>,
 ) {
-	let mut paths_to_alter = Vec::new();
-	let mut paths_to_obstruct = Vec::new();
+	let time_step = time.delta_seconds();
+
+	if time_step <= 0.0 { return }
+
+	struct Agent {
+		entity		: Entity,
+		unit_type	: UnitType,
+		position	: Vec2,
+		/// npcs on a bridge and under it don't avoid each other
+		layer		: layers::NavLayer,
+		velocity	: Vec2,
+		radius		: f32,
+		/// walking speed if npc is moving along a path
+		speed		: Option<f32>,
+	}
 
-	let mut last_close_target = Vec3::ZERO;
+	let agents = q_npc.iter()
+		.map(|(entity, transform, unit_type, steering, move_task)| {
+			let speed = move_task.filter(|_| q_path.contains(entity)).map(|move_task| move_task.speed);
+
+			Agent {
+				entity,
+				unit_type	: *unit_type,
+				position	: transform.translation.xz(),
+				layer		: polyanya.layer_at(transform.translation),
+				velocity	: if speed.is_some() { steering.velocity } else { Vec2::ZERO },
+				radius		: unit_definitions.get(*unit_type).collider.radius,
+				speed,
+			}
+		})
+		.collect::<Vec<Agent>>();
 
-	for (movable_entity, collider) in q_movable.iter() {
-		let Ok(movable_transform) = q_transform.get(movable_entity) else { panic!("entity with NpcTaskMove has no Transform component!") };
+	// grid with cells of the biggest neighbour distance, so neighbours of every npc are in its cell and the ones around it
+	let cell_size = unit_definitions.units.values().fold(1.0, |size : f32, definition| size.max(definition.avoidance.neighbour_distance));
 
-		let Ok(path) = q_path.get(movable_entity) else { panic!("") };
+	let cell = |position: Vec2| (position / cell_size).floor().as_ivec2();
 
-		let mut movable_position_navmesh = movable_transform.translation();
-		movable_position_navmesh.y = 0.0;
+	let mut grid : HashMap<IVec2, Vec<usize>> = HashMap::new();
 
-		let Some(cylinder) = collider.as_cylinder() else { panic!("currently npcs can only have cylinder as collider!") };
-		let cylinder_diameter = cylinder.radius() * 2.0;
+	for (agent_index, agent) in agents.iter().enumerate() {
+		grid.entry(cell(agent.position)).or_default().push(agent_index);
+	}
 
-		let distance_to_target = movable_position_navmesh.distance(path.current);
+	let mut velocities : HashMap<Entity, Vec2> = HashMap::new();
 
-		// check for collision only when approaching target position
-		if path.next.len() != 0 || distance_to_target > cylinder_diameter {
-			continue;
-		}
+	for agent in agents.iter() {
+		let Some(speed) = agent.speed else { continue };
 
-		let fixed_target = path.target_entity.is_some();
+		let Ok(mut path) = q_path.get_mut(agent.entity) else { continue };
 
-		let position_to_test = movable_transform.translation() + Vec3::Y * 0.01;
+		let tunables = unit_definitions.get(agent.unit_type).avoidance;
 
-		let mut hit_entity = None;
-		let mut nearest_dist_sq = f32::MAX;
+		let center = cell(agent.position);
 
-		rapier_context.intersections_with_shape(
-			position_to_test,
-			Quat::IDENTITY,
-			collider,
-			QueryFilter::new().exclude_collider(movable_entity),
-			|entity: Entity| -> bool {
-				let Ok(hit_entity_transform) = q_transform.get(entity) else { panic!("hit entity doesnt have transform component!") };
+		let mut neighbours = (-1 ..= 1)
+			.flat_map(|x| (-1 ..= 1).map(move |y| center + IVec2::new(x, y)))
+			.filter_map(|neighbour_cell| grid.get(&neighbour_cell))
+			.flatten()
+			.map(|neighbour_index| &agents[*neighbour_index])
+			.filter(|neighbour| neighbour.entity != agent.entity && neighbour.layer == agent.layer)
+			.map(|neighbour| (neighbour.position.distance(agent.position), neighbour))
+			.filter(|(distance, _)| *distance < tunables.neighbour_distance)
+			.collect::<Vec<(f32, &Agent)>>();
 
-				let dist_sq = movable_transform.translation().distance_squared(hit_entity_transform.translation());
+		neighbours.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+		neighbours.truncate(tunables.max_neighbours);
 
-				if dist_sq < nearest_dist_sq {
-					nearest_dist_sq = dist_sq;
-					hit_entity = Some(entity);
-				}
+		let waypoint = path.current.xz();
+		let final_waypoint = path.next.is_empty();
 
-				true
+		// final waypoint is taken by someone standing there, npc can't get any closer
+		let blocked = final_waypoint && neighbours.iter().any(|(distance, neighbour)| {
+			neighbour.speed.is_none()
+			&& neighbour.position.distance(waypoint) < agent.radius + neighbour.radius
+			&& *distance < agent.radius + neighbour.radius + tunables.separation * 2.0
+		});
+
+		if blocked {
+			if path.target_entity.is_some() {
+				path.obstructed = true;
+			} else {
+				path.current = Vec3::new(agent.position.x, 0.0, agent.position.y);
+				path.altered = true;
 			}
-		);
 
-		let Some(hit_entity) = hit_entity else {
-			continue;
-		};
+			velocities.insert(agent.entity, Vec2::ZERO);
 
-		if fixed_target {
-			if let Ok(other_path) = q_path.get(hit_entity) {
-				let targets_are_close = (other_path.current - path.current).length() < cylinder_diameter;
-				let last_close_target_found = (last_close_target - path.current).length() < cylinder_diameter;
+			continue
+		}
 
-				if targets_are_close && !last_close_target_found {
-					last_close_target = path.current;
+		// slows down on the last step so waypoint is reached instead of overshot
+		let to_waypoint = waypoint - agent.position;
+		let preferred_velocity = to_waypoint.normalize_or_zero() * speed.min(to_waypoint.length() / time_step);
+
+		let avoidance_neighbours = neighbours.iter()
+			.map(|(_, neighbour)| AvoidanceNeighbour {
+				position		: neighbour.position,
+				velocity		: neighbour.velocity,
+				radius			: neighbour.radius + tunables.separation / 2.0,
+				responsibility	: if neighbour.speed.is_some() { 0.5 } else { 1.0 },
+			})
+			.collect::<Vec<AvoidanceNeighbour>>();
+
+		// boundary of npc's layer keeps it on navmesh, closest edges first. Waypoint off the layer is the other end of a ramp link
+		// or a locator inside of an obstacle, path leaves navmesh there on purpose
+		let leaving_layer = !polyanya.layer_mesh(agent.layer).point_in_mesh(waypoint);
+		let obstacle_distance = tunables.time_horizon * speed + agent.radius;
+
+		let mut obstacles = polyanya.layer_boundary(agent.layer).iter()
+			.filter(|_| !leaving_layer)
+			.map(|obstacle| (obstacle.distance_to(agent.position), *obstacle))
+			.filter(|(distance, _)| *distance < obstacle_distance)
+			.collect::<Vec<(f32, AvoidanceObstacle)>>();
+
+		obstacles.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+		let avoidance_obstacles = obstacles.into_iter().map(|(_, obstacle)| obstacle).collect::<Vec<AvoidanceObstacle>>();
+
+		let velocity = orca_velocity(
+			agent.position,
+			agent.velocity,
+			agent.radius + tunables.separation / 2.0,
+			preferred_velocity,
+			speed,
+			&avoidance_neighbours,
+			&avoidance_obstacles,
+			(agent.radius - polyanya.layer_inflation(agent.layer)).max(0.0),
+			tunables.time_horizon,
+			time_step,
+		);
 
-					continue;
-				}
+		velocities.insert(agent.entity, velocity);
+	}
 
-				last_close_target = Vec3::ZERO;
+	for (npc_entity, _, _, mut steering, _) in q_npc.iter_mut() {
+		steering.velocity = velocities.get(&npc_entity).copied().unwrap_or(Vec2::ZERO);
+	}
+}
+
+pub fn movable_update(
+		time			: Res<Time>,
+		terrain			: Res<Terrain>,
+		polyanya		: Res<PolyanyaResource>,
+		q_locator		: Query<&Locator>,
+		q_occupies		: Query<&Occupies>,
+	mut q_transform		: Query<&mut Transform>,
+	mut q_movable		: Query<(Entity, &Steering, &mut MovePath), (With<NpcMovable>, With<NpcTaskMove>)>,
+	mut commands		: Commands,
+) {
+	for (movable_entity, steering, mut path) in q_movable.iter_mut() {
+		if let Ok(occupies) = q_occupies.get(movable_entity) {
+			// locator could be despawned together with its container (depleted deposit for example)
+			if let Some(mut locator_commands) = commands.get_entity(occupies.whom()) {
+				locator_commands.remove::<Occupied>();
 			}
+			commands.entity(movable_entity).remove::<Occupies>();
+		}
 
-			paths_to_obstruct.push(movable_entity);
-		} else {
-			let Ok(hit_entity_transform) = q_transform.get(hit_entity) else { panic!("hit entity doesnt have transform component!") };
+		let Ok(mut movable_transform) = q_transform.get_mut(movable_entity) else { panic!("entity with NpcTaskMove has not Transform component!") };
 
-			let mut hit_offset = movable_transform.translation() - hit_entity_transform.translation();
-			hit_offset.y = 0.0;
+		// npc keeps its height above ground or level it walks on, path itself is on navmesh plane
+		let height_above_ground = movable_transform.translation.y - polyanya.ground_height(movable_transform.translation, &terrain);
 
-			let disjoint_direction = hit_offset.normalize_or_zero();
-			let disjoint_offset = (cylinder_diameter - hit_offset.length()) * disjoint_direction;
+		let delta = Vec3::new(steering.velocity.x, 0.0, steering.velocity.y) * time.delta_seconds();
 
-			paths_to_alter.push((movable_entity, movable_position_navmesh + disjoint_offset));
-		}
-	}
+		movable_transform.translation += delta;
 
-	for entity in paths_to_obstruct.iter() {
-		if let Ok(mut path) = q_path.get_mut(*entity) {
-			path.obstructed = true;
+		if !path.altered && delta.length_squared() > f32::EPSILON {
+			movable_transform.rotation = Quat::from_rotation_arc(Vec3::Z, delta.normalize());
 		}
-	}
 
-	for (entity, new_current) in paths_to_alter.iter() {
-		if let Ok(mut path) = q_path.get_mut(*entity) {
-			if polyanya.mesh.point_in_mesh(new_current.xz()) {
-				path.current = *new_current;
-				path.altered = true;
+		let movable_position_navmesh = Vec3::new(movable_transform.translation.x, 0.0, movable_transform.translation.z);
+
+		let distance_to_target = movable_position_navmesh.distance(path.current);
+
+		let distance_margin = if path.next.is_empty() { 0.01 } else { WAYPOINT_TOLERANCE };
+
+		if distance_to_target < distance_margin {
+			if let Some(next) = path.next.pop() {
+				path.current = next;
+			} else {
+				commands.entity(movable_entity)
+					.insert(NpcTaskMoveFinished)
+					.remove::<MovePath>()
+					.remove::<NpcTaskMove>()
+				;
+
+				movable_transform.translation.x = path.current.x;
+				movable_transform.translation.z = path.current.z;
+
+				if let Some(target_rotation) = path.target_rotation {
+					movable_transform.rotation = target_rotation;
+				}
+
+				if let Some(target_entity) = path.target_entity {
+					if let Ok(_) = q_locator.get(target_entity) {
+						commands.entity(movable_entity).insert(Occupies { 0: target_entity });
+						commands.entity(target_entity).insert(Occupied);
+					}
+				}
 			}
 		}
+
+		// new position still has the old height, so npc keeps to its layer: it climbs a ramp it walked onto and stays under a bridge
+		movable_transform.translation.y = polyanya.ground_height(movable_transform.translation, &terrain) + height_above_ground;
 	}
 }
 
 pub fn update_task_resource_collection(
+		unit_definitions	: Res<UnitDefinitions>,
 		polyanya			: Res<PolyanyaResource>,
-		q_interactable_locators	: Query<(Entity, &Transform), (With<NpcInteractable>, With<LocatorsContainer>, Without<NpcMovable>, Without<Locator>)>,
+		q_interactable_locators	: Query<(Entity, &Transform), (With<NpcInteractable>, With<LocatorsContainer>, Without<NpcMovable>, Without<Locator>, Without<UnderConstruction>)>,
 		q_task_move			: Query<&MovePath, With<NpcTaskMove>>,
 		q_task_move_finished: Query<&NpcTaskMoveFinished>,
 		q_children			: Query<&Children>,
 		q_locator			: Query<(&Locator, &GlobalTransform, Option<&Occupied>)>,
 	mut q_active_collector	: Query<(&ActiveCollecting, &mut ResourceCollector)>,
 	mut q_handheld			: Query<&mut Handheld>,
-	mut q_task_owner		: Query<(Entity, &HandheldOwner, &Transform, &mut NpcTaskResourceCollection), Without<BaseBuilding>>,
+		q_base				: Query<(Entity, &Transform, &Faction), With<BaseBuilding>>,
+		q_command_queue		: Query<&NpcCommandQueue>,
+	mut q_task_owner		: Query<(Entity, &UnitType, &Faction, &HandheldOwner, &Transform, &mut NpcTaskResourceCollection), Without<BaseBuilding>>,
 	mut commands			: Commands,
 ) {
-	let navmesh = &polyanya.mesh;
+	for (npc_entity, unit_type, faction, handheld_owner, npc_transform, mut task) in q_task_owner.iter_mut() {
+		let unit_speed = unit_definitions.get(*unit_type).speed;
 
-	for (npc_entity, handheld_owner, npc_transform, mut task) in q_task_owner.iter_mut() {
 		let Ok(mut handheld) = q_handheld.get_mut(handheld_owner.handheld_entity) else { panic!("HandheldOwner entity has no Handheld component!") };
 
 		match task.stage {
@@ -496,7 +863,7 @@ pub fn update_task_resource_collection(
 						npc_transform,
 						&q_children,
 						&q_locator,
-						navmesh
+						&polyanya
 					) {
 						commands.entity(npc_entity).insert(new_move_path);
 					}
@@ -520,9 +887,25 @@ pub fn update_task_resource_collection(
 
 				// moving to base is finished, now deliver resources and start the cycle over
 				if task_move_query_res.is_err() && (task_move_finished_query_res.is_ok() || q_active_collector.get(npc_entity).is_err()) {
+					// more commands are queued, mining is over once resources are delivered
+					if q_command_queue.get(npc_entity).is_ok_and(|command_queue| !command_queue.is_empty()) {
+						if q_active_collector.get(npc_entity).is_ok() { continue }
+
+						commands.entity(npc_entity)
+							.remove::<NpcTaskMoveFinished>()
+							.remove::<NpcTaskResourceCollection>()
+						;
+
+						continue;
+					}
+
 					commands.entity(npc_entity).remove::<NpcTaskMoveFinished>();
 
-					let Some(resource_entity) = task.resource_entity else { panic!("for now we always assume resource entity is there") };
+					// deposit was depleted while npc was delivering and there was no other deposit of the same type to retarget to
+					let Some(resource_entity) = task.resource_entity else {
+						commands.entity(npc_entity).remove::<NpcTaskResourceCollection>();
+						continue;
+					};
 
 					let Ok((container_entity, _)) = q_interactable_locators.get(resource_entity) else { panic!("resource_entity has either no Transform component or no LocatorsContainer component!") };
 
@@ -532,12 +915,12 @@ pub fn update_task_resource_collection(
 						npc_transform,
 						&q_children,
 						&q_locator,
-						navmesh
+						&polyanya
 					) {
 						commands.entity(npc_entity).insert((
 							new_move_path,
 							NpcTaskMove {
-								speed : 2.0
+								speed : unit_speed
 							}
 						));
 
@@ -550,22 +933,15 @@ pub fn update_task_resource_collection(
 				// if we're here it means npc has just finished collecting resources and needs a path to base building
 				let Ok((_, mut resource_collector)) = q_active_collector.get_mut(npc_entity) else { panic!("npc has either no ResourceCollector or ActiveCollecting component but it's in ResourceCollectionStage::MovingToBase") };
 
-				// find nearest base if no cache available
+				// find nearest base of the same faction if no cache available
 				let base_building_entity = match resource_collector.base_building_entity {
 					Some(entity) => entity,
 					None => {
-						let (mut nearest_entity, mut nearest_distance_sq) = (None, f32::MAX);
-						for (base_entity, base_transform) in q_interactable_locators.iter() {
-							let distance_sq = npc_transform.translation.distance_squared(base_transform.translation);
-
-							if distance_sq < nearest_distance_sq {
-								nearest_distance_sq = distance_sq;
-								nearest_entity = Some(base_entity);
-							}
-						}
+						// faction has no base to deliver to, keep carrying until it gets one
+						let Some(nearest_entity) = pick_nearest_base(*faction, npc_transform.translation, &q_base) else { continue };
 
-						resource_collector.base_building_entity = nearest_entity;
-						nearest_entity.unwrap()
+						resource_collector.base_building_entity = Some(nearest_entity);
+						nearest_entity
 					}
 				};
 
@@ -575,12 +951,12 @@ pub fn update_task_resource_collection(
 					npc_transform,
 					&q_children,
 					&q_locator,
-					navmesh
+					&polyanya
 				) {
 					commands.entity(npc_entity).insert((
 						move_path,
 						NpcTaskMove {
-							speed : 2.0
+							speed : unit_speed
 						}
 					));
 				}
@@ -589,16 +965,409 @@ pub fn update_task_resource_collection(
 	}
 }
 
+pub fn update_task_construction(
+		polyanya			: Res<PolyanyaResource>,
+		q_construction		: Query<(), With<UnderConstruction>>,
+		q_task_move			: Query<&MovePath, With<NpcTaskMove>>,
+		q_task_move_finished: Query<&NpcTaskMoveFinished>,
+		q_children			: Query<&Children>,
+		q_locator			: Query<(&Locator, &GlobalTransform, Option<&Occupied>)>,
+		q_task_owner		: Query<(Entity, &HandheldOwner, &Transform, &NpcTaskConstruction)>,
+	mut q_handheld			: Query<&mut Handheld>,
+	mut commands			: Commands,
+) {
+	for (npc_entity, handheld_owner, npc_transform, task) in q_task_owner.iter() {
+		let Ok(mut handheld) = q_handheld.get_mut(handheld_owner.handheld_entity) else { panic!("HandheldOwner entity has no Handheld component!") };
+
+		// building is finished or was removed, worker stays where it is
+		if q_construction.get(task.building_entity).is_err() {
+			handheld.deactivate();
+
+			commands.entity(npc_entity).remove::<NpcTaskConstruction>();
+
+			continue;
+		}
+
+		if q_task_move_finished.get(npc_entity).is_ok() {
+			handheld.activate();
+
+			commands.entity(npc_entity).remove::<NpcTaskMoveFinished>();
+		} else if let Ok(move_path) = q_task_move.get(npc_entity) {
+			if !move_path.obstructed { continue }
+
+			if let Some(new_move_path) = make_path_to_nearest_locator(
+				Locator::Interact,
+				&task.building_entity,
+				npc_transform,
+				&q_children,
+				&q_locator,
+				&polyanya
+			) {
+				commands.entity(npc_entity).insert(new_move_path);
+			}
+		}
+	}
+}
+
+/// Walks to the nearest base of unit's faction, task is over once resources are delivered or if there is nothing to deliver
+pub fn update_task_return_cargo(
+		unit_definitions	: Res<UnitDefinitions>,
+		polyanya			: Res<PolyanyaResource>,
+		q_base				: Query<(Entity, &Transform, &Faction), With<BaseBuilding>>,
+		q_active_collecting	: Query<(), With<ActiveCollecting>>,
+		q_task_move			: Query<(), With<MovePath>>,
+		q_task_move_finished: Query<(), With<NpcTaskMoveFinished>>,
+		q_children			: Query<&Children>,
+		q_locator			: Query<(&Locator, &GlobalTransform, Option<&Occupied>)>,
+		q_task_owner		: Query<(Entity, &UnitType, &Faction, &Transform), With<NpcTaskReturnCargo>>,
+	mut commands			: Commands,
+) {
+	for (npc_entity, unit_type, faction, npc_transform) in q_task_owner.iter() {
+		// delivered, unit stays at base instead of going back to mine
+		if q_active_collecting.get(npc_entity).is_err() {
+			commands.entity(npc_entity)
+				.remove::<NpcTaskReturnCargo>()
+				.remove::<NpcTaskMoveFinished>()
+			;
+
+			continue;
+		}
+
+		// on the way or already at base waiting for delivery
+		if q_task_move.get(npc_entity).is_ok() || q_task_move_finished.get(npc_entity).is_ok() { continue }
+
+		let Some(base_entity) = pick_nearest_base(*faction, npc_transform.translation, &q_base) else {
+			println!("{:?} faction has no base to return cargo to!", faction);
+			commands.entity(npc_entity).remove::<NpcTaskReturnCargo>();
+			continue;
+		};
+
+		let Some(move_path) = make_path_to_nearest_locator(Locator::Interact, &base_entity, npc_transform, &q_children, &q_locator, &polyanya) else {
+			println!("Error: can't create path from {:?} to base {:?}!", npc_transform.translation, base_entity);
+			commands.entity(npc_entity).remove::<NpcTaskReturnCargo>();
+			continue;
+		};
+
+		commands.entity(npc_entity).insert((
+			move_path,
+			NpcTaskMove {
+				speed : unit_definitions.get(*unit_type).speed
+			}
+		));
+	}
+}
+
+/// Next point of the route is picked once unit has walked to the previous one
+pub fn update_task_patrol(
+		unit_definitions	: Res<UnitDefinitions>,
+		polyanya			: Res<PolyanyaResource>,
+	mut q_task_owner		: Query<(Entity, &UnitType, &Transform, &mut NpcTaskPatrol), Without<MovePath>>,
+	mut commands			: Commands,
+) {
+	for (npc_entity, unit_type, npc_transform, mut patrol) in q_task_owner.iter_mut() {
+		commands.entity(npc_entity).remove::<NpcTaskMoveFinished>();
+
+		let Some(point) = patrol.points.get(patrol.next).copied() else {
+			commands.entity(npc_entity).remove::<NpcTaskPatrol>();
+			continue;
+		};
+
+		patrol.next = (patrol.next + 1) % patrol.points.len();
+
+		// navmesh could change since route was given, e.g. a building was placed on it
+		let Some(move_path) = make_path_to_point(npc_transform.translation, point, &polyanya) else {
+			println!("Error: can't create path from {:?} to patrol point {:?}! patrol is cancelled", npc_transform.translation, point);
+			commands.entity(npc_entity).remove::<NpcTaskPatrol>();
+			continue;
+		};
+
+		commands.entity(npc_entity).insert((
+			move_path,
+			NpcTaskMove {
+				speed : unit_definitions.get(*unit_type).speed
+			}
+		));
+	}
+}
+
+/// Path to target is made again only when target got far enough from where the current path leads,
+/// follower stops once it's close to target and waits for it to move away
+pub fn update_task_follow(
+		unit_definitions	: Res<UnitDefinitions>,
+		polyanya			: Res<PolyanyaResource>,
+		q_target			: Query<&GlobalTransform>,
+		q_task_move			: Query<(), With<MovePath>>,
+	mut q_task_owner		: Query<(Entity, &UnitType, &Transform, &mut NpcTaskFollow)>,
+	mut commands			: Commands,
+) {
+	for (npc_entity, unit_type, npc_transform, mut follow) in q_task_owner.iter_mut() {
+		// target was despawned
+		let Ok(target_transform) = q_target.get(follow.target_entity) else {
+			commands.entity(npc_entity).remove::<NpcTaskFollow>();
+			continue;
+		};
+
+		// heights are kept for navmesh to tell which layer both are on, distances are measured on the ground plane
+		let target_position = target_transform.translation();
+
+		let npc_position = npc_transform.translation;
+
+		let moving = q_task_move.get(npc_entity).is_ok();
+
+		if npc_position.xz().distance(target_position.xz()) <= FOLLOW_DISTANCE {
+			if moving {
+				commands.entity(npc_entity)
+					.remove::<MovePath>()
+					.remove::<NpcTaskMove>()
+				;
+			}
+
+			follow.planned_for = None;
+			continue;
+		}
+
+		if moving && follow.planned_for.is_some_and(|planned_for| planned_for.xz().distance(target_position.xz()) < FOLLOW_REPLAN_DISTANCE) { continue }
+
+		// target can be off navmesh for a moment, e.g. player jumping on an obstacle, then the old path is kept
+		let Some(move_path) = make_path_to_point(npc_position, target_position, &polyanya) else { continue };
+
+		follow.planned_for = Some(target_position);
+
+		commands.entity(npc_entity)
+			.remove::<NpcTaskMoveFinished>()
+			.insert((
+				move_path,
+				NpcTaskMove {
+					speed : unit_definitions.get(*unit_type).speed
+				}
+			))
+		;
+	}
+}
+
+/// Starts next queued command once unit is done with the previous one. Commands are carried out the same way rally points are
+pub fn update_command_queue(
+		q_transform			: Query<&GlobalTransform>,
+		q_site				: Query<(), With<UnderConstruction>>,
+	mut q_task_owner		: Query<(Entity, &Transform, &mut NpcCommandQueue), (
+			Without<NpcTaskRally>,
+			Without<MovePath>,
+			Without<NpcTaskResourceCollection>,
+			Without<NpcTaskConstruction>,
+			Without<NpcTaskReturnCargo>,
+			Without<NpcTaskPatrol>,
+			Without<NpcTaskFollow>,
+			Without<HoldPosition>
+		)>,
+	mut commands			: Commands,
+) {
+	for (npc_entity, npc_transform, mut command_queue) in q_task_owner.iter_mut() {
+		let Some(command) = command_queue.pop_front() else { continue };
+
+		let (position, target_entity) = match command {
+			NpcCommand::Move(position) => (position, None),
+			NpcCommand::ReturnCargo => {
+				commands.entity(npc_entity).insert(NpcTaskReturnCargo);
+				continue;
+			},
+			NpcCommand::Follow(entity) => {
+				commands.entity(npc_entity).insert(NpcTaskFollow::new(entity));
+				continue;
+			},
+			NpcCommand::Patrol(point) => {
+				let mut points = vec![npc_transform.translation, point];
+
+				while let Some(NpcCommand::Patrol(point)) = command_queue.front().copied() {
+					points.push(point);
+					command_queue.pop_front();
+				}
+
+				commands.entity(npc_entity).insert(NpcTaskPatrol::new(points));
+				continue;
+			},
+			NpcCommand::Mine(entity) | NpcCommand::Build(entity) => {
+				// deposit got depleted or building got finished while unit was busy with previous commands
+				let Ok(transform) = q_transform.get(entity) else { continue };
+
+				if matches!(command, NpcCommand::Build(_)) && q_site.get(entity).is_err() { continue }
+
+				(transform.translation(), Some(entity))
+			},
+		};
+
+		commands.entity(npc_entity).insert(NpcTaskRally(RallyPoint {
+			position,
+			target_entity,
+		}));
+	}
+}
+
+/// Sends unit to rally point, previous task is dropped. If rally point is on a deposit or on a construction site
+/// of unit's faction and unit can drill, it starts mining or building right away
+pub fn start_task_rally(
+		unit_definitions	: Res<UnitDefinitions>,
+		polyanya			: Res<PolyanyaResource>,
+		q_deposit			: Query<Entity, (With<LocatorsContainer>, With<NpcInteractable>, With<CollectableResource>)>,
+		q_site				: Query<&Faction, (With<LocatorsContainer>, With<NpcInteractable>, With<UnderConstruction>)>,
+		q_children			: Query<&Children>,
+		q_locator			: Query<(&Locator, &GlobalTransform, Option<&Occupied>)>,
+		q_task_owner		: Query<(Entity, &UnitType, &Faction, &Transform, &NpcTaskRally, Option<&HandheldOwner>)>,
+	mut q_handheld			: Query<&mut Handheld>,
+	mut q_collector			: Query<&mut ResourceCollector>,
+	mut commands			: Commands,
+) {
+	for (npc_entity, unit_type, faction, npc_transform, rally, handheld_owner) in q_task_owner.iter() {
+		commands.entity(npc_entity)
+			.remove::<NpcTaskRally>()
+			.remove::<NpcTaskResourceCollection>()
+			.remove::<NpcTaskConstruction>()
+			.remove::<NpcTaskMoveFinished>()
+		;
+
+		if let Some(handheld_owner) = handheld_owner {
+			if let Ok(mut handheld) = q_handheld.get_mut(handheld_owner.handheld_entity) {
+				if handheld.activated() {
+					handheld.deactivate();
+				}
+			}
+		}
+
+		// only units with a drill can mine or build
+		let target_entity = rally.target_entity.filter(|_| handheld_owner.is_some());
+
+		let move_path = match target_entity {
+			Some(deposit_entity) if q_deposit.get(deposit_entity).is_ok() => {
+				commands.entity(npc_entity).insert(NpcTaskResourceCollection {
+					resource_entity : Some(deposit_entity),
+					..default()
+				});
+
+				// deposit can be far from the base unit came from, deliver to whichever base is nearest instead
+				if let Ok(mut resource_collector) = q_collector.get_mut(npc_entity) {
+					resource_collector.base_building_entity = None;
+				}
+
+				make_path_to_nearest_locator(Locator::Interact, &deposit_entity, npc_transform, &q_children, &q_locator, &polyanya)
+			},
+			Some(site_entity) if q_site.get(site_entity).is_ok_and(|site_faction| site_faction == faction) => {
+				commands.entity(npc_entity).insert(NpcTaskConstruction {
+					building_entity : site_entity,
+				});
+
+				make_path_to_nearest_locator(Locator::Interact, &site_entity, npc_transform, &q_children, &q_locator, &polyanya)
+			},
+			_ => make_path_to_point(npc_transform.translation, rally.position, &polyanya),
+		};
+
+		let Some(move_path) = move_path else {
+			println!("Error: can't create path from {:?} to rally point {:?}!", npc_transform.translation, rally.position);
+			continue;
+		};
+
+		commands.entity(npc_entity).insert((
+			move_path,
+			NpcTaskMove {
+				speed : unit_definitions.get(*unit_type).speed
+			}
+		));
+	}
+}
+
+pub fn retarget_task_resource_collection(
+		unit_definitions	: Res<UnitDefinitions>,
+		polyanya			: Res<PolyanyaResource>,
+		q_deposit			: Query<(Entity, &CollectableResource, &ResourceDeposit, &GlobalTransform)>,
+		q_children			: Query<&Children>,
+		q_locator			: Query<(&Locator, &GlobalTransform, Option<&Occupied>)>,
+		q_active_collector	: Query<(&ActiveCollecting, &ResourceCollector)>,
+	mut q_handheld			: Query<&mut Handheld>,
+	mut q_task_owner		: Query<(Entity, &UnitType, &HandheldOwner, &Transform, &mut NpcTaskResourceCollection)>,
+	mut commands			: Commands,
+) {
+	for (npc_entity, unit_type, handheld_owner, npc_transform, mut task) in q_task_owner.iter_mut() {
+		let Some(resource_entity) = task.resource_entity else { continue };
+
+		// only tasks pointing at depleted or already despawned deposits need a new target
+		let resource_type = match q_deposit.get(resource_entity) {
+			Ok((_, _, deposit, _)) if !deposit.depleted() => continue,
+			Ok((_, resource_type, _, _)) => Some(*resource_type),
+			Err(_) => None,
+		};
+
+		let new_resource_entity = resource_type.and_then(|resource_type| {
+			pick_nearest_deposit(resource_type, resource_entity, npc_transform.translation, &q_deposit)
+		});
+
+		task.resource_entity = new_resource_entity;
+
+		// npc on its way to base keeps going and picks up new target (or goes idle) after delivery
+		if let ResourceCollectionStage::MovingToBase = task.stage { continue }
+
+		if let Ok(mut handheld) = q_handheld.get_mut(handheld_owner.handheld_entity) {
+			handheld.deactivate();
+		}
+
+		commands.entity(npc_entity).remove::<NpcTaskMoveFinished>();
+
+		let (carried_shards_num, carrying_limit_reached) = match q_active_collector.get(npc_entity) {
+			Ok((collecting, collector)) => (collecting.total_shards_num(), collecting.total_shards_num() >= collector.limit),
+			Err(_) => (0, false),
+		};
+
+		// deliver what was collected so far first, update_task_resource_collection will make a path to base
+		if carrying_limit_reached || (new_resource_entity.is_none() && carried_shards_num > 0) {
+			commands.entity(npc_entity)
+				.remove::<MovePath>()
+				.remove::<NpcTaskMove>()
+			;
+
+			task.stage = ResourceCollectionStage::MovingToBase;
+
+			continue;
+		}
+
+		let new_move_path = new_resource_entity.and_then(|container_entity| {
+			make_path_to_nearest_locator(
+				Locator::Interact,
+				&container_entity,
+				npc_transform,
+				&q_children,
+				&q_locator,
+				&polyanya
+			)
+		});
+
+		if let Some(move_path) = new_move_path {
+			commands.entity(npc_entity).insert((
+				move_path,
+				NpcTaskMove {
+					speed : unit_definitions.get(*unit_type).speed
+				}
+			));
+
+			task.stage = ResourceCollectionStage::MovingToResource;
+		} else {
+			// nothing left to mine of this type so npc stops where it is
+			commands.entity(npc_entity)
+				.remove::<NpcTaskResourceCollection>()
+				.remove::<MovePath>()
+				.remove::<NpcTaskMove>()
+			;
+		}
+	}
+}
+
 pub fn spawn_task_resource_collection(
+		unit_definitions	: Res<UnitDefinitions>,
 		rapier_context	: Res<RapierContext>,
 		polyanya		: Res<PolyanyaResource>,
 		game_assets		: Res<GameAssets>,
-		q_task			: Query<(Entity, &NpcSpawnTaskResourceCollection)>,
+		q_task			: Query<(Entity, &NpcSpawnTaskResourceCollection, &Faction)>,
 		q_children		: Query<&Children>,
 		q_locator		: Query<(&Locator, &GlobalTransform, Option<&Occupied>)>,
 	mut commands		: Commands,
 ) {
-	for (spawner_entity, task) in q_task.iter() {
+	for (spawner_entity, task, faction) in q_task.iter() {
 		let mut leftovers = Vec::new();
 
 		for batch in task.queue.iter() {
@@ -614,13 +1383,14 @@ pub fn spawn_task_resource_collection(
 
 				let locator_transform = locator_transform_global.compute_transform();
 
-				let Some((svin_entity, _svin_collider)) = spawn::svin(
+				let Some((svin_entity, _svin_collider)) = spawn::unit(
 					&locator_transform,
-					SVIN_CARRYING_CAPACITY,
+					UnitType::Svin,
+					unit_definitions.get(UnitType::Svin),
+					*faction,
 					&game_assets,
-					true, // with_drill
 					Some(spawner_entity),
-					Some(&polyanya.mesh),
+					Some(&polyanya),
 					Some(&rapier_context),
 					&mut commands
 				) else { continue };
@@ -633,7 +1403,7 @@ pub fn spawn_task_resource_collection(
 					&locator_transform,
 					&q_children,
 					&q_locator,
-					&polyanya.mesh
+					&polyanya
 				) else { continue };
 
 				commands.entity(svin_entity)
@@ -643,7 +1413,7 @@ pub fn spawn_task_resource_collection(
 					})
 					.insert(move_path)
 					.insert(NpcTaskMove {
-						speed : 2.0
+						speed : unit_definitions.get(UnitType::Svin).speed
 					})
 				;
 
@@ -668,8 +1438,10 @@ pub fn spawn_task_resource_collection(
 }
 
 pub fn spawn_svin_at_raypicked_base(
+		unit_definitions	: Res<UnitDefinitions>,
 		key				: Res<ButtonInput<KeyCode>>,
 		main_entities 	: Res<MainEntities>,
+		camera_mode		: Res<State<CameraMode>>,
 		polyanya		: Res<PolyanyaResource>,
 		game_assets		: Res<GameAssets>,
 		rapier_context	: Res<RapierContext>,
@@ -677,7 +1449,7 @@ pub fn spawn_svin_at_raypicked_base(
 		q_children		: Query<&Children>,
 		q_locator		: Query<&Locator>,
 		q_transform		: Query<&Transform>,
-		q_base_building	: Query<Entity, (With<LocatorsContainer>, With<BaseBuilding>)>,
+		q_base_building	: Query<(Entity, &Faction, Option<&RallyPoint>), (With<LocatorsContainer>, With<BaseBuilding>)>,
 	mut commands		: Commands,
 ) {
 	let needed_keys_pressed = key.just_pressed(KeyCode::NumpadAdd);
@@ -686,11 +1458,11 @@ pub fn spawn_svin_at_raypicked_base(
 		return
 	}
 
-	let Ok(raypick) = q_raypick.get(main_entities.player_camera) else { panic!("player camera has no raypick!") };
+	let Ok(raypick) = q_raypick.get(main_entities.raypick_camera(*camera_mode.get())) else { panic!("camera has no raypick!") };
 
 	let Some(raypicked_entity) = raypick.entity else { return };
 
-	let Ok(base_entity) = q_base_building.get(raypicked_entity) else { return };
+	let Ok((base_entity, faction, rally_point)) = q_base_building.get(raypicked_entity) else { return };
 
 	for locator_entity in q_children.iter_descendants(base_entity) {
 		let Ok(locator) = q_locator.get(locator_entity) else { continue };
@@ -699,16 +1471,21 @@ pub fn spawn_svin_at_raypicked_base(
 
 		let Ok(locator_transform) = q_transform.get(locator_entity) else { panic!("entity with Locator::Spawn component has no Transform component!") };
 
-		if spawn::svin(
+		let Some((svin_entity, _)) = spawn::unit(
 			&locator_transform,
-			SVIN_CARRYING_CAPACITY,
+			UnitType::Svin,
+			unit_definitions.get(UnitType::Svin),
+			*faction,
 			&game_assets,
-			true, // with_drill
 			Some(base_entity),
-			Some(&polyanya.mesh),
+			Some(&polyanya),
 			Some(&rapier_context),
 			&mut commands
-		).is_none() { continue };
+		) else { continue };
+
+		if let Some(rally_point) = rally_point {
+			commands.entity(svin_entity).insert(NpcTaskRally(*rally_point));
+		}
 
 		break;
 	}
@@ -752,11 +1529,11 @@ pub fn spawn_stresstest(
 		let purplite_pos	= Vec3::new(-3.0, 0.0, 15.0) + offset;
 		let base_pos		= Vec3::ZERO + offset;
 
-		let tealite_entity = spawn::tealite(Transform::from_translation(tealite_pos), &game_assets, &mut commands);
+		let tealite_entity = spawn::tealite(Transform::from_translation(tealite_pos), DEPOSIT_DEFAULT_AMOUNT, &game_assets, &mut commands);
 
-		let purplite_entity = spawn::purplite(Transform::from_translation(purplite_pos), &game_assets, &mut commands);
+		let purplite_entity = spawn::purplite(Transform::from_translation(purplite_pos), DEPOSIT_DEFAULT_AMOUNT, &game_assets, &mut commands);
 
-		let base_entity = spawn::base_building(Transform::from_translation(base_pos), &game_assets, &mut commands);
+		let base_entity = spawn::base_building(Transform::from_translation(base_pos), Faction::default(), &game_assets, &mut commands);
 
 		commands.entity(base_entity).insert(
 			NpcSpawnTaskResourceCollection { queue: [(4, tealite_entity), (4, purplite_entity)].to_vec() }
@@ -0,0 +1,697 @@
+use bevy :: prelude :: *;
+
+use super :: {
+	FOLLOW_DISTANCE,
+	HoldPosition,
+	Locator,
+	MovePath,
+	NpcCommand,
+	NpcCommandQueue,
+	NpcSpawnTaskResourceCollection,
+	NpcTaskFollow,
+	NpcTaskMove,
+	NpcTaskPatrol,
+	NpcTaskRally,
+	NpcTaskResourceCollection,
+	NpcTaskReturnCargo,
+	RallyPoint,
+	navmesh :: { NavmeshTiles, collider_footprint, boundary_obstacles },
+	layers :: NavLayer,
+	avoidance :: { AvoidanceNeighbour, AvoidanceObstacle, orca_velocity },
+};
+
+use crate :: handheld :: { Handheld, HandheldOwner };
+
+use crate :: test_support :: TestApp;
+
+use crate :: map :: {
+	GroundBounds,
+	Terrain,
+	Level,
+	LevelSide,
+	MapLevel,
+	MapRamp,
+};
+
+use crate :: setup :: terrain_navmesh;
+
+use bevy_rapier3d :: prelude :: Collider;
+
+use std :: f32 :: consts :: FRAC_PI_4;
+
+use crate :: units :: UnitType;
+
+use crate :: faction :: Faction;
+
+use crate :: resource_collection :: {
+	ActiveCollecting,
+	CollectableResource,
+	FactionResources,
+	ResourceCollectionStage,
+};
+
+// longest trip between base and deposit is a few seconds, anything above this means svin is stuck
+const MAX_SECONDS_IN_STAGE : f32 = 45.0;
+
+#[test]
+fn single_svin_completes_collection_cycle() {
+	let mut test_app = TestApp::new();
+
+	let svin_capacity = test_app.unit_definitions().get(UnitType::Svin).capacity;
+
+	let tealite_entity = test_app.deposit(CollectableResource::Tealite);
+
+	test_app.queue_svins(&[(1, tealite_entity)]);
+
+	let delivered = test_app.step_until(90.0, |world| world.resource::<FactionResources>().get(Faction::default()).tealite >= svin_capacity);
+	assert!(delivered, "svin didn't deliver tealite in time");
+
+	// give svin time to turn back to deposit after delivery
+	test_app.step_seconds(5.0);
+
+	let svins = test_app.svins();
+	assert_eq!(svins.len(), 1);
+
+	let svin_entity = svins[0];
+
+	test_app.assert_full_cycle(svin_entity);
+
+	let record = test_app.record(svin_entity);
+	assert_eq!(record.stages.first(), Some(&ResourceCollectionStage::MovingToResource));
+	assert!(record.deliveries >= 1);
+
+	let Some(task) = test_app.task(svin_entity) else { panic!("svin lost its task after delivery") };
+	assert_eq!(task.resource_entity, Some(tealite_entity));
+
+	assert_eq!(test_app.collected().purplite, 0);
+
+	test_app.assert_no_svin_stuck(MAX_SECONDS_IN_STAGE);
+}
+
+#[test]
+fn svins_crowding_one_deposit_dont_get_stuck() {
+	let mut test_app = TestApp::new();
+
+	let purplite_entity = test_app.deposit(CollectableResource::Purplite);
+
+	let svins_num = 6;
+
+	test_app.queue_svins(&[(svins_num, purplite_entity)]);
+
+	test_app.step_seconds(120.0);
+
+	let svins = test_app.svins();
+	assert_eq!(svins.len(), svins_num);
+
+	for svin_entity in svins.iter() {
+		assert!(test_app.record(*svin_entity).deliveries >= 1, "svin {:?} never delivered anything: {:?}", svin_entity, test_app.record(*svin_entity));
+	}
+
+	assert!(test_app.collected().purplite >= test_app.total_deliveries());
+
+	test_app.assert_no_svin_stuck(MAX_SECONDS_IN_STAGE);
+}
+
+#[test]
+fn spawning_waits_for_blocked_spawn_locators() {
+	let mut test_app = TestApp::new();
+
+	let base_entity = test_app.base();
+	let tealite_entity = test_app.deposit(CollectableResource::Tealite);
+
+	let spawn_locators = test_app.locators(base_entity, Locator::Spawn);
+	assert!(!spawn_locators.is_empty(), "base building has no spawn locators");
+
+	let blockers = spawn_locators.iter()
+		.map(|(_, locator_transform)| test_app.spawn_blocker(locator_transform.translation()))
+		.collect::<Vec<Entity>>();
+
+	// let physics pick up blockers
+	test_app.step(2);
+
+	test_app.queue_svins(&[(2, tealite_entity)]);
+
+	test_app.step_seconds(3.0);
+
+	assert!(test_app.svins().is_empty(), "svin was spawned on a blocked locator");
+	assert!(test_app.app.world.get::<NpcSpawnTaskResourceCollection>(base_entity).is_some(), "spawn task was dropped while locators were blocked");
+
+	for blocker_entity in blockers {
+		test_app.despawn(blocker_entity);
+	}
+
+	let spawned = test_app.step_until(10.0, |world| {
+		world.query_filtered::<(), With<NpcTaskResourceCollection>>().iter(world).count() == 2
+	});
+	assert!(spawned, "svins were not spawned after spawn locators got free");
+
+	test_app.step(2);
+
+	assert!(test_app.app.world.get::<NpcSpawnTaskResourceCollection>(base_entity).is_none());
+}
+
+#[test]
+fn depleted_deposit_retargets_to_nearest_deposit_of_same_type() {
+	let mut test_app = TestApp::new();
+
+	let svin_capacity = test_app.unit_definitions().get(UnitType::Svin).capacity;
+
+	let tealite_entity = test_app.deposit(CollectableResource::Tealite);
+
+	test_app.set_deposit_amount(tealite_entity, svin_capacity);
+
+	let other_tealite_entity = test_app.spawn_deposit(CollectableResource::Tealite, Vec3::new(10.0, 0.0, 15.0), 100);
+
+	test_app.queue_svins(&[(1, tealite_entity)]);
+
+	let depleted = test_app.step_until(60.0, |world| world.get_entity(tealite_entity).is_none());
+	assert!(depleted, "deposit wasn't depleted in time");
+
+	let svin_entity = test_app.svins()[0];
+
+	let Some(task) = test_app.task(svin_entity) else { panic!("svin went idle while another tealite deposit was available") };
+	assert_eq!(task.resource_entity, Some(other_tealite_entity));
+
+	let collected_more = test_app.step_until(90.0, |world| world.resource::<FactionResources>().get(Faction::default()).tealite >= svin_capacity * 2);
+	assert!(collected_more, "svin didn't collect from new deposit");
+
+	assert!(test_app.deposit_exists(other_tealite_entity));
+
+	test_app.assert_no_svin_stuck(MAX_SECONDS_IN_STAGE);
+}
+
+#[test]
+fn depleted_deposit_without_replacement_sends_svin_idle_after_delivery() {
+	let mut test_app = TestApp::new();
+
+	let svin_capacity = test_app.unit_definitions().get(UnitType::Svin).capacity;
+
+	let purplite_entity = test_app.deposit(CollectableResource::Purplite);
+
+	test_app.set_deposit_amount(purplite_entity, svin_capacity);
+
+	test_app.queue_svins(&[(1, purplite_entity)]);
+
+	let idle = test_app.step_until(90.0, |world| {
+		world.resource::<FactionResources>().get(Faction::default()).purplite > 0 &&
+		world.query_filtered::<(), With<NpcTaskResourceCollection>>().iter(world).next().is_none()
+	});
+	assert!(idle, "svin didn't go idle after delivering the last of the deposit");
+
+	assert!(!test_app.deposit_exists(purplite_entity));
+	assert_eq!(test_app.collected().purplite, svin_capacity);
+
+	test_app.assert_no_svin_stuck(MAX_SECONDS_IN_STAGE);
+}
+
+#[test]
+fn queued_mine_command_starts_after_delivery() {
+	let mut test_app = TestApp::new();
+
+	let svin_capacity = test_app.unit_definitions().get(UnitType::Svin).capacity;
+
+	let tealite_entity = test_app.deposit(CollectableResource::Tealite);
+	let purplite_entity = test_app.deposit(CollectableResource::Purplite);
+
+	test_app.queue_svins(&[(1, tealite_entity)]);
+
+	let spawned = test_app.step_until(10.0, |world| world.query_filtered::<(), With<NpcTaskResourceCollection>>().iter(world).next().is_some());
+	assert!(spawned, "svin wasn't spawned");
+
+	let svin_entity = test_app.svins()[0];
+
+	let Some(mut command_queue) = test_app.app.world.get_mut::<NpcCommandQueue>(svin_entity) else { panic!("svin has no command queue!") };
+
+	command_queue.push_back(NpcCommand::Mine(purplite_entity));
+
+	let switched = test_app.step_until(90.0, |world| world.get::<NpcTaskResourceCollection>(svin_entity).is_some_and(|task| task.resource_entity == Some(purplite_entity)));
+	assert!(switched, "svin didn't start queued command");
+
+	assert_eq!(test_app.collected().tealite, svin_capacity, "svin should deliver exactly one load of tealite before switching");
+
+	let Some(command_queue) = test_app.app.world.get::<NpcCommandQueue>(svin_entity) else { panic!("svin lost its command queue!") };
+	assert!(command_queue.is_empty());
+
+	test_app.assert_no_svin_stuck(MAX_SECONDS_IN_STAGE);
+}
+
+#[test]
+fn queued_patrol_points_make_one_route_after_delivery() {
+	let mut test_app = TestApp::new();
+
+	let base_entity = test_app.base();
+	let tealite_entity = test_app.deposit(CollectableResource::Tealite);
+
+	let first_point = test_app.locators(base_entity, Locator::Spawn)[0].1.translation();
+	let second_point = test_app.locators(tealite_entity, Locator::Interact)[0].1.translation();
+
+	test_app.queue_svins(&[(1, tealite_entity)]);
+
+	let spawned = test_app.step_until(10.0, |world| world.query_filtered::<(), With<NpcTaskResourceCollection>>().iter(world).next().is_some());
+	assert!(spawned, "svin wasn't spawned");
+
+	let svin_entity = test_app.svins()[0];
+
+	let Some(mut command_queue) = test_app.app.world.get_mut::<NpcCommandQueue>(svin_entity) else { panic!("svin has no command queue!") };
+
+	command_queue.extend([NpcCommand::ReturnCargo, NpcCommand::Patrol(first_point), NpcCommand::Patrol(second_point)]);
+
+	let patrolling = test_app.step_until(90.0, |world| world.get::<NpcTaskPatrol>(svin_entity).is_some());
+	assert!(patrolling, "svin didn't start queued patrol");
+
+	assert!(test_app.collected().tealite > 0, "svin should deliver what it mined before patrolling");
+	assert!(test_app.task(svin_entity).is_none(), "svin kept mining after queued commands started");
+
+	let Some(patrol) = test_app.app.world.get::<NpcTaskPatrol>(svin_entity) else { unreachable!() };
+	assert_eq!(patrol.points.len(), 3, "consecutive patrol points should make one route starting where svin is");
+	assert_eq!(patrol.points[1 ..], [first_point, second_point]);
+
+	let Some(command_queue) = test_app.app.world.get::<NpcCommandQueue>(svin_entity) else { panic!("svin lost its command queue!") };
+	assert!(command_queue.is_empty());
+}
+
+#[test]
+fn return_cargo_delivers_partial_load_without_restarting_mining() {
+	let mut test_app = TestApp::new();
+
+	let tealite_entity = test_app.deposit(CollectableResource::Tealite);
+
+	test_app.queue_svins(&[(1, tealite_entity)]);
+
+	let collecting = test_app.step_until(60.0, |world| world.query_filtered::<(), With<ActiveCollecting>>().iter(world).next().is_some());
+	assert!(collecting, "svin didn't start collecting");
+
+	let svin_entity = test_app.svins()[0];
+
+	// same as pressing `c` with svin selected
+	let Some(handheld_owner) = test_app.app.world.get::<HandheldOwner>(svin_entity) else { panic!("svin has no drill!") };
+	let handheld_entity = handheld_owner.handheld_entity;
+
+	let Some(mut handheld) = test_app.app.world.get_mut::<Handheld>(handheld_entity) else { panic!("drill has no Handheld component!") };
+	handheld.deactivate();
+
+	test_app.app.world.entity_mut(svin_entity)
+		.remove::<NpcTaskResourceCollection>()
+		.insert(NpcTaskReturnCargo)
+	;
+
+	let delivered = test_app.step_until(60.0, |world| world.get::<NpcTaskReturnCargo>(svin_entity).is_none());
+	assert!(delivered, "svin didn't finish returning cargo");
+
+	let collected = test_app.collected().tealite;
+	assert!(collected > 0, "cargo wasn't delivered");
+
+	test_app.step_seconds(10.0);
+
+	assert!(test_app.task(svin_entity).is_none(), "svin went back to mining");
+	assert_eq!(test_app.collected().tealite, collected);
+}
+
+#[test]
+fn held_svin_isnt_pushed_or_retasked() {
+	let mut test_app = TestApp::new();
+
+	let tealite_entity = test_app.deposit(CollectableResource::Tealite);
+
+	test_app.queue_svins(&[(2, tealite_entity)]);
+
+	let spawned = test_app.step_until(10.0, |world| world.query_filtered::<(), With<NpcTaskResourceCollection>>().iter(world).count() == 2);
+	assert!(spawned, "svins weren't spawned");
+
+	let svins = test_app.svins();
+	let (held_entity, walker_entity) = (svins[0], svins[1]);
+
+	// same as pressing `h` with svin selected
+	for svin_entity in [held_entity, walker_entity] {
+		let Some(handheld_owner) = test_app.app.world.get::<HandheldOwner>(svin_entity) else { panic!("svin has no drill!") };
+		let handheld_entity = handheld_owner.handheld_entity;
+
+		let Some(mut handheld) = test_app.app.world.get_mut::<Handheld>(handheld_entity) else { panic!("drill has no Handheld component!") };
+		handheld.deactivate();
+
+		test_app.app.world.entity_mut(svin_entity)
+			.remove::<NpcTaskResourceCollection>()
+			.remove::<MovePath>()
+			.remove::<NpcTaskMove>()
+		;
+	}
+
+	test_app.app.world.entity_mut(held_entity).insert(HoldPosition);
+
+	test_app.step(2);
+
+	let Some(held_transform) = test_app.app.world.get::<Transform>(held_entity) else { panic!("svin has no Transform!") };
+	let held_position = held_transform.translation;
+
+	let Some(mut command_queue) = test_app.app.world.get_mut::<NpcCommandQueue>(held_entity) else { panic!("svin has no command queue!") };
+	command_queue.push_back(NpcCommand::Move(held_position + Vec3::X * 3.0));
+
+	// other svin is sent right onto the spot held svin stands at
+	let speed = test_app.unit_definitions().get(UnitType::Svin).speed;
+
+	test_app.app.world.entity_mut(walker_entity).insert((
+		MovePath::new(Vec3::new(held_position.x, 0.0, held_position.z), Vec::new(), None, None),
+		NpcTaskMove { speed },
+	));
+
+	let arrived = test_app.step_until(15.0, |world| world.get::<MovePath>(walker_entity).is_none());
+	assert!(arrived, "walking svin never got next to the held one");
+
+	test_app.step_seconds(2.0);
+
+	let Some(held_transform) = test_app.app.world.get::<Transform>(held_entity) else { panic!("svin has no Transform!") };
+	assert!(held_transform.translation.xz().distance(held_position.xz()) < 0.01, "held svin was pushed from {:?} to {:?}", held_position, held_transform.translation);
+
+	let Some(walker_transform) = test_app.app.world.get::<Transform>(walker_entity) else { panic!("svin has no Transform!") };
+	assert!(walker_transform.translation.xz().distance(held_position.xz()) > 0.1, "walking svin ended up inside the held one");
+
+	assert!(test_app.app.world.get::<HoldPosition>(held_entity).is_some(), "svin stopped holding position on its own");
+	assert!(test_app.app.world.get::<MovePath>(held_entity).is_none(), "held svin started walking");
+
+	let Some(command_queue) = test_app.app.world.get::<NpcCommandQueue>(held_entity) else { panic!("svin lost its command queue!") };
+	assert_eq!(command_queue.len(), 1, "held svin started its queued command");
+}
+
+/// Spawns svins at the base and takes their mining task away so they can be given other orders
+fn idle_svins(test_app: &mut TestApp, count: usize) -> Vec<Entity> {
+	let tealite_entity = test_app.deposit(CollectableResource::Tealite);
+
+	test_app.queue_svins(&[(count, tealite_entity)]);
+
+	let spawned = test_app.step_until(20.0, |world| world.query_filtered::<(), With<NpcTaskResourceCollection>>().iter(world).count() == count);
+	assert!(spawned, "svins were not spawned");
+
+	let svins = test_app.svins();
+
+	for svin_entity in svins.iter() {
+		test_app.app.world.entity_mut(*svin_entity).remove::<(MovePath, NpcTaskMove, NpcTaskResourceCollection)>();
+	}
+
+	svins
+}
+
+fn is_near(world: &World, entity: Entity, position: Vec3, distance: f32) -> bool {
+	world.get::<Transform>(entity).is_some_and(|transform| transform.translation.xz().distance(position.xz()) < distance)
+}
+
+#[test]
+fn patrolling_svin_walks_route_back_and_forth() {
+	let mut test_app = TestApp::new();
+
+	let svin_entity = idle_svins(&mut test_app, 1)[0];
+
+	let (point_a, point_b) = (Vec3::new(-10.0, 0.0, -10.0), Vec3::new(10.0, 0.0, -10.0));
+
+	test_app.app.world.entity_mut(svin_entity).insert(NpcTaskPatrol::new(vec![point_a, point_b]));
+
+	for (point, name) in [(point_b, "second"), (point_a, "first"), (point_b, "second")] {
+		let reached = test_app.step_until(30.0, |world| is_near(world, svin_entity, point, 0.5));
+		assert!(reached, "svin didn't reach {} patrol point", name);
+	}
+
+	assert!(test_app.app.world.get::<NpcTaskPatrol>(svin_entity).is_some(), "patrol should go on until svin gets another order");
+}
+
+#[test]
+fn follower_catches_up_and_stops_near_target() {
+	let mut test_app = TestApp::new();
+
+	let svins = idle_svins(&mut test_app, 2);
+
+	let (leader_entity, follower_entity) = (svins[0], svins[1]);
+
+	let destination = Vec3::new(15.0, 0.0, -15.0);
+
+	test_app.app.world.entity_mut(leader_entity).insert(NpcTaskRally(RallyPoint { position : destination, target_entity : None }));
+	test_app.app.world.entity_mut(follower_entity).insert(NpcTaskFollow::new(leader_entity));
+
+	let arrived = test_app.step_until(40.0, |world| is_near(world, leader_entity, destination, 0.5));
+	assert!(arrived, "leader didn't reach destination");
+
+	let caught_up = test_app.step_until(20.0, |world| is_near(world, follower_entity, destination, FOLLOW_DISTANCE + 1.0) && world.get::<MovePath>(follower_entity).is_none());
+	assert!(caught_up, "follower didn't stop next to its target");
+
+	assert!(test_app.app.world.get::<NpcTaskFollow>(follower_entity).is_some());
+}
+
+#[test]
+fn follower_goes_up_the_ramp_after_its_target() {
+	let mut test_app = TestApp::with_map("maps/levels.map.ron");
+
+	let svins = idle_svins(&mut test_app, 2);
+
+	let (leader_entity, follower_entity) = (svins[0], svins[1]);
+
+	// middle of the plateau deck, its ramp goes down towards the base
+	let destination = Vec3::new(45.0, 4.0, 0.0);
+
+	test_app.app.world.entity_mut(leader_entity).insert(NpcTaskRally(RallyPoint { position : destination, target_entity : None }));
+	test_app.app.world.entity_mut(follower_entity).insert(NpcTaskFollow::new(leader_entity));
+
+	let arrived = test_app.step_until(60.0, |world| is_near(world, leader_entity, destination, 0.5));
+	assert!(arrived, "leader didn't reach the plateau");
+
+	let caught_up = test_app.step_until(30.0, |world| is_near(world, follower_entity, destination, FOLLOW_DISTANCE + 1.0) && world.get::<MovePath>(follower_entity).is_none());
+	assert!(caught_up, "follower didn't follow its target onto the plateau");
+
+	let Some(follower_transform) = test_app.app.world.get::<Transform>(follower_entity) else { panic!("follower has no Transform!") };
+	assert!(follower_transform.translation.y > destination.y - 0.5, "follower should stand on the deck, not under it");
+}
+
+#[test]
+fn navmesh_tiles_are_stitched_across_borders() {
+	let ground = GroundBounds { min : Vec2::splat(-50.0), max : Vec2::splat(50.0) };
+
+	let mut navmesh_tiles = NavmeshTiles::new(&ground, 25.0);
+
+	// wall straddling the border between two middle tiles, with gaps on both ends to walk around it
+	let obstacle_entity = Entity::from_raw(0);
+
+	navmesh_tiles.set_obstacle(obstacle_entity, vec![
+		Vec2::new(-5.0, -30.0),
+		Vec2::new(-5.0, 30.0),
+		Vec2::new(5.0, 30.0),
+		Vec2::new(5.0, -30.0),
+	]);
+
+	let Some(navmesh) = navmesh_tiles.rebuild_now() else { panic!("stitching navmesh tiles failed!") };
+
+	assert!(navmesh.path(Vec2::new(-40.0, 0.0), Vec2::new(40.0, 0.0)).is_some(), "path should go around the wall through other tiles");
+	assert!(navmesh.path(Vec2::new(-40.0, 0.0), Vec2::new(0.0, 0.0)).is_none(), "middle of the wall shouldn't be walkable");
+
+	// only tiles under the wall are rebuilt once it's gone
+	navmesh_tiles.remove_obstacle(obstacle_entity);
+
+	let Some(navmesh) = navmesh_tiles.rebuild_now() else { panic!("stitching navmesh tiles failed!") };
+
+	assert!(navmesh.path(Vec2::new(-40.0, 0.0), Vec2::new(0.0, 0.0)).is_some(), "removed wall should be walkable again");
+}
+
+#[test]
+fn rotated_collider_footprint_follows_rotation() {
+	let collider = Collider::cuboid(4.0, 1.0, 0.5);
+
+	let transform = GlobalTransform::from(Transform::from_rotation(Quat::from_rotation_y(FRAC_PI_4)));
+
+	let footprint = collider_footprint(&*collider.raw, &transform, 0.0);
+
+	assert_eq!(footprint.len(), 4, "box footprint should stay a quad after simplification");
+
+	let contains = |point: Vec2| (0 .. footprint.len()).all(|i| {
+		let (a, b) = (footprint[i], footprint[(i + 1) % footprint.len()]);
+
+		(b - a).perp_dot(point - a) >= 0.0
+	});
+
+	// long side of the box goes along the diagonal, aabb of the rotated box would cover corners of the square around it
+	let along_diagonal = Vec2::new(1.0, -1.0).normalize() * 3.5;
+
+	assert!(contains(along_diagonal), "footprint should cover the rotated box");
+	assert!(!contains(Vec2::new(2.5, 2.5)), "footprint shouldn't cover corners of the rotated box aabb");
+}
+
+#[test]
+fn paths_between_levels_go_through_ramps() {
+	let ground = GroundBounds { min : Vec2::splat(-50.0), max : Vec2::splat(50.0) };
+
+	let terrain = Terrain::flat(&ground);
+
+	let ramp = |side: LevelSide| MapRamp { side, offset : 0.0, width : 4.0, length : 10.0 };
+
+	// plateau with a ramp from x = 0 up to its deck at x = 10
+	let plateau = MapLevel {
+		min		: Vec2::new(10.0, -10.0),
+		max		: Vec2::new(30.0, 10.0),
+		height	: 4.0,
+		bridge	: false,
+		ramps	: vec![ramp(LevelSide::MinX)],
+	};
+
+	// bridge along X with ramps on both ends, ground under its deck stays walkable
+	let bridge = MapLevel {
+		min		: Vec2::new(-30.0, -30.0),
+		max		: Vec2::new(-10.0, -26.0),
+		height	: 5.0,
+		bridge	: true,
+		ramps	: vec![ramp(LevelSide::MinX), ramp(LevelSide::MaxX)],
+	};
+
+	let levels = [plateau, bridge].iter().map(|map_level| Level::new(map_level, &terrain)).collect();
+
+	let (polyanya, _) = terrain_navmesh(&terrain, levels);
+
+	let on_deck = Vec3::new(25.0, 4.0, 0.0);
+	let under_deck = Vec3::new(25.0, 0.0, 0.0);
+
+	assert_eq!(polyanya.layer_at(on_deck), NavLayer::Level(0));
+	assert_eq!(polyanya.layer_at(under_deck), NavLayer::Ground);
+	assert!(polyanya.point_in_mesh(on_deck), "plateau deck should be walkable");
+	assert!(!polyanya.point_in_mesh(under_deck), "ground under plateau shouldn't be walkable");
+
+	let halfway_up = Vec3::new(5.0, 2.0, 0.0);
+
+	assert_eq!(polyanya.layer_at(halfway_up), NavLayer::Level(0), "ramp belongs to its level");
+	assert!((polyanya.ground_height(halfway_up, &terrain) - 2.0).abs() < 0.001, "ramp should rise evenly from its foot to the deck");
+
+	let Some(up) = polyanya.path(Vec3::new(-5.0, 0.0, 20.0), on_deck) else { panic!("deck should be reachable from the ground!") };
+
+	assert!(up.path.iter().any(|point| point.distance(Vec2::ZERO) < 1.0), "path to the deck should go up the ramp");
+	assert!(up.path.last().is_some_and(|point| point.distance(on_deck.xz()) < 0.001));
+
+	// ground path under the bridge doesn't care about its deck
+	let Some(under) = polyanya.path(Vec3::new(-20.0, 0.0, -40.0), Vec3::new(-20.0, 0.0, -15.0)) else { panic!("ground under bridge should be walkable!") };
+
+	assert!((under.length - 25.0).abs() < 0.01, "path under bridge should be straight");
+
+	// from one level to another through ramps of both
+	let on_bridge = Vec3::new(-20.0, 5.0, -28.0);
+
+	assert_eq!(polyanya.layer_at(on_bridge), NavLayer::Level(1));
+
+	let Some(across) = polyanya.path(on_bridge, on_deck) else { panic!("plateau should be reachable from the bridge!") };
+
+	assert!(across.path.iter().any(|point| point.distance(Vec2::new(0.0, -28.0)) < 1.0), "path should go down the bridge ramp closer to plateau");
+	assert!(across.path.iter().any(|point| point.distance(Vec2::ZERO) < 1.0), "path should go up the plateau ramp");
+}
+
+#[test]
+fn orca_steers_head_on_units_apart() {
+	let neighbour = AvoidanceNeighbour {
+		position		: Vec2::new(4.0, 0.0),
+		velocity		: Vec2::new(-2.0, 0.0),
+		radius			: 0.5,
+		responsibility	: 0.5,
+	};
+
+	let velocity = orca_velocity(Vec2::ZERO, Vec2::new(2.0, 0.0), 0.5, Vec2::new(2.0, 0.0), 2.0, &[neighbour], &[], 0.5, 1.5, 1.0 / 60.0);
+
+	assert!(velocity.length() <= 2.0 + 0.001, "avoidance shouldn't exceed max speed");
+	assert!(velocity.y.abs() > 0.1, "unit should turn aside instead of walking into the one coming towards it");
+	assert!(velocity.x > 0.0, "unit should keep going towards its target");
+
+	// nobody around, preferred velocity is kept as is
+	let velocity = orca_velocity(Vec2::ZERO, Vec2::ZERO, 0.5, Vec2::new(0.0, 1.5), 2.0, &[], &[], 0.5, 1.5, 1.0 / 60.0);
+
+	assert!(velocity.distance(Vec2::new(0.0, 1.5)) < 0.001);
+}
+
+#[test]
+fn orca_units_pass_each_other_next_to_a_wall() {
+	let ground = GroundBounds { min : Vec2::splat(-20.0), max : Vec2::splat(20.0) };
+
+	let mut navmesh_tiles = NavmeshTiles::new(&ground, 10.0);
+
+	// wall face is at y = 1, units walk along it closer than their diameter so one of them has to step away from it
+	navmesh_tiles.set_obstacle(Entity::from_raw(0), vec![
+		Vec2::new(-10.0, 1.0),
+		Vec2::new(10.0, 1.0),
+		Vec2::new(10.0, 3.0),
+		Vec2::new(-10.0, 3.0),
+	]);
+
+	let Some(navmesh) = navmesh_tiles.rebuild_now() else { panic!("stitching navmesh tiles failed!") };
+
+	let boundary = boundary_obstacles(&navmesh);
+
+	let (radius, speed, time_horizon, time_step) = (0.5, 2.0, 1.5, 1.0 / 60.0);
+
+	// slightly off each other, perfectly symmetric units deadlock in ORCA
+	let mut positions = [Vec2::new(-6.0, 0.4), Vec2::new(6.0, 0.35)];
+	let targets = [Vec2::new(6.0, 0.4), Vec2::new(-6.0, 0.4)];
+	let mut velocities = [Vec2::ZERO; 2];
+
+	let mut closest = f32::MAX;
+	let mut closest_to_wall = f32::MAX;
+
+	for _ in 0 .. 20 * 60 {
+		let new_velocities = [0, 1].map(|unit| {
+			let other = 1 - unit;
+			let position = positions[unit];
+
+			let neighbour = AvoidanceNeighbour {
+				position		: positions[other],
+				velocity		: velocities[other],
+				radius,
+				responsibility	: 0.5,
+			};
+
+			let mut obstacles = boundary.iter()
+				.filter(|obstacle| obstacle.distance_to(position) < time_horizon * speed + radius)
+				.copied()
+				.collect::<Vec<AvoidanceObstacle>>();
+
+			obstacles.sort_by(|a, b| a.distance_to(position).total_cmp(&b.distance_to(position)));
+
+			let to_target = targets[unit] - position;
+			let preferred_velocity = to_target.normalize_or_zero() * speed.min(to_target.length() / time_step);
+
+			orca_velocity(position, velocities[unit], radius, preferred_velocity, speed, &[neighbour], &obstacles, radius, time_horizon, time_step)
+		});
+
+		for unit in 0 .. 2 {
+			velocities[unit] = new_velocities[unit];
+			positions[unit] += new_velocities[unit] * time_step;
+
+			closest_to_wall = closest_to_wall.min(1.0 - positions[unit].y);
+		}
+
+		closest = closest.min(positions[0].distance(positions[1]));
+	}
+
+	assert!(closest > radius * 2.0 - 0.05, "units overlapped, closest distance {closest}");
+	assert!(closest_to_wall > radius - 0.01, "unit walked into the wall, closest distance {closest_to_wall}");
+	assert!(positions[0].distance(targets[0]) < 0.1 && positions[1].distance(targets[1]) < 0.1, "units didn't get past each other");
+}
+
+#[test]
+fn svins_walking_towards_each_other_pass_without_overlapping() {
+	let mut test_app = TestApp::new();
+
+	let svins = idle_svins(&mut test_app, 2);
+
+	let (point_a, point_b) = (Vec3::new(-8.0, 0.0, -12.0), Vec3::new(8.0, 0.0, -12.0));
+
+	for (svin_entity, point) in [(svins[0], point_a), (svins[1], point_b)] {
+		test_app.app.world.entity_mut(svin_entity).insert(NpcTaskRally(RallyPoint { position : point, target_entity : None }));
+	}
+
+	let lined_up = test_app.step_until(30.0, |world| is_near(world, svins[0], point_a, 0.5) && is_near(world, svins[1], point_b, 0.5));
+	assert!(lined_up, "svins didn't reach their starting points");
+
+	for (svin_entity, point) in [(svins[0], point_b), (svins[1], point_a)] {
+		test_app.app.world.entity_mut(svin_entity).insert(NpcTaskRally(RallyPoint { position : point, target_entity : None }));
+	}
+
+	let mut closest = f32::MAX;
+
+	let swapped = test_app.step_until(30.0, |world| {
+		let position = |entity: Entity| world.get::<Transform>(entity).map_or(Vec2::ZERO, |transform| transform.translation.xz());
+
+		closest = closest.min(position(svins[0]).distance(position(svins[1])));
+
+		is_near(world, svins[0], point_b, 0.5) && is_near(world, svins[1], point_a, 0.5)
+	});
+
+	assert!(swapped, "svins didn't get past each other");
+	assert!(closest > 0.9, "svins overlapped while passing, {} meters between their centers", closest);
+}
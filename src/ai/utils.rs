@@ -15,22 +15,38 @@ use polyanya :: Mesh as PolyanyaMesh;
 use itertools :: Itertools;
 
 use super :: {
+	PolyanyaResource,
 	Occupied,
 	Locator,
 	MovePath,
+	NpcTaskMove,
+	NpcTaskMoveFinished,
+	NpcTaskRally,
+	NpcTaskResourceCollection,
+	NpcTaskConstruction,
+	NpcTaskReturnCargo,
+	NpcTaskPatrol,
+	NpcTaskFollow,
+	HoldPosition,
 };
 
+use crate :: resource_collection :: { CollectableResource, ResourceDeposit };
+
+use crate :: game :: BaseBuilding;
+
+use crate :: faction :: Faction;
+
 use std :: f32 :: consts :: PI;
 
-// taken from vleue_navigator
-pub fn navmesh_to_wireframe(polyanya_mesh: &PolyanyaMesh) -> Mesh {
+// taken from vleue_navigator, vertices are lifted to the surface of their layer
+pub fn navmesh_to_wireframe(polyanya_mesh: &PolyanyaMesh, height: impl Fn(Vec2) -> f32) -> Mesh {
 	let mut new_mesh = Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::all());
 	new_mesh.insert_attribute(
 		Mesh::ATTRIBUTE_POSITION,
 		polyanya_mesh
 			.vertices
 			.iter()
-			.map(|v| [v.coords.x, 0.0, v.coords.y])
+			.map(|v| [v.coords.x, height(v.coords), v.coords.y])
 			.collect::<Vec<[f32; 3]>>(),
 	);
 	new_mesh.insert_attribute(
@@ -140,7 +156,7 @@ pub fn make_path_to_nearest_locator(
 	movable_transform	: &Transform,
 	q_children			: &Query<&Children>,
 	q_locator			: &Query<(&Locator, &GlobalTransform, Option<&Occupied>)>,
-	navmesh				: &PolyanyaMesh,
+	polyanya			: &PolyanyaResource,
 ) -> Option<MovePath> {
 	let Some((locator_pos, locator_rot, locator_entity)) = pick_nearest_locator(
 		locator,
@@ -152,16 +168,13 @@ pub fn make_path_to_nearest_locator(
 		return None;
 	};
 
-	let npc_pos2	= movable_transform.translation.xz();
-	let locator_pos2= locator_pos.xz();
-
-	if !navmesh.point_in_mesh(locator_pos2) {
-		// println!("Error: can't create path to {:?}! locator is not on navmesh!", locator_pos2);
+	if !polyanya.point_in_mesh(locator_pos) {
+		// println!("Error: can't create path to {:?}! locator is not on navmesh!", locator_pos);
 		return None
 	}
 
-	let Some(path_wlen) = navmesh.path(npc_pos2, locator_pos2) else {
-		// println!("Error: can't create path from {:?} to {:?}! navmesh.path returned None!", npc_pos2, locator_pos2);
+	let Some(path_wlen) = polyanya.path(movable_transform.translation, locator_pos) else {
+		// println!("Error: can't create path from {:?} to {:?}! navmesh.path returned None!", movable_transform.translation, locator_pos);
 		return None
 	};
 
@@ -179,8 +192,91 @@ pub fn make_path_to_nearest_locator(
 	}
 }
 
-use interpolation :: *;
+pub fn make_path_to_point(
+	from		: Vec3,
+	to			: Vec3,
+	polyanya	: &PolyanyaResource,
+) -> Option<MovePath> {
+	if !polyanya.point_in_mesh(to) { return None }
+
+	let path_wlen = polyanya.path(from, to)?;
+
+	let (first, remaining) = path_wlen.path.split_first()?;
+
+	let mut remaining = remaining.iter().map(|v| { Vec3::new(v.x, 0.0, v.y) }).collect::<Vec<Vec3>>();
+	remaining.reverse();
+
+	let current = Vec3::new(first.x, 0.0, first.y);
+
+	Some(MovePath::new(current, remaining, None, None))
+}
+
+pub fn pick_nearest_deposit(
+	resource_type	: CollectableResource,
+	exclude_entity	: Entity,
+	position		: Vec3,
+	q_deposit		: &Query<(Entity, &CollectableResource, &ResourceDeposit, &GlobalTransform)>,
+) -> Option<Entity> {
+	let mut nearest_entity = None;
+	let mut nearest_dist_sq = f32::MAX;
+
+	for (deposit_entity, deposit_type, deposit, deposit_transform) in q_deposit.iter() {
+		if deposit_entity == exclude_entity || *deposit_type != resource_type || deposit.depleted() { continue }
+
+		let dist_sq = deposit_transform.translation().distance_squared(position);
+
+		if dist_sq < nearest_dist_sq {
+			nearest_dist_sq = dist_sq;
+			nearest_entity = Some(deposit_entity);
+		}
+	}
+
+	nearest_entity
+}
+
+/// Finished base of the given faction closest to position, resources can only be delivered there
+pub fn pick_nearest_base(
+	faction			: Faction,
+	position		: Vec3,
+	q_base			: &Query<(Entity, &Transform, &Faction), With<BaseBuilding>>,
+) -> Option<Entity> {
+	let mut nearest_entity = None;
+	let mut nearest_dist_sq = f32::MAX;
+
+	for (base_entity, base_transform, base_faction) in q_base.iter() {
+		if *base_faction != faction { continue }
+
+		let dist_sq = base_transform.translation.distance_squared(position);
 
+		if dist_sq < nearest_dist_sq {
+			nearest_dist_sq = dist_sq;
+			nearest_entity = Some(base_entity);
+		}
+	}
+
+	nearest_entity
+}
+
+/// Drops path and every task unit had, handheld has to be deactivated separately
+pub fn remove_tasks(
+	npc_entity		: Entity,
+	commands		: &mut Commands,
+) {
+	commands.entity(npc_entity)
+		.remove::<MovePath>()
+		.remove::<NpcTaskMove>()
+		.remove::<NpcTaskMoveFinished>()
+		.remove::<NpcTaskRally>()
+		.remove::<NpcTaskResourceCollection>()
+		.remove::<NpcTaskConstruction>()
+		.remove::<NpcTaskReturnCargo>()
+		.remove::<NpcTaskPatrol>()
+		.remove::<NpcTaskFollow>()
+		.remove::<HoldPosition>()
+	;
+}
+
+use interpolation :: *;
 pub fn draw_floor_circle(
 	circle_radius	: f32,
 	vert_move_coef	: f32,
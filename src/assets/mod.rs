@@ -1,11 +1,26 @@
-use bevy :: prelude :: *;
+use bevy :: {
+	prelude :: *,
+	utils :: HashMap,
+};
 
 use bevy_hanabi :: prelude :: *;
 
 use super :: game :: GameState;
 
+use super :: map :: Map;
+
+use super :: units :: {
+	UnitDefinitions,
+	UnitType,
+};
+
+use super :: buildings :: BuildingDefinitions;
+
 mod systems;
 
+mod ron_loader;
+pub use ron_loader :: RonAssetLoader;
+
 pub struct AssetsPlugin;
 
 impl Plugin for AssetsPlugin {
@@ -15,6 +30,8 @@ impl Plugin for AssetsPlugin {
 				systems::start_assets_loading,
 			))
 			.add_systems(Update, (
+				systems::check_unit_definitions_loading,
+				systems::check_building_definitions_loading,
 				systems::check_assets_loading,
 				systems::check_cubemap_loading
 			).run_if(in_state(GameState::Loading)))
@@ -38,7 +55,14 @@ pub struct GameAssets {
 	pub purplite_shard	: Handle<Scene>,
 	pub base_building	: Handle<Scene>,
 	pub drill_miller_falls: Handle<Scene>,
-	pub svin			: Handle<Scene>,
+	/// filled after unit definitions are loaded
+	pub unit_scenes		: HashMap<UnitType, Handle<Scene>>,
+
+	pub map				: Handle<Map>,
+	/// starts loading once map is loaded and turns out to have a heightmap
+	pub heightmap		: Option<Handle<Image>>,
+	pub unit_definitions: Handle<UnitDefinitions>,
+	pub building_definitions: Handle<BuildingDefinitions>,
 
 	pub resource_drilling_effect: Handle<EffectAsset>,
 	pub default_drilling_effect	: Handle<EffectAsset>,
@@ -53,10 +77,17 @@ impl GameAssets {
 			self.purplite_shard.clone_weak(),
 			self.base_building.clone_weak(),
 			self.drill_miller_falls.clone_weak(),
-			self.svin.clone_weak(),
 		];
 
-		handles.to_vec()
+		handles.into_iter()
+			.chain(self.unit_scenes.values().map(|handle| handle.clone_weak()))
+			.collect()
+	}
+
+	pub fn unit_scene(&self, unit_type: UnitType) -> Handle<Scene> {
+		let Some(scene) = self.unit_scenes.get(&unit_type) else { panic!("scene of unit type {:?} is not loaded!", unit_type) };
+
+		scene.clone_weak()
 	}
 }
 
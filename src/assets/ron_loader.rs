@@ -0,0 +1,56 @@
+use bevy :: {
+	prelude :: *,
+	asset :: {
+		io :: Reader,
+		AssetLoader,
+		AsyncReadExt,
+		LoadContext,
+	},
+	scene :: ron,
+	utils :: BoxedFuture,
+};
+
+use serde :: de :: DeserializeOwned;
+
+use std :: marker :: PhantomData;
+
+/// Loads any deserializable asset from a ron file, extension is what tells which asset it is, e.g. `default.map.ron`
+pub struct RonAssetLoader<A> {
+	extensions	: &'static [&'static str],
+	marker		: PhantomData<fn() -> A>,
+}
+
+impl<A> RonAssetLoader<A> {
+	pub fn new(extensions: &'static [&'static str]) -> Self {
+		Self {
+			extensions,
+			marker : PhantomData,
+		}
+	}
+}
+
+impl<A: Asset + DeserializeOwned> AssetLoader for RonAssetLoader<A> {
+	type Asset = A;
+	type Settings = ();
+	type Error = Box<dyn std::error::Error + Send + Sync>;
+
+	fn load<'a>(
+		&'a self,
+		reader		: &'a mut Reader,
+		_settings	: &'a (),
+		_load_context: &'a mut LoadContext,
+	) -> BoxedFuture<'a, Result<A, Self::Error>> {
+		Box::pin(async move {
+			let mut bytes = Vec::new();
+			reader.read_to_end(&mut bytes).await?;
+
+			let asset = ron::de::from_bytes::<A>(&bytes)?;
+
+			Ok(asset)
+		})
+	}
+
+	fn extensions(&self) -> &[&str] {
+		self.extensions
+	}
+}
@@ -6,7 +6,13 @@ use bevy :: {
 
 use super :: *;
 
-pub fn start_assets_loading(mut commands: Commands, asset_server: Res<AssetServer>) {
+use crate :: options :: LaunchOptions;
+
+use crate :: units :: UNIT_DEFINITIONS_PATH;
+
+use crate :: buildings :: BUILDING_DEFINITIONS_PATH;
+
+pub fn start_assets_loading(mut commands: Commands, asset_server: Res<AssetServer>, launch_options: Res<LaunchOptions>) {
 	let skybox_texture	= asset_server.load("environment/rosendal/rosendal_park_sunset_1k.png");
 	let ibl_diffuse		= asset_server.load("environment/rosendal/diffuse_rgb9e5_zstd.ktx2");
 	let ibl_specular	= asset_server.load("environment/rosendal/specular_rgb9e5_zstd.ktx2");
@@ -24,7 +30,9 @@ pub fn start_assets_loading(mut commands: Commands, asset_server: Res<AssetServe
 	let purplite_shard		= asset_server.load("models/purplite_shard.glb#Scene0");
 	let base_building		= asset_server.load("models/base_building.glb#Scene0");
 	let drill_miller_falls	= asset_server.load("models/miller_falls_drill.glb#Scene0");
-	let svin				= asset_server.load("models/svin.glb#Scene0");
+	let map					= asset_server.load(launch_options.map.clone());
+	let unit_definitions	= asset_server.load(UNIT_DEFINITIONS_PATH);
+	let building_definitions = asset_server.load(BUILDING_DEFINITIONS_PATH);
 
 	commands.insert_resource(GameAssets {
 		tealite,
@@ -33,7 +41,10 @@ pub fn start_assets_loading(mut commands: Commands, asset_server: Res<AssetServe
 		purplite_shard,
 		base_building,
 		drill_miller_falls,
-		svin,
+
+		map,
+		unit_definitions,
+		building_definitions,
 
 		..default()
 	});
@@ -43,13 +54,71 @@ pub fn start_assets_loading(mut commands: Commands, asset_server: Res<AssetServe
 	]));
 }
 
+/// Unit scenes are listed in unit definitions, so they start loading only after definitions are loaded
+pub fn check_unit_definitions_loading(
+		unit_definitions_assets	: Res<Assets<UnitDefinitions>>,
+		unit_definitions		: Option<Res<UnitDefinitions>>,
+		asset_server			: Res<AssetServer>,
+	mut game_assets				: ResMut<GameAssets>,
+	mut commands				: Commands,
+) {
+	if unit_definitions.is_some() { return }
+
+	if asset_server.load_state(&game_assets.unit_definitions) == LoadState::Failed {
+		panic!("failed to load unit definitions {:?}!", UNIT_DEFINITIONS_PATH);
+	}
+
+	let Some(definitions) = unit_definitions_assets.get(&game_assets.unit_definitions) else { return };
+
+	let missing = definitions.missing();
+	if !missing.is_empty() {
+		panic!("{:?} has no definitions for {:?}!", UNIT_DEFINITIONS_PATH, missing);
+	}
+
+	for (unit_type, definition) in definitions.units.iter() {
+		game_assets.unit_scenes.insert(*unit_type, asset_server.load(definition.scene.clone()));
+	}
+
+	commands.insert_resource(definitions.clone());
+}
+
+/// Building definitions are copied into a resource the same way as unit definitions, they have no scenes to load though
+pub fn check_building_definitions_loading(
+		building_definitions_assets	: Res<Assets<BuildingDefinitions>>,
+		building_definitions		: Option<Res<BuildingDefinitions>>,
+		asset_server				: Res<AssetServer>,
+		game_assets					: Res<GameAssets>,
+	mut commands					: Commands,
+) {
+	if building_definitions.is_some() { return }
+
+	if asset_server.load_state(&game_assets.building_definitions) == LoadState::Failed {
+		panic!("failed to load building definitions {:?}!", BUILDING_DEFINITIONS_PATH);
+	}
+
+	let Some(definitions) = building_definitions_assets.get(&game_assets.building_definitions) else { return };
+
+	let missing = definitions.missing();
+	if !missing.is_empty() {
+		panic!("{:?} has no definitions for {:?}!", BUILDING_DEFINITIONS_PATH, missing);
+	}
+
+	commands.insert_resource(definitions.clone());
+}
+
 pub fn check_assets_loading(
 		cubemap			: Res<Cubemap>,
+		unit_definitions: Option<Res<UnitDefinitions>>,
+		building_definitions: Option<Res<BuildingDefinitions>>,
+		maps			: Res<Assets<Map>>,
 	mut game_state		: ResMut<NextState<GameState>>,
-		game_assets		: Res<GameAssets>,
+	mut game_assets		: ResMut<GameAssets>,
 		animations		: Res<Animations>,
 		asset_server	: Res<AssetServer>,
 ) {
+	// unit scenes start loading only after unit definitions are loaded, see check_unit_definitions_loading
+	if unit_definitions.is_none() || building_definitions.is_none() { return }
+
 	let scene_handles = game_assets.all_scene_handhles();
 	for handle in scene_handles.iter() {
 		if asset_server.load_state(handle) != LoadState::Loaded { return }
@@ -60,6 +129,25 @@ pub fn check_assets_loading(
 
 	if !cubemap.is_loaded { return }
 
+	match asset_server.load_state(&game_assets.map) {
+		LoadState::Loaded => (),
+		LoadState::Failed => panic!("failed to load map {:?}!", asset_server.get_path(&game_assets.map)),
+		_ => return,
+	}
+
+	// heightmap path is only known once map is loaded
+	let Some(map) = maps.get(&game_assets.map) else { return };
+
+	if let Some(heightmap_path) = map.terrain.as_ref().and_then(|terrain| terrain.heightmap.clone()) {
+		let heightmap = game_assets.heightmap.get_or_insert_with(|| asset_server.load(heightmap_path.clone())).clone_weak();
+
+		match asset_server.load_state(&heightmap) {
+			LoadState::Loaded => (),
+			LoadState::Failed => panic!("failed to load heightmap {:?}!", heightmap_path),
+			_ => return,
+		}
+	}
+
 	game_state.set(GameState::Loaded);
 }
 
@@ -0,0 +1,142 @@
+use bevy :: {
+	prelude :: *,
+	utils :: HashMap,
+};
+
+use serde :: Deserialize;
+
+use super :: game :: {
+	GameState,
+	InputContext,
+	MainEntities,
+	BatchOfResources,
+};
+
+use super :: assets :: RonAssetLoader;
+
+pub mod spawn;
+pub mod systems;
+
+#[cfg(test)]
+mod tests;
+
+/// Construction mode: building picked in build menu is shown as a ghost following player's raypick until it's placed or cancelled.
+/// Placed building is only a construction site until svins (or player) drill it for `build_time` seconds
+pub struct BuildingsPlugin;
+
+impl Plugin for BuildingsPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.register_type::<BuildingType>()
+			.register_type::<UnderConstruction>()
+
+			.init_asset::<BuildingDefinitions>()
+			.register_asset_loader(RonAssetLoader::<BuildingDefinitions>::new(&["buildings.ron"]))
+
+			.init_resource::<Placement>()
+
+			.add_systems(Update, (
+				systems::construction_progress,
+			).run_if(in_state(GameState::Main)))
+
+			.add_systems(Update, (
+				systems::construction_progress_draw,
+			).run_if(in_state(GameState::Main)).run_if(resource_exists::<MainEntities>))
+
+			.add_systems(Update, (
+				systems::placement_ghost_spawn,
+				systems::placement_ghost_update,
+				systems::placement_control.run_if(in_state(InputContext::Placement)),
+			).chain().run_if(in_state(GameState::Main)).run_if(resource_exists::<MainEntities>))
+		;
+	}
+}
+
+pub const BUILDING_DEFINITIONS_PATH : &str = "buildings/default.buildings.ron";
+
+/// Building stands on the lowest ground under its footprint, ground under the rest of it can't be higher than this
+pub const PLACEMENT_MAX_HEIGHT_DIFFERENCE : f32 = 1.0;
+
+pub const GHOST_VALID_COLOR : Color = Color::rgba(0.2, 1.0, 0.3, 0.35);
+pub const GHOST_INVALID_COLOR : Color = Color::rgba(1.0, 0.2, 0.2, 0.35);
+
+#[derive(Component, Reflect, Deserialize, Default, PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub enum BuildingType {
+	#[default]
+	Base,
+	Wall,
+}
+
+impl BuildingType {
+	/// also the order of buildings in build menu
+	pub const ALL : [BuildingType; 2] = [BuildingType::Base, BuildingType::Wall];
+}
+
+/// Building names, costs and sizes from `assets/buildings/default.buildings.ron`, copied into a resource once loaded
+#[derive(Asset, Resource, TypePath, Deserialize, Clone, Debug)]
+pub struct BuildingDefinitions {
+	pub buildings		: HashMap<BuildingType, BuildingDefinition>,
+}
+
+impl BuildingDefinitions {
+	pub fn get(&self, building_type: BuildingType) -> &BuildingDefinition {
+		let Some(definition) = self.buildings.get(&building_type) else { panic!("there is no definition for building type {:?}!", building_type) };
+
+		definition
+	}
+
+	/// Every building type has to be defined, otherwise definitions are rejected
+	pub fn missing(&self) -> Vec<BuildingType> {
+		BuildingType::ALL.into_iter()
+			.filter(|building_type| !self.buildings.contains_key(building_type))
+			.collect()
+	}
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct BuildingDefinition {
+	pub name			: String,
+	pub cost			: BatchOfResources,
+	/// footprint and height in meters, placement ghost is a box of this size. Walls are exactly this box
+	pub size			: Vec3,
+	/// seconds of drilling by one worker, several workers build proportionally faster
+	pub build_time		: f32,
+}
+
+/// Building stays a construction site without its usual behavior (e.g. base can't spawn npcs) until progress reaches build_time
+#[derive(Component, Reflect, Default, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct UnderConstruction {
+	pub progress		: f32,
+	pub build_time		: f32,
+}
+
+impl UnderConstruction {
+	pub fn new(build_time: f32) -> Self {
+		Self { progress : 0.0, build_time }
+	}
+
+	pub fn fraction(&self) -> f32 {
+		(self.progress / self.build_time).clamp(0.0, 1.0)
+	}
+
+	pub fn finished(&self) -> bool {
+		self.progress >= self.build_time
+	}
+}
+
+/// Building player is placing at the moment, set from build menu and reset once building is placed or cancelled
+#[derive(Resource, Default)]
+pub struct Placement {
+	pub building		: Option<BuildingType>,
+	/// rotation around Y axis in radians, kept between placements
+	pub rotation		: f32,
+}
+
+#[derive(Component)]
+pub struct PlacementGhost {
+	pub building_type	: BuildingType,
+	pub valid			: bool,
+	pub material		: Handle<StandardMaterial>,
+}
@@ -0,0 +1,168 @@
+use bevy :: {
+	prelude :: *,
+	pbr :: NotShadowCaster,
+};
+
+use super :: *;
+
+use crate :: assets :: GameAssets;
+
+use crate :: ai :: { LocatorsContainer, NpcInteractable, NpcSpawner };
+
+use crate :: faction :: Faction;
+
+use crate :: game :: {
+	BaseBuilding,
+	ProductionQueue,
+	spawn as game_spawn,
+};
+
+use std :: f32 :: consts :: FRAC_PI_2;
+
+/// Finished building, used when building is restored from a save
+pub fn building(
+	building_type	: BuildingType,
+	transform		: Transform,
+	definition		: &BuildingDefinition,
+	faction			: Faction,
+	game_assets		: &GameAssets,
+	meshes			: &mut Assets<Mesh>,
+	materials		: &mut Assets<StandardMaterial>,
+	commands		: &mut Commands,
+) -> Entity {
+	let building_entity = site(building_type, transform, definition, faction, game_assets, meshes, materials, commands);
+
+	complete(building_type, building_entity, commands);
+
+	building_entity
+}
+
+/// Placed buildings are navmesh obstacle containers from the start like the rest of static entities, so navmesh
+/// is rebuilt around them right away and workers can walk up to their interact locators
+pub fn construction_site(
+	building_type	: BuildingType,
+	transform		: Transform,
+	definition		: &BuildingDefinition,
+	faction			: Faction,
+	game_assets		: &GameAssets,
+	meshes			: &mut Assets<Mesh>,
+	materials		: &mut Assets<StandardMaterial>,
+	commands		: &mut Commands,
+) -> Entity {
+	let site_entity = site(building_type, transform, definition, faction, game_assets, meshes, materials, commands);
+
+	commands.entity(site_entity).insert(UnderConstruction::new(definition.build_time));
+
+	site_entity
+}
+
+/// Turns on behavior that construction site doesn't have
+pub fn complete(
+	building_type	: BuildingType,
+	building_entity	: Entity,
+	commands		: &mut Commands,
+) {
+	match building_type {
+		BuildingType::Base => {
+			commands.entity(building_entity).insert((
+				BaseBuilding,
+				NpcSpawner,
+				ProductionQueue::default(),
+			));
+		},
+		// nothing to do at a finished wall so npcs can't be sent to it anymore
+		BuildingType::Wall => {
+			commands.entity(building_entity).remove::<NpcInteractable>();
+		},
+	}
+}
+
+fn site(
+	building_type	: BuildingType,
+	transform		: Transform,
+	definition		: &BuildingDefinition,
+	faction			: Faction,
+	game_assets		: &GameAssets,
+	meshes			: &mut Assets<Mesh>,
+	materials		: &mut Assets<StandardMaterial>,
+	commands		: &mut Commands,
+) -> Entity {
+	let site_entity = match building_type {
+		BuildingType::Base => game_spawn::base_building_site(transform, faction, game_assets, commands),
+		BuildingType::Wall => {
+			let wall_entity = game_spawn::obstacle(transform, definition.size, meshes, materials, commands);
+
+			// wall is a plain box without locators in it, workers stand at its ends facing it
+			let end_offset = definition.size.x / 2.0 + 1.0;
+
+			for (side, rotation) in [(1.0, -FRAC_PI_2), (-1.0, FRAC_PI_2)] {
+				let locator_entity = commands.spawn((
+					Name::new("interact_locator"),
+					SpatialBundle::from_transform(Transform {
+						translation : Vec3::X * end_offset * side,
+						rotation : Quat::from_rotation_y(rotation),
+						..default()
+					}),
+				)).id();
+
+				commands.entity(wall_entity).add_child(locator_entity);
+			}
+
+			commands.entity(wall_entity).insert((
+				NpcInteractable,
+				LocatorsContainer,
+			));
+
+			wall_entity
+		},
+	};
+
+	commands.entity(site_entity).insert((
+		Name::new(definition.name.clone()),
+		building_type,
+		faction,
+	));
+
+	site_entity
+}
+
+/// Translucent box of building size, it has no collider so raypick goes through it
+pub fn ghost(
+	building_type	: BuildingType,
+	definition		: &BuildingDefinition,
+	meshes			: &mut Assets<Mesh>,
+	materials		: &mut Assets<StandardMaterial>,
+	commands		: &mut Commands,
+) -> Entity {
+	let material = materials.add(StandardMaterial {
+		base_color : GHOST_INVALID_COLOR,
+		alpha_mode : AlphaMode::Blend,
+		unlit : true,
+		..default()
+	});
+
+	let mesh_entity = commands.spawn((
+		PbrBundle {
+			mesh : meshes.add(Cuboid::from_size(definition.size)),
+			material : material.clone(),
+			transform : Transform::from_translation(Vec3::Y * definition.size.y / 2.0),
+			..default()
+		},
+		NotShadowCaster,
+	)).id();
+
+	commands.spawn((
+		Name::new("Placement Ghost"),
+		SpatialBundle {
+			visibility : Visibility::Hidden,
+			..default()
+		},
+		PlacementGhost {
+			building_type,
+			valid : false,
+			material,
+		},
+	))
+	.add_child(mesh_entity)
+	.id()
+}
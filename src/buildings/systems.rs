@@ -0,0 +1,289 @@
+use bevy :: prelude :: *;
+
+use bevy_rapier3d :: prelude :: *;
+use bevy_vector_shapes :: prelude :: *;
+
+use super :: *;
+
+use crate :: assets :: GameAssets;
+
+use crate :: ai :: PolyanyaResource;
+
+use crate :: game :: { Raypick, InputContext, CameraMode };
+
+use crate :: handheld :: { Handheld, CurrentHandheld };
+
+use crate :: utils :: calc_rotation_facing_camera;
+
+use crate :: resource_collection :: FactionResources;
+
+use crate :: faction :: Faction;
+
+use crate :: map :: { Terrain, GROUND_GROUP };
+
+use std :: f32 :: consts :: { FRAC_PI_4, TAU };
+
+/// Any activated handheld aimed at a construction site builds it, so it works the same for svins and player
+pub fn construction_progress(
+		time			: Res<Time>,
+		q_handheld		: Query<(&Handheld, &Raypick), With<CurrentHandheld>>,
+	mut q_site			: Query<(Entity, &BuildingType, &mut UnderConstruction)>,
+	mut commands		: Commands,
+) {
+	for (handheld, raypick) in q_handheld.iter() {
+		if !handheld.activated() { continue }
+
+		let Some(raypicked_entity) = raypick.entity else { continue };
+
+		let Ok((_, _, mut construction)) = q_site.get_mut(raypicked_entity) else { continue };
+
+		construction.progress += time.delta_seconds();
+	}
+
+	for (site_entity, building_type, construction) in q_site.iter() {
+		if !construction.finished() { continue }
+
+		commands.entity(site_entity).remove::<UnderConstruction>();
+
+		spawn::complete(*building_type, site_entity, &mut commands);
+	}
+}
+
+pub fn construction_progress_draw(
+		main_entities			: Res<MainEntities>,
+		building_definitions	: Res<BuildingDefinitions>,
+		q_camera				: Query<(&GlobalTransform, &Camera)>,
+		q_site					: Query<(&GlobalTransform, &BuildingType, &UnderConstruction)>,
+	mut painter					: ShapePainter,
+) {
+	let camera_transform = {
+		let Ok((camera_player_transform, camera_player))	= q_camera.get(main_entities.player_camera) else { panic!("player camera entity has not Camera or Transform component!") };
+		let Ok((camera_orbit_transform, _camera_orbit))		= q_camera.get(main_entities.orbit_camera) else { panic!("orbit camera entity has not Camera or Transform component!") };
+
+		if camera_player.is_active {
+			camera_player_transform
+		} else {
+			camera_orbit_transform
+		}
+	};
+
+	for (site_transform, building_type, construction) in q_site.iter() {
+		let height = building_definitions.get(*building_type).size.y;
+
+		painter.set_translation(site_transform.translation() + Vec3::Y * (height + 1.0));
+		painter.set_rotation(calc_rotation_facing_camera(site_transform, camera_transform));
+		painter.set_scale(Vec3::ONE);
+
+		painter.hollow = true;
+		painter.thickness = 0.15;
+
+		painter.color = Color::DARK_GRAY.with_a(0.7);
+		painter.circle(0.8);
+
+		painter.color = Color::YELLOW;
+		painter.arc(0.8, 0.0, TAU * construction.fraction());
+	}
+}
+
+/// Keeps ghost in sync with `Placement`: spawns it when building is picked, replaces it when another one is picked and removes it when placement is over
+pub fn placement_ghost_spawn(
+		placement				: Res<Placement>,
+		building_definitions	: Res<BuildingDefinitions>,
+		q_ghost					: Query<(Entity, &PlacementGhost)>,
+	mut meshes					: ResMut<Assets<Mesh>>,
+	mut materials				: ResMut<Assets<StandardMaterial>>,
+	mut commands				: Commands,
+) {
+	if !placement.is_changed() { return }
+
+	let current_ghost = q_ghost.get_single().ok();
+
+	if current_ghost.map(|(_, ghost)| ghost.building_type) == placement.building { return }
+
+	if let Some((ghost_entity, _)) = current_ghost {
+		commands.entity(ghost_entity).despawn_recursive();
+	}
+
+	let Some(building_type) = placement.building else { return };
+
+	spawn::ghost(
+		building_type,
+		building_definitions.get(building_type),
+		&mut meshes,
+		&mut materials,
+		&mut commands
+	);
+}
+
+pub fn placement_ghost_update(
+		key						: Res<ButtonInput<KeyCode>>,
+		main_entities			: Res<MainEntities>,
+		camera_mode				: Res<State<CameraMode>>,
+		rapier_context			: Res<RapierContext>,
+		polyanya				: Res<PolyanyaResource>,
+		terrain					: Res<Terrain>,
+		building_definitions	: Res<BuildingDefinitions>,
+		q_raypick				: Query<&Raypick>,
+	mut placement				: ResMut<Placement>,
+	mut q_ghost					: Query<(&mut PlacementGhost, &mut Transform, &mut Visibility)>,
+	mut materials				: ResMut<Assets<StandardMaterial>>,
+) {
+	let Ok((mut ghost, mut ghost_transform, mut ghost_visibility)) = q_ghost.get_single_mut() else { return };
+
+	let Ok(raypick) = q_raypick.get(main_entities.raypick_camera(*camera_mode.get())) else { panic!("camera has no raypick!") };
+
+	if key.just_pressed(KeyCode::KeyR) {
+		placement.rotation += FRAC_PI_4;
+	}
+
+	let valid = if raypick.entity.is_some() {
+		*ghost_visibility = Visibility::Visible;
+
+		let size = building_definitions.get(ghost.building_type).size;
+
+		// raypick may hit something standing on the ground, ghost is always put on the ground itself
+		*ghost_transform = on_terrain(
+			&Transform::from_translation(raypick.pos).with_rotation(Quat::from_rotation_y(placement.rotation)),
+			size,
+			&terrain,
+		);
+
+		placement_allowed(&ghost_transform, size, &rapier_context, &polyanya, &terrain)
+	} else {
+		*ghost_visibility = Visibility::Hidden;
+
+		false
+	};
+
+	if ghost.valid == valid { return }
+
+	ghost.valid = valid;
+
+	if let Some(material) = materials.get_mut(&ghost.material) {
+		material.base_color = if valid { GHOST_VALID_COLOR } else { GHOST_INVALID_COLOR };
+	}
+}
+
+pub fn placement_control(
+		mouse_button			: Res<ButtonInput<MouseButton>>,
+		key						: Res<ButtonInput<KeyCode>>,
+		main_entities			: Res<MainEntities>,
+		game_assets				: Res<GameAssets>,
+		building_definitions	: Res<BuildingDefinitions>,
+		q_ghost					: Query<(&PlacementGhost, &Transform)>,
+		q_faction				: Query<&Faction>,
+	mut placement				: ResMut<Placement>,
+	mut next_context			: ResMut<NextState<InputContext>>,
+	mut faction_resources		: ResMut<FactionResources>,
+	mut meshes					: ResMut<Assets<Mesh>>,
+	mut materials				: ResMut<Assets<StandardMaterial>>,
+	mut commands				: Commands,
+) {
+	let Some(building_type) = placement.building else { return };
+
+	if mouse_button.just_pressed(MouseButton::Right) {
+		placement.building = None;
+		next_context.set(InputContext::Gameplay);
+		return
+	}
+
+	if !mouse_button.just_pressed(MouseButton::Left) { return }
+
+	let Ok((ghost, ghost_transform)) = q_ghost.get_single() else { return };
+
+	let definition = building_definitions.get(building_type);
+
+	if !ghost.valid {
+		println!("CAN'T PLACE {} HERE!", definition.name.to_uppercase());
+		return
+	}
+
+	// buildings placed by player belong to player's faction and are paid from its stockpile
+	let Ok(faction) = q_faction.get(main_entities.player) else { panic!("player entity has no Faction component!") };
+
+	let collected_resources = faction_resources.get_mut(*faction);
+
+	if !collected_resources.is_enough(&definition.cost) {
+		println!("NOT ENOUGH RESOURCES COLLECTED TO BUILD {}!", definition.name.to_uppercase());
+		placement.building = None;
+		next_context.set(InputContext::Gameplay);
+		return
+	}
+
+	collected_resources.deduct(&definition.cost);
+
+	spawn::construction_site(
+		building_type,
+		*ghost_transform,
+		definition,
+		*faction,
+		&game_assets,
+		&mut meshes,
+		&mut materials,
+		&mut commands
+	);
+
+	// holding shift allows placing several buildings of the same type in a row, e.g. a line of walls
+	if !key.pressed(KeyCode::ShiftLeft) {
+		placement.building = None;
+		next_context.set(InputContext::Gameplay);
+	}
+}
+
+/// Center and corners of building's footprint on XZ plane
+fn footprint(transform: &Transform, size: Vec3) -> [Vec2; 5] {
+	let half_size = size / 2.0;
+
+	[
+		Vec3::ZERO,
+		Vec3::new(-half_size.x, 0.0, -half_size.z),
+		Vec3::new(-half_size.x, 0.0,  half_size.z),
+		Vec3::new( half_size.x, 0.0,  half_size.z),
+		Vec3::new( half_size.x, 0.0, -half_size.z),
+	]
+	.map(|offset| (transform.translation + transform.rotation * offset).xz())
+}
+
+/// Same transform moved onto the lowest ground under building's footprint, the rest of it sinks into the slope
+pub fn on_terrain(transform: &Transform, size: Vec3, terrain: &Terrain) -> Transform {
+	let height = footprint(transform, size).iter()
+		.map(|point| terrain.height_at(*point))
+		.fold(f32::INFINITY, f32::min);
+
+	Transform {
+		translation : Vec3::new(transform.translation.x, height, transform.translation.z),
+		..*transform
+	}
+}
+
+/// Ground under footprint of building put `on_terrain` is even enough to build on
+pub fn level_enough(transform: &Transform, size: Vec3, terrain: &Terrain) -> bool {
+	footprint(transform, size).iter()
+		.all(|point| terrain.height_at(*point) - transform.translation.y <= PLACEMENT_MAX_HEIGHT_DIFFERENCE)
+}
+
+/// Building put `on_terrain` can be placed if ground under it is level enough, its box doesn't touch any collider other than the ground
+/// and its whole footprint is on navmesh. Navmesh already has holes around other buildings, obstacles and steep slopes so they can't overlap either
+pub fn placement_allowed(
+	transform		: &Transform,
+	size			: Vec3,
+	rapier_context	: &RapierContext,
+	polyanya		: &PolyanyaResource,
+	terrain			: &Terrain,
+) -> bool {
+	if !level_enough(transform, size, terrain) { return false }
+
+	let half_size = size / 2.0;
+
+	// part of the box is below ground on slopes, so the ground itself is left out
+	if rapier_context.intersection_with_shape(
+		transform.translation + Vec3::Y * half_size.y,
+		transform.rotation,
+		&Collider::cuboid(half_size.x, half_size.y, half_size.z),
+		QueryFilter::new().groups(CollisionGroups::new(Group::ALL, Group::ALL ^ GROUND_GROUP)),
+	).is_some() {
+		return false;
+	}
+
+	footprint(transform, size).iter().all(|point| polyanya.mesh.point_in_mesh(*point))
+}
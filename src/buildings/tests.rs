@@ -0,0 +1,87 @@
+use bevy :: {
+	prelude :: *,
+	ecs :: system :: RunSystemOnce,
+	scene :: ron,
+};
+
+use super :: *;
+
+use crate :: ai :: NpcSpawner;
+
+use crate :: game :: BaseBuilding;
+
+use crate :: map :: { GroundBounds, MapHill, MapTerrain, Terrain };
+
+#[test]
+fn default_building_definitions_are_valid() {
+	let definitions = match ron::de::from_str::<BuildingDefinitions>(include_str!("../../assets/buildings/default.buildings.ron")) {
+		Ok(definitions) => definitions,
+		Err(error) => panic!("failed to parse building definitions: {}", error),
+	};
+
+	assert!(definitions.missing().is_empty(), "building types without definition: {:?}", definitions.missing());
+
+	for (building_type, definition) in definitions.buildings.iter() {
+		assert!(definition.size.min_element() > 0.0, "{:?}", building_type);
+		assert!(definition.build_time > 0.0, "{:?}", building_type);
+		assert!(definition.cost.purplite + definition.cost.tealite > 0, "{:?} is free", building_type);
+	}
+}
+
+#[test]
+fn base_can_spawn_npcs_only_after_construction_is_finished() {
+	let mut world = World::new();
+
+	world.insert_resource(Time::<()>::default());
+
+	let finished_entity = world.spawn((BuildingType::Base, UnderConstruction { progress: 30.0, build_time: 30.0 })).id();
+	let unfinished_entity = world.spawn((BuildingType::Base, UnderConstruction { progress: 10.0, build_time: 30.0 })).id();
+
+	world.run_system_once(systems::construction_progress);
+
+	assert!(world.get::<UnderConstruction>(finished_entity).is_none());
+	assert!(world.get::<BaseBuilding>(finished_entity).is_some());
+	assert!(world.get::<NpcSpawner>(finished_entity).is_some());
+
+	assert!(world.get::<UnderConstruction>(unfinished_entity).is_some());
+	assert!(world.get::<BaseBuilding>(unfinished_entity).is_none());
+	assert!(world.get::<NpcSpawner>(unfinished_entity).is_none());
+}
+
+#[test]
+fn buildings_are_put_on_terrain_and_allowed_only_on_level_ground() {
+	let ground = GroundBounds { min : Vec2::splat(-50.0), max : Vec2::splat(50.0) };
+
+	let hill = MapHill { position : Vec2::new(20.0, 0.0), radius : 20.0, height : 8.0 };
+
+	let terrain = Terrain::generate(&ground, &MapTerrain {
+		heightmap	: None,
+		max_height	: 0.0,
+		resolution	: 129,
+		hills		: vec![hill.clone()],
+		max_slope	: 30.0,
+	});
+
+	let Ok(definitions) = ron::de::from_str::<BuildingDefinitions>(include_str!("../../assets/buildings/default.buildings.ron")) else { panic!("failed to parse building definitions") };
+
+	let base_size = definitions.get(BuildingType::Base).size;
+	let wall_size = definitions.get(BuildingType::Wall).size;
+
+	// raypick may be above the ground, e.g. on top of something
+	let on_flat = systems::on_terrain(&Transform::from_xyz(-30.0, 3.0, 0.0), base_size, &terrain);
+
+	assert_eq!(on_flat.translation, Vec3::new(-30.0, 0.0, 0.0));
+	assert!(systems::level_enough(&on_flat, base_size, &terrain));
+
+	let on_slope = systems::on_terrain(&Transform::from_xyz(hill.position.x - hill.radius / 2.0, 0.0, 0.0), base_size, &terrain);
+
+	assert!(on_slope.translation.y < terrain.height_at(on_slope.translation.xz()), "building on slope should stand on its lowest corner");
+	assert!(!systems::level_enough(&on_slope, base_size, &terrain), "base shouldn't be allowed on hillside");
+
+	// small building fits on the flat top of the hill
+	let on_top = systems::on_terrain(&Transform::from_xyz(hill.position.x, 0.0, hill.position.y).with_rotation(Quat::from_rotation_y(0.3)), wall_size, &terrain);
+
+	assert!(on_top.translation.y > hill.height - PLACEMENT_MAX_HEIGHT_DIFFERENCE);
+	assert!(systems::level_enough(&on_top, wall_size, &terrain));
+	assert_eq!(on_top.rotation, Quat::from_rotation_y(0.3));
+}
@@ -0,0 +1,168 @@
+use bevy :: prelude :: *;
+
+use serde :: Deserialize;
+
+use super :: game :: GameState;
+
+use super :: faction :: Faction;
+
+use super :: units :: { UnitDefinitions, UnitType };
+
+use super :: buildings :: { BuildingDefinitions, BuildingType };
+
+use super :: resource_collection :: { CollectableResource, CollectedResources };
+
+use super :: ai;
+
+pub mod strategies;
+	mod systems;
+
+#[cfg(test)]
+mod tests;
+
+/// Computer opponents. Every commander owns one faction and once in `THINK_INTERVAL` seconds asks its `Strategy` what to do
+/// with what faction has. Strategy only sees a `CommanderView` and answers with `Order`s, orders are checked and carried out
+/// by `systems::commanders_update` the same way player's commands are, so strategies can't cheat
+pub struct CommanderPlugin;
+
+impl Plugin for CommanderPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.init_resource::<Commanders>()
+
+			.add_systems(OnEnter(GameState::Main), (
+				systems::setup_commanders,
+			))
+
+			.add_systems(Update, (
+				systems::commanders_update.before(ai::systems::start_task_rally),
+			).run_if(in_state(GameState::Main)))
+		;
+	}
+}
+
+pub const THINK_INTERVAL : f32 = 1.0;
+
+/// Decides what faction does next. Implement it to write another opponent and pick it in map's `commanders` list
+pub trait Strategy : Send + Sync {
+	fn think(&mut self, view: &CommanderView) -> Vec<Order>;
+}
+
+/// Strategies that can be picked in map file
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum StrategyKind {
+	Balanced,
+	Greedy,
+}
+
+impl StrategyKind {
+	pub fn create(&self) -> Box<dyn Strategy> {
+		match self {
+			StrategyKind::Balanced	=> Box::new(strategies::Balanced::default()),
+			StrategyKind::Greedy	=> Box::new(strategies::Greedy::default()),
+		}
+	}
+}
+
+pub struct Commander {
+	pub faction		: Faction,
+	pub strategy	: Box<dyn Strategy>,
+	pub next_think	: f32,
+}
+
+impl Commander {
+	pub fn new(faction: Faction, strategy: Box<dyn Strategy>) -> Self {
+		Self { faction, strategy, next_think : 0.0 }
+	}
+}
+
+#[derive(Resource, Default)]
+pub struct Commanders(pub Vec<Commander>);
+
+/// What commander knows about its faction and the map at the moment of thinking
+pub struct CommanderView<'a> {
+	pub faction					: Faction,
+	pub collected				: CollectedResources,
+	pub bases					: Vec<BaseView>,
+	pub sites					: Vec<SiteView>,
+	pub units					: Vec<UnitView>,
+	/// every deposit on the map, not only those close to faction's bases
+	pub deposits				: Vec<DepositView>,
+	pub unit_definitions		: &'a UnitDefinitions,
+	pub building_definitions	: &'a BuildingDefinitions,
+}
+
+pub struct BaseView {
+	pub entity		: Entity,
+	pub position	: Vec3,
+	/// units in production queue
+	pub queued		: usize,
+	pub queue_full	: bool,
+}
+
+/// Faction's building that is still under construction
+pub struct SiteView {
+	pub entity			: Entity,
+	pub building_type	: BuildingType,
+	pub position		: Vec3,
+}
+
+pub struct UnitView {
+	pub entity		: Entity,
+	pub unit_type	: UnitType,
+	pub position	: Vec3,
+	pub job			: UnitJob,
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum UnitJob {
+	Idle,
+	/// walking somewhere without a task
+	Moving,
+	/// deposit can be gone already while unit delivers what it carries
+	Mining(Option<Entity>),
+	Building(Entity),
+}
+
+pub struct DepositView {
+	pub entity			: Entity,
+	pub resource_type	: CollectableResource,
+	pub position		: Vec3,
+	pub remaining		: usize,
+}
+
+impl CommanderView<'_> {
+	pub fn deposit(&self, entity: Entity) -> Option<&DepositView> {
+		self.deposits.iter().find(|deposit| deposit.entity == entity)
+	}
+
+	pub fn units_of_type(&self, unit_type: UnitType) -> usize {
+		self.units.iter().filter(|unit| unit.unit_type == unit_type).count()
+	}
+
+	pub fn queued(&self) -> usize {
+		self.bases.iter().map(|base| base.queued).sum()
+	}
+
+	/// Distance from position to the closest of faction's bases and construction sites
+	pub fn distance_to_nearest_base(&self, position: Vec3) -> f32 {
+		self.bases.iter().map(|base| base.position)
+			.chain(self.sites.iter().map(|site| site.position))
+			.map(|base_position| base_position.distance(position))
+			.fold(f32::MAX, f32::min)
+	}
+}
+
+/// What strategy wants to be done. Orders that can't be carried out (not enough resources, full queue, no place
+/// for a base etc) are skipped, strategy will see the same situation on next think and can try again
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Order {
+	/// queue unit at faction's base, it's paid from faction's stockpile right away
+	Produce { base: Entity, unit_type: UnitType },
+	/// send unit to mine deposit, it keeps mining until there are no deposits of the same type left to retarget to
+	Mine { unit: Entity, deposit: Entity },
+	/// place base construction site next to deposit, it's paid from faction's stockpile right away
+	Expand { deposit: Entity },
+	/// send unit to drill construction site until it's finished
+	Construct { unit: Entity, site: Entity },
+}
@@ -0,0 +1,231 @@
+use bevy :: {
+	prelude :: *,
+	utils :: HashSet,
+};
+
+use super :: *;
+
+/// Deposit closer than this to a base is mined from that base, farther ones need a new base
+pub const MINING_RADIUS : f32 = 40.0;
+
+/// Units waiting in a production queue are already paid for, so strategies don't queue more than this at a time
+pub const MAX_QUEUED : usize = 2;
+
+/// Keeps `workers_per_base` svins at every base and splits them between purplite and tealite. Expands to the nearest
+/// free deposit once bases are saturated or deposits around them run low, saving up for the new base if needed
+pub struct Balanced {
+	pub workers_per_base	: usize,
+	/// part of miners sent to tealite, svins cost only purplite but bases need tealite too
+	pub tealite_share		: f32,
+	pub builders_per_site	: usize,
+	/// shards left in deposits around bases below which it's time to expand
+	pub low_remaining		: usize,
+}
+
+impl Default for Balanced {
+	fn default() -> Self {
+		Self {
+			workers_per_base	: 8,
+			tealite_share		: 0.35,
+			builders_per_site	: 3,
+			low_remaining		: 100,
+		}
+	}
+}
+
+impl Strategy for Balanced {
+	fn think(&mut self, view: &CommanderView) -> Vec<Order> {
+		let mut orders = Vec::new();
+		let mut budget = view.collected;
+
+		let svin_cost = view.unit_definitions.get(UnitType::Svin).cost;
+		let base_cost = view.building_definitions.get(BuildingType::Base).cost;
+
+		let mut svins = view.units_of_type(UnitType::Svin) + view.queued();
+		let target_svins = self.workers_per_base * view.bases.len();
+
+		let saturated = svins >= target_svins;
+		let running_low = remaining_near_bases(view) < self.low_remaining;
+
+		let mut saving_for_base = false;
+
+		if view.sites.is_empty() && (saturated || running_low) {
+			if let Some(deposit) = free_deposit(view) {
+				if budget.is_enough(&base_cost) {
+					budget.deduct(&base_cost);
+					orders.push(Order::Expand { deposit : deposit.entity });
+				} else {
+					saving_for_base = true;
+				}
+			}
+		}
+
+		if !saving_for_base {
+			for base in view.bases.iter() {
+				if svins >= target_svins || !budget.is_enough(&svin_cost) { break }
+
+				if base.queue_full || base.queued >= MAX_QUEUED { continue }
+
+				budget.deduct(&svin_cost);
+				svins += 1;
+
+				orders.push(Order::Produce { base : base.entity, unit_type : UnitType::Svin });
+			}
+		}
+
+		let mut busy = HashSet::new();
+
+		for site in view.sites.iter() {
+			let builders = view.units.iter().filter(|unit| unit.job == UnitJob::Building(site.entity)).count();
+
+			for _ in builders .. self.builders_per_site {
+				// idle units go first, then miners closest to the site
+				let Some(unit) = nearest_worker(view, site.position, &busy, true).or_else(|| nearest_worker(view, site.position, &busy, false)) else { break };
+
+				busy.insert(unit.entity);
+
+				orders.push(Order::Construct { unit : unit.entity, site : site.entity });
+			}
+		}
+
+		let miners = |resource_type: CollectableResource| {
+			view.units.iter()
+				.filter_map(|unit| match unit.job { UnitJob::Mining(Some(deposit)) => view.deposit(deposit), _ => None })
+				.filter(|deposit| deposit.resource_type == resource_type)
+				.count()
+		};
+
+		let mut tealite_miners = miners(CollectableResource::Tealite);
+		let mut purplite_miners = miners(CollectableResource::Purplite);
+
+		for unit in idle_workers(view, &busy) {
+			let tealite_wanted = (self.tealite_share * (tealite_miners + purplite_miners + 1) as f32).round() as usize;
+
+			let preferred = if tealite_miners < tealite_wanted {
+				CollectableResource::Tealite
+			} else {
+				CollectableResource::Purplite
+			};
+
+			let Some(deposit) = nearest_deposit(view, unit.position, Some(preferred)).or_else(|| nearest_deposit(view, unit.position, None)) else { break };
+
+			match deposit.resource_type {
+				CollectableResource::Tealite => tealite_miners += 1,
+				CollectableResource::Purplite => purplite_miners += 1,
+			}
+
+			orders.push(Order::Mine { unit : unit.entity, deposit : deposit.entity });
+		}
+
+		orders
+	}
+}
+
+/// Spends everything on svins and sends each of them to the nearest deposit whatever it is. Expands only when
+/// there is nothing left to mine around its bases
+pub struct Greedy {
+	pub max_svins		: usize,
+}
+
+impl Default for Greedy {
+	fn default() -> Self {
+		Self {
+			max_svins		: 40,
+		}
+	}
+}
+
+impl Strategy for Greedy {
+	fn think(&mut self, view: &CommanderView) -> Vec<Order> {
+		let mut orders = Vec::new();
+		let mut budget = view.collected;
+
+		let svin_cost = view.unit_definitions.get(UnitType::Svin).cost;
+		let base_cost = view.building_definitions.get(BuildingType::Base).cost;
+
+		let mut svins = view.units_of_type(UnitType::Svin) + view.queued();
+
+		let nothing_to_mine = remaining_near_bases(view) == 0;
+
+		if nothing_to_mine && view.sites.is_empty() {
+			if let Some(deposit) = free_deposit(view) {
+				if budget.is_enough(&base_cost) {
+					budget.deduct(&base_cost);
+					orders.push(Order::Expand { deposit : deposit.entity });
+				}
+			}
+		}
+
+		// svins are useless without anything to mine, better save for a new base
+		if !nothing_to_mine {
+			for base in view.bases.iter() {
+				if svins >= self.max_svins || !budget.is_enough(&svin_cost) { break }
+
+				if base.queue_full || base.queued >= MAX_QUEUED { continue }
+
+				budget.deduct(&svin_cost);
+				svins += 1;
+
+				orders.push(Order::Produce { base : base.entity, unit_type : UnitType::Svin });
+			}
+		}
+
+		let busy = HashSet::new();
+
+		for unit in idle_workers(view, &busy) {
+			if let Some(site) = view.sites.first() {
+				orders.push(Order::Construct { unit : unit.entity, site : site.entity });
+			} else if let Some(deposit) = nearest_deposit(view, unit.position, None) {
+				orders.push(Order::Mine { unit : unit.entity, deposit : deposit.entity });
+			}
+		}
+
+		orders
+	}
+}
+
+fn can_drill(view: &CommanderView, unit: &UnitView) -> bool {
+	view.unit_definitions.get(unit.unit_type).drill
+}
+
+/// Units with a drill and nothing to do
+fn idle_workers<'a>(view: &'a CommanderView<'a>, busy: &'a HashSet<Entity>) -> impl Iterator<Item = &'a UnitView> {
+	view.units.iter().filter(move |unit| unit.job == UnitJob::Idle && can_drill(view, unit) && !busy.contains(&unit.entity))
+}
+
+fn nearest_worker<'a>(view: &'a CommanderView, position: Vec3, busy: &HashSet<Entity>, idle_only: bool) -> Option<&'a UnitView> {
+	view.units.iter()
+		.filter(|unit| can_drill(view, unit) && !busy.contains(&unit.entity))
+		.filter(|unit| match unit.job {
+			UnitJob::Idle => true,
+			UnitJob::Mining(_) => !idle_only,
+			UnitJob::Moving | UnitJob::Building(_) => false,
+		})
+		.min_by(|a, b| a.position.distance_squared(position).total_cmp(&b.position.distance_squared(position)))
+}
+
+/// Nearest deposit that isn't depleted, of any type if `resource_type` is `None`
+fn nearest_deposit<'a>(view: &'a CommanderView, position: Vec3, resource_type: Option<CollectableResource>) -> Option<&'a DepositView> {
+	view.deposits.iter()
+		.filter(|deposit| deposit.remaining > 0 && resource_type.map_or(true, |resource_type| deposit.resource_type == resource_type))
+		.min_by(|a, b| a.position.distance_squared(position).total_cmp(&b.position.distance_squared(position)))
+}
+
+fn remaining_near_bases(view: &CommanderView) -> usize {
+	view.deposits.iter()
+		.filter(|deposit| view.distance_to_nearest_base(deposit.position) < MINING_RADIUS)
+		.map(|deposit| deposit.remaining)
+		.sum()
+}
+
+/// Deposit closest to faction's bases that none of them mines yet
+fn free_deposit<'a>(view: &'a CommanderView) -> Option<&'a DepositView> {
+	if view.bases.is_empty() { return None }
+
+	view.deposits.iter()
+		.filter(|deposit| deposit.remaining > 0)
+		.map(|deposit| (deposit, view.distance_to_nearest_base(deposit.position)))
+		.filter(|(_, distance)| *distance >= MINING_RADIUS)
+		.min_by(|(_, a), (_, b)| a.total_cmp(b))
+		.map(|(deposit, _)| deposit)
+}
@@ -0,0 +1,227 @@
+use bevy :: prelude :: *;
+
+use bevy_rapier3d :: prelude :: *;
+
+use super :: *;
+
+use crate :: options :: LaunchOptions;
+
+use crate :: assets :: GameAssets;
+
+use crate :: map :: { Map, Terrain };
+
+use crate :: game :: {
+	BaseBuilding,
+	ProductionQueue,
+};
+
+use crate :: ai :: {
+	PolyanyaResource,
+	MovePath,
+	NpcMovable,
+	NpcTaskRally,
+	NpcTaskResourceCollection,
+	NpcTaskConstruction,
+	RallyPoint,
+};
+
+use crate :: buildings :: {
+	UnderConstruction,
+	spawn as buildings_spawn,
+	systems :: { on_terrain, placement_allowed },
+};
+
+use crate :: resource_collection :: {
+	FactionResources,
+	ResourceDeposit,
+};
+
+use std :: f32 :: consts :: TAU;
+
+/// How far from deposit commander tries to place a new base, far enough for workers to walk around the deposit
+const EXPAND_DISTANCE : f32 = 14.0;
+const EXPAND_DIRECTIONS : usize = 8;
+
+/// Commanders are listed in map file. Player's faction is never given to a commander unless there is no player (headless)
+pub fn setup_commanders(
+		launch_options	: Res<LaunchOptions>,
+		game_assets		: Res<GameAssets>,
+		maps			: Res<Assets<Map>>,
+	mut commanders		: ResMut<Commanders>,
+) {
+	let Some(map) = maps.get(&game_assets.map) else { panic!("map should be loaded before setup!") };
+
+	commanders.0 = map.commanders.iter()
+		.filter(|map_commander| launch_options.headless || map_commander.faction != map.player_faction)
+		.map(|map_commander| Commander::new(map_commander.faction, map_commander.strategy.create()))
+		.collect();
+
+	for commander in commanders.0.iter() {
+		println!("{:?} faction is controlled by commander", commander.faction);
+	}
+}
+
+pub fn commanders_update(
+		time					: Res<Time>,
+		unit_definitions		: Res<UnitDefinitions>,
+		building_definitions	: Res<BuildingDefinitions>,
+		game_assets				: Res<GameAssets>,
+		rapier_context			: Res<RapierContext>,
+		polyanya				: Res<PolyanyaResource>,
+		terrain					: Res<Terrain>,
+		q_site					: Query<(Entity, &BuildingType, &Faction, &Transform), With<UnderConstruction>>,
+		q_unit					: Query<(Entity, &UnitType, &Faction, &Transform, Option<&NpcTaskResourceCollection>, Option<&NpcTaskConstruction>, Has<NpcTaskRally>, Has<MovePath>), With<NpcMovable>>,
+		q_deposit				: Query<(Entity, &CollectableResource, &ResourceDeposit, &Transform)>,
+	mut q_base					: Query<(Entity, &Faction, &Transform, &mut ProductionQueue), With<BaseBuilding>>,
+	mut commanders				: ResMut<Commanders>,
+	mut faction_resources		: ResMut<FactionResources>,
+	mut meshes					: ResMut<Assets<Mesh>>,
+	mut materials				: ResMut<Assets<StandardMaterial>>,
+	mut commands				: Commands,
+) {
+	let now = time.elapsed_seconds();
+
+	for commander in commanders.0.iter_mut() {
+		if now < commander.next_think { continue }
+
+		commander.next_think = now + THINK_INTERVAL;
+
+		let faction = commander.faction;
+
+		let view = CommanderView {
+			faction,
+			collected : faction_resources.get(faction),
+			bases : q_base.iter()
+				.filter(|(_, base_faction, _, _)| **base_faction == faction)
+				.map(|(entity, _, transform, production_queue)| BaseView {
+					entity,
+					position	: transform.translation,
+					queued		: production_queue.items.len(),
+					queue_full	: production_queue.is_full(),
+				})
+				.collect(),
+			sites : q_site.iter()
+				.filter(|(_, _, site_faction, _)| **site_faction == faction)
+				.map(|(entity, building_type, _, transform)| SiteView {
+					entity,
+					building_type	: *building_type,
+					position		: transform.translation,
+				})
+				.collect(),
+			units : q_unit.iter()
+				.filter(|(_, _, unit_faction, ..)| **unit_faction == faction)
+				.map(|(entity, unit_type, _, transform, collection, construction, rallying, moving)| UnitView {
+					entity,
+					unit_type	: *unit_type,
+					position	: transform.translation,
+					job			: match (collection, construction) {
+						(Some(collection), _)	=> UnitJob::Mining(collection.resource_entity),
+						(_, Some(construction))	=> UnitJob::Building(construction.building_entity),
+						_ if rallying || moving	=> UnitJob::Moving,
+						_						=> UnitJob::Idle,
+					},
+				})
+				.collect(),
+			deposits : q_deposit.iter()
+				.map(|(entity, resource_type, deposit, transform)| DepositView {
+					entity,
+					resource_type	: *resource_type,
+					position		: transform.translation,
+					remaining		: deposit.remaining,
+				})
+				.collect(),
+			unit_definitions		: &unit_definitions,
+			building_definitions	: &building_definitions,
+		};
+
+		let orders = commander.strategy.think(&view);
+
+		for order in orders {
+			match order {
+				Order::Produce { base, unit_type } => {
+					let Ok((_, base_faction, _, mut production_queue)) = q_base.get_mut(base) else { continue };
+
+					if *base_faction != faction || production_queue.is_full() { continue }
+
+					let definition = unit_definitions.get(unit_type);
+
+					let collected_resources = faction_resources.get_mut(faction);
+
+					if !collected_resources.is_enough(&definition.cost) { continue }
+
+					collected_resources.deduct(&definition.cost);
+
+					production_queue.push(unit_type, definition.build_time, definition.cost);
+				},
+				Order::Mine { unit, deposit : target } => {
+					let Some(deposit) = view.deposit(target) else { continue };
+
+					send_unit(unit, deposit.position, target, faction, &q_unit, &mut commands);
+				},
+				Order::Construct { unit, site : target } => {
+					let Some(site) = view.sites.iter().find(|site| site.entity == target) else { continue };
+
+					send_unit(unit, site.position, target, faction, &q_unit, &mut commands);
+				},
+				Order::Expand { deposit : target } => {
+					let Some(deposit) = view.deposit(target) else { continue };
+
+					let definition = building_definitions.get(BuildingType::Base);
+
+					let collected_resources = faction_resources.get_mut(faction);
+
+					if !collected_resources.is_enough(&definition.cost) { continue }
+
+					// try spots around deposit starting from the one closest to faction's bases
+					let mut candidates = (0 .. EXPAND_DIRECTIONS)
+						.map(|i| {
+							let direction = Quat::from_rotation_y(TAU * i as f32 / EXPAND_DIRECTIONS as f32) * Vec3::X;
+
+							// checked and placed the same way as buildings placed by player
+							on_terrain(&Transform::from_translation(deposit.position + direction * EXPAND_DISTANCE), definition.size, &terrain)
+						})
+						.collect::<Vec<Transform>>();
+
+					candidates.sort_by(|a, b| view.distance_to_nearest_base(a.translation).total_cmp(&view.distance_to_nearest_base(b.translation)));
+
+					let Some(transform) = candidates.into_iter().find(|transform| placement_allowed(transform, definition.size, &rapier_context, &polyanya, &terrain)) else {
+						println!("{:?} commander found no place for a base near {:?}", faction, deposit.position);
+						continue
+					};
+
+					collected_resources.deduct(&definition.cost);
+
+					buildings_spawn::construction_site(
+						BuildingType::Base,
+						transform,
+						definition,
+						faction,
+						&game_assets,
+						&mut meshes,
+						&mut materials,
+						&mut commands
+					);
+				},
+			}
+		}
+	}
+}
+
+/// Commander's units are sent the same way player's freshly produced units are, through a rally point
+fn send_unit(
+	unit_entity		: Entity,
+	position		: Vec3,
+	target_entity	: Entity,
+	faction			: Faction,
+	q_unit			: &Query<(Entity, &UnitType, &Faction, &Transform, Option<&NpcTaskResourceCollection>, Option<&NpcTaskConstruction>, Has<NpcTaskRally>, Has<MovePath>), With<NpcMovable>>,
+	commands		: &mut Commands,
+) {
+	let Ok((_, _, unit_faction, ..)) = q_unit.get(unit_entity) else { return };
+
+	if *unit_faction != faction { return }
+
+	commands.entity(unit_entity).insert(NpcTaskRally(RallyPoint {
+		position,
+		target_entity : Some(target_entity),
+	}));
+}
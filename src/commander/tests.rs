@@ -0,0 +1,110 @@
+use bevy :: scene :: ron;
+
+use super :: *;
+
+use super :: strategies :: { Balanced, MINING_RADIUS };
+
+use crate :: test_support :: TestApp;
+
+use crate :: ai :: NpcTaskResourceCollection;
+
+use crate :: resource_collection :: FactionResources;
+
+fn definitions() -> (UnitDefinitions, BuildingDefinitions) {
+	let Ok(unit_definitions) = ron::de::from_str::<UnitDefinitions>(include_str!("../../assets/units/default.units.ron")) else { panic!("failed to parse unit definitions") };
+	let Ok(building_definitions) = ron::de::from_str::<BuildingDefinitions>(include_str!("../../assets/buildings/default.buildings.ron")) else { panic!("failed to parse building definitions") };
+
+	(unit_definitions, building_definitions)
+}
+
+fn view<'a>(unit_definitions: &'a UnitDefinitions, building_definitions: &'a BuildingDefinitions) -> CommanderView<'a> {
+	CommanderView {
+		faction		: Faction::Red,
+		collected	: CollectedResources::default(),
+		bases		: vec![BaseView { entity : Entity::from_raw(1), position : Vec3::ZERO, queued : 0, queue_full : false }],
+		sites		: Vec::new(),
+		units		: Vec::new(),
+		deposits	: vec![
+			DepositView { entity : Entity::from_raw(2), resource_type : CollectableResource::Tealite, position : Vec3::new(3.0, 0.0, 15.0), remaining : 300 },
+			DepositView { entity : Entity::from_raw(3), resource_type : CollectableResource::Purplite, position : Vec3::new(-3.0, 0.0, 15.0), remaining : 300 },
+		],
+		unit_definitions,
+		building_definitions,
+	}
+}
+
+fn idle_svin(index: u32) -> UnitView {
+	UnitView { entity : Entity::from_raw(100 + index), unit_type : UnitType::Svin, position : Vec3::ZERO, job : UnitJob::Idle }
+}
+
+#[test]
+fn balanced_strategy_splits_idle_svins_between_resources() {
+	let (unit_definitions, building_definitions) = definitions();
+
+	let mut view = view(&unit_definitions, &building_definitions);
+	view.units = (0 .. 6).map(idle_svin).collect();
+
+	let orders = Balanced::default().think(&view);
+
+	let mining = |deposit_entity: Entity| orders.iter().filter(|order| matches!(order, Order::Mine { deposit, .. } if *deposit == deposit_entity)).count();
+
+	let (tealite, purplite) = (mining(view.deposits[0].entity), mining(view.deposits[1].entity));
+
+	assert_eq!(tealite + purplite, 6, "every idle svin should be sent mining: {:?}", orders);
+	assert!(tealite >= 1 && purplite > tealite, "svins are split badly, tealite {} purplite {}", tealite, purplite);
+
+	// nothing to pay with
+	assert!(!orders.iter().any(|order| matches!(order, Order::Produce { .. } | Order::Expand { .. })));
+}
+
+#[test]
+fn balanced_strategy_saves_for_expansion_when_deposits_run_low() {
+	let (unit_definitions, building_definitions) = definitions();
+
+	let mut view = view(&unit_definitions, &building_definitions);
+
+	for deposit in view.deposits.iter_mut() {
+		deposit.remaining = 10;
+	}
+
+	let far_deposit = Entity::from_raw(4);
+
+	view.deposits.push(DepositView { entity : far_deposit, resource_type : CollectableResource::Purplite, position : Vec3::new(MINING_RADIUS * 2.0, 0.0, 0.0), remaining : 300 });
+
+	// enough for svins but not for a base
+	view.collected = CollectedResources { purplite : 50, tealite : 0 };
+
+	let orders = Balanced::default().think(&view);
+	assert!(orders.is_empty(), "commander should save resources for a new base: {:?}", orders);
+
+	view.collected = CollectedResources { purplite : 100, tealite : 100 };
+
+	let orders = Balanced::default().think(&view);
+	assert!(orders.contains(&Order::Expand { deposit : far_deposit }), "commander didn't expand to the free deposit: {:?}", orders);
+}
+
+#[test]
+fn commander_produces_svins_and_sends_them_mining() {
+	let mut test_app = TestApp::new();
+
+	let base_entity = test_app.base();
+
+	test_app.app.world.entity_mut(base_entity).insert(Faction::Red);
+
+	*test_app.app.world.resource_mut::<FactionResources>().get_mut(Faction::Red) = CollectedResources { purplite : 60, tealite : 0 };
+
+	test_app.app.world.resource_mut::<Commanders>().0.push(Commander::new(Faction::Red, StrategyKind::Balanced.create()));
+
+	let mining = test_app.step_until(60.0, |world| {
+		world.query_filtered::<&Faction, With<NpcTaskResourceCollection>>().iter(world).filter(|faction| **faction == Faction::Red).count() >= 2
+	});
+	assert!(mining, "commander didn't produce svins or didn't send them mining");
+
+	let delivered = test_app.step_until(90.0, |world| {
+		let collected = world.resource::<FactionResources>().get(Faction::Red);
+		collected.purplite + collected.tealite > 0
+	});
+	assert!(delivered, "commander's svins didn't deliver anything");
+
+	assert_eq!(test_app.collected().purplite + test_app.collected().tealite, 0, "commander's svins delivered to player's stockpile");
+}
@@ -0,0 +1,40 @@
+use bevy :: prelude :: *;
+
+use serde :: Deserialize;
+
+#[cfg(test)]
+mod tests;
+
+pub struct FactionPlugin;
+
+impl Plugin for FactionPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.register_type::<Faction>()
+		;
+	}
+}
+
+/// Who owns a base, a unit or a handheld. Local player is whatever faction player entity has,
+/// player can only select and command entities of that faction
+#[derive(Component, Reflect, Deserialize, Default, PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub enum Faction {
+	#[default]
+	Blue,
+	Red,
+	Green,
+}
+
+impl Faction {
+	pub const ALL : [Faction; 3] = [Faction::Blue, Faction::Red, Faction::Green];
+
+	/// used for selection indicators
+	pub fn color(&self) -> Color {
+		match self {
+			Faction::Blue	=> Color::rgb(0.15, 0.55, 1.0),
+			Faction::Red	=> Color::rgb(1.0, 0.25, 0.2),
+			Faction::Green	=> Color::rgb(0.3, 0.9, 0.3),
+		}
+	}
+}
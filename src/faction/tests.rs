@@ -0,0 +1,33 @@
+use super :: *;
+
+use crate :: test_support :: TestApp;
+
+use crate :: handheld :: HandheldOwner;
+
+use crate :: resource_collection :: {
+	CollectableResource,
+	FactionResources,
+};
+
+#[test]
+fn svins_deliver_to_stockpile_of_their_base_faction() {
+	let mut test_app = TestApp::new();
+
+	let base_entity = test_app.base();
+	let tealite_entity = test_app.deposit(CollectableResource::Tealite);
+
+	test_app.app.world.entity_mut(base_entity).insert(Faction::Red);
+
+	test_app.queue_svins(&[(1, tealite_entity)]);
+
+	let delivered = test_app.step_until(90.0, |world| world.resource::<FactionResources>().get(Faction::Red).tealite > 0);
+	assert!(delivered, "svin of red base didn't deliver tealite to red stockpile");
+
+	let svin_entity = test_app.svins()[0];
+	assert_eq!(test_app.app.world.get::<Faction>(svin_entity), Some(&Faction::Red));
+
+	let Some(handheld_owner) = test_app.app.world.get::<HandheldOwner>(svin_entity) else { panic!("svin has no drill!") };
+	assert_eq!(test_app.app.world.get::<Faction>(handheld_owner.handheld_entity), Some(&Faction::Red));
+
+	assert_eq!(test_app.collected().tealite, 0, "red svin delivered to player's stockpile");
+}
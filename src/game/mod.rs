@@ -1,41 +1,70 @@
 use bevy :: prelude :: *;
 
+use serde :: Deserialize;
+
 use super :: ai;
 
+use super :: units :: UnitType;
+
+use super :: buildings :: BuildingType;
+
+use super :: faction :: Faction;
+
 pub mod spawn;
 	mod systems;
 
+#[cfg(test)]
+mod tests;
+
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
 	fn build(&self, app: &mut App) {
 		app
+			.register_type::<BaseBuilding>()
+			.register_type::<ProductionQueue>()
+			.register_type::<ProductionItem>()
+			.register_type::<BatchOfResources>()
+
 			.init_state::<GameState>()
+			.init_state::<InputContext>()
+			.init_state::<CameraMode>()
 
 			.insert_resource(BuildMenuState::default())
+			.insert_resource(SelectionBox::default())
+			.insert_resource(ControlGroups::default())
 
+			.add_systems(Update, (
+				systems::production_control,
+				systems::control_groups_prune,
+			).run_if(in_state(GameState::Main)))
+
+			// everything below needs player and cameras so none of it runs in headless mode
 			.add_systems(PreUpdate, (
 				systems::culling_control,
-			).run_if(in_state(GameState::Main)))
+			).run_if(in_state(GameState::Main)).run_if(resource_exists::<MainEntities>))
 
 			.add_systems(Update, (
 				systems::cursor_control,
 				systems::camera_raypick,
 				systems::toggle_orbit_camera,
+				systems::rts_camera_control.run_if(in_state(CameraMode::Rts)),
 				systems::player_state_control,
 				systems::player_input_control,
 				systems::selectable_control.after(ai::systems::movable_update),
+				systems::selection_box_control.after(ai::systems::movable_update),
+				systems::selection_box_draw.after(systems::selection_box_control),
+				systems::select_idle_svins.run_if(in_state(InputContext::Gameplay)),
 				systems::selectable_draw,
-				systems::build_menu_control,
-				systems::build_menu_draw,
-			).run_if(in_state(GameState::Main)))
+				systems::build_menu_toggle.run_if(not(in_state(InputContext::Placement))),
+				systems::build_menu_control.after(systems::build_menu_toggle).run_if(in_state(InputContext::BuildMenu)),
+				systems::build_menu_draw.after(systems::build_menu_control),
+				systems::control_groups_control.run_if(in_state(InputContext::Gameplay)),
+			).run_if(in_state(GameState::Main)).run_if(resource_exists::<MainEntities>))
 		;
 	}
 }
 
-pub const SVIN_CARRYING_CAPACITY : usize = 3;
-pub const SVIN_PRICE : BatchOfResources = BatchOfResources::new(15, 0);
-
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, States, Default)]
 pub enum GameState {
 	#[default]
@@ -44,6 +73,41 @@ pub enum GameState {
 	Main,
 }
 
+/// Decides which systems get input that several of them listen to: digit keys pick build menu entries while it's open
+/// and recall control groups otherwise, orders are given to npcs only in gameplay. Build menu is open and building is placed
+/// only while context says so, it's switched by whatever opens or closes them in the same frame that happens
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, States, Default)]
+pub enum InputContext {
+	#[default]
+	Gameplay,
+	BuildMenu,
+	Placement,
+}
+
+/// First person camera aims with crosshair, in rts mode pan-orbit camera is active and everything is pointed at with cursor
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, States, Default)]
+pub enum CameraMode {
+	#[default]
+	FirstPerson,
+	Rts,
+}
+
+/// How far cursor ray reaches in rts mode, camera can be much farther from the ground than player
+pub const RTS_RAYPICK_LENGTH : f32 = 500.0;
+
+/// Cursor closer than this many pixels to window edge moves rts camera
+pub const RTS_EDGE_SCROLL_MARGIN : f32 = 10.0;
+
+/// Rts camera speed, multiplied by its distance to focus point
+pub const RTS_PAN_SPEED : f32 = 1.0;
+
+pub const RTS_ZOOM_MIN : f32 = 8.0;
+pub const RTS_ZOOM_MAX : f32 = 80.0;
+
+/// Rts camera looks at the ground from above, between these pitch angles in radians
+pub const RTS_PITCH_MIN : f32 = 0.6;
+pub const RTS_PITCH_MAX : f32 = 1.4;
+
 #[derive(Resource)]
 pub struct MainEntities {
 	pub player_camera	: Entity,
@@ -52,6 +116,17 @@ pub struct MainEntities {
 	pub player			: Entity,
 	pub player_handheld	: Entity,
 	pub build_menu		: Entity,
+	pub selection_box	: Entity,
+}
+
+impl MainEntities {
+	/// Camera whose raypick is used for selection and commands in given camera mode
+	pub fn raypick_camera(&self, camera_mode: CameraMode) -> Entity {
+		match camera_mode {
+			CameraMode::FirstPerson	=> self.player_camera,
+			CameraMode::Rts			=> self.orbit_camera,
+		}
+	}
 }
 
 #[derive(Component, Default)]
@@ -59,9 +134,38 @@ pub struct PlayerState {
 	pub drilling_animation_allowed : bool,
 }
 
-#[derive(Component)]
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct BaseBuilding;
 
+pub const PRODUCTION_QUEUE_LIMIT : usize = 5;
+
+/// Units ordered at a base building. Only the first item is being built, once it's done it waits for a free spawn locator
+#[derive(Component, Reflect, Default, Debug)]
+#[reflect(Component)]
+pub struct ProductionQueue {
+	pub items		: Vec<ProductionItem>,
+}
+
+impl ProductionQueue {
+	pub fn is_full(&self) -> bool {
+		self.items.len() >= PRODUCTION_QUEUE_LIMIT
+	}
+
+	pub fn push(&mut self, unit_type: UnitType, build_time: f32, paid: BatchOfResources) {
+		self.items.push(ProductionItem { unit_type, remaining : build_time, paid });
+	}
+}
+
+#[derive(Reflect, Clone, Copy, Debug)]
+pub struct ProductionItem {
+	pub unit_type	: UnitType,
+	/// seconds left to build, zero means the unit is ready and waits for a free spawn locator
+	pub remaining	: f32,
+	/// what was deducted when unit was queued, this is what cancelling gives back even if unit cost was changed since
+	pub paid		: BatchOfResources,
+}
+
 #[derive(Component, Default)]
 pub struct Raypick {
 	pub entity		: Option<Entity>,
@@ -69,6 +173,8 @@ pub struct Raypick {
 	pub pos			: Vec3,
 	pub nrm			: Vec3,
 	pub dist		: f32,
+	/// cast through mouse cursor instead of camera's forward axis
+	pub from_cursor	: bool,
 }
 
 #[derive(Component, Default)]
@@ -80,6 +186,53 @@ pub struct Selectable {
 #[derive(Component)]
 pub struct Selected;
 
+/// Drag shorter than this many pixels is a click
+pub const SELECTION_DRAG_THRESHOLD : f32 = 5.0;
+
+/// Click in pan-orbit camera picks selectable whose position is projected this close to cursor, in pixels
+pub const SELECTION_CLICK_RADIUS : f32 = 30.0;
+
+/// Second click on the same unit within this many seconds selects all visible units of its type
+pub const DOUBLE_CLICK_TIME : f32 = 0.3;
+
+/// Second press of the same control group key within this many seconds centers pan-orbit camera on the group
+pub const DOUBLE_TAP_TIME : f32 = 0.3;
+
+/// Selections stored with Ctrl + digit and recalled with digit. Despawned entities are dropped by `control_groups_prune`
+#[derive(Resource, Default)]
+pub struct ControlGroups {
+	/// n-th group is bound to n-th key of `CONTROL_GROUP_KEYS`
+	pub groups		: [Vec<Entity>; 10],
+	/// when and which group was recalled last time
+	pub last_recall	: Option<(f32, usize)>,
+}
+
+impl ControlGroups {
+	pub fn remove(&mut self, entity: Entity) {
+		for group in self.groups.iter_mut() {
+			group.retain(|grouped_entity| *grouped_entity != entity);
+		}
+	}
+}
+
+/// Marquee drag selection in pan-orbit camera and the last click to detect double clicks
+#[derive(Resource, Default)]
+pub struct SelectionBox {
+	/// cursor position where left mouse button was pressed, `None` while not dragging
+	pub start		: Option<Vec2>,
+	/// when and what was clicked last time
+	pub last_click	: Option<(f32, Entity)>,
+}
+
+/// Selectable as it's seen through pan-orbit camera
+pub struct OnScreen {
+	pub entity		: Entity,
+	pub position	: Vec2,
+	pub faction		: Option<Faction>,
+	/// marquee picks units before buildings
+	pub unit		: bool,
+}
+
 #[derive(Component, Default)]
 pub struct Culling {
 	pub particles	: bool,
@@ -88,19 +241,30 @@ pub struct Culling {
 
 #[derive(Resource, Default)]
 pub struct BuildMenuState {
-	pub active		: bool,
+	/// what menu offers right now, n-th entry is picked with n-th digit key
+	pub entries		: Vec<BuildMenuEntry>,
+}
+
+/// Units are built at selected base, buildings are placed anywhere so they are offered with or without a selected base
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BuildMenuEntry {
+	Unit(UnitType),
+	Building(BuildingType),
 }
 
+#[derive(Reflect, Deserialize, Default, Clone, Copy, Debug)]
 pub struct BatchOfResources {
 	pub purplite	: usize,
 	pub tealite		: usize,
 }
 
-impl BatchOfResources {
-	pub const fn new(purplite: usize, tealite: usize) -> Self {
-		Self {
-			purplite,
-			tealite
+impl std::fmt::Display for BatchOfResources {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		match (self.purplite, self.tealite) {
+			(0, 0) => write!(f, "free"),
+			(purplite, 0) => write!(f, "{} purplite", purplite),
+			(0, tealite) => write!(f, "{} tealite", tealite),
+			(purplite, tealite) => write!(f, "{} purplite, {} tealite", purplite, tealite),
 		}
 	}
 }
@@ -2,20 +2,23 @@ use bevy :: prelude :: *;
 
 use bevy_rapier3d :: prelude :: *;
 
-use polyanya :: Mesh as PolyanyaMesh;
-
 use super :: {
 	BaseBuilding,
+	ProductionQueue,
 	Selectable,
 	Raypick,
 	Culling,
 };
 
-use crate :: resource_collection :: { ResourceCollector, CollectableResource };
+use crate :: resource_collection :: { ResourceCollector, CollectableResource, ResourceDeposit };
 
 use crate :: assets :: GameAssets;
 
-use crate :: ai :: NpcMovable;
+use crate :: units :: { UnitDefinition, UnitType };
+
+use crate :: ai :: { NpcMovable, NpcCommandQueue, Steering, PolyanyaResource };
+
+use crate :: faction :: Faction;
 
 use crate :: handheld :: {
 	Handheld,
@@ -25,30 +28,35 @@ use crate :: handheld :: {
 
 use crate :: ai :: { NavmeshObstacleContainer, LocatorsContainer, NpcInteractable, NpcSpawner };
 
-use std :: f32 :: consts :: PI;
+use rand :: Rng;
+
+use std :: f32 :: consts :: { PI, TAU };
 
-pub fn svin(
+pub fn unit(
 	spawn_locator	: &Transform,
-	collection_limit: usize,
+	unit_type		: UnitType,
+	definition		: &UnitDefinition,
+	faction			: Faction,
 	game_assets		: &GameAssets,
-	with_drill		: bool,
 	base_entity		: Option<Entity>,
-	navmesh			: Option<&PolyanyaMesh>,
+	polyanya		: Option<&PolyanyaResource>,
 	rapier_context	: Option<&RapierContext>,
 	commands		: &mut Commands,
 ) -> Option<(Entity, Collider)> {
-	let cylinder_half_height = 0.6;
-	let cylinder_radius = 0.5;
+	let cylinder_half_height = definition.collider.half_height;
+	let cylinder_radius = definition.collider.radius;
 
 	let spawn_position = spawn_locator.translation + Vec3::Y * (cylinder_half_height + 0.01);
 
-	let svin_collider = Collider::cylinder(cylinder_half_height, cylinder_radius);
+	// collider dimensions in definition are in world space, unit transform is scaled so the attached collider is scaled back
+	let world_collider = Collider::cylinder(cylinder_half_height, cylinder_radius);
+	let unit_collider = Collider::cylinder(cylinder_half_height / definition.scale, cylinder_radius / definition.scale);
 
 	if let Some(rapier) = rapier_context {
 		if let Some(_) = rapier.intersection_with_shape(
 			spawn_position,
 			spawn_locator.rotation,
-			&svin_collider,
+			&world_collider,
 			QueryFilter::new(),
 		) {
 			// println!("skipping spawning because spawn point is occupied! {:?} {:?}", spawn_position, entity);
@@ -56,33 +64,36 @@ pub fn svin(
 		}
 	}
 
-	if let Some(navmesh) = navmesh {
-		if !navmesh.point_in_mesh(Vec2::new(spawn_position.x, spawn_position.z)) {
+	if let Some(polyanya) = polyanya {
+		if !polyanya.point_in_mesh(spawn_position) {
 			// println!("skipping spawning because spawn point is not on navmesh! occupied! {:?}", spawn_position);
 			return None;
 		}
 	}
 
-	let svin_entity = commands.spawn((
-		Name::new("Svin Bot"),
+	let unit_entity = commands.spawn((
+		Name::new(definition.name.clone()),
 		SceneBundle {
-			scene : game_assets.svin.clone_weak(),
+			scene : game_assets.unit_scene(unit_type),
 			transform: Transform {
 				translation : spawn_position,
 				rotation : spawn_locator.rotation,
-				..default()
+				scale : Vec3::splat(definition.scale),
 			},
 			..default()
 		},
+		unit_type,
+		faction,
 		NpcMovable,
-		ResourceCollector { limit : collection_limit, base_building_entity : base_entity },
-		Selectable { indicator_offset : Vec3::Y, ..default() },
+		NpcCommandQueue::default(),
+		Steering::default(),
+		Selectable { indicator_offset : Vec3::Y * definition.scale, ..default() },
 		Culling::default(),
 		RigidBody::Fixed,
-		svin_collider.clone(),
+		unit_collider.clone(),
 	)).id();
 
-	if with_drill {		
+	if definition.drill {
 		let drill_transform = Transform {
 			translation: Vec3::new(-0.25, 0.0, 0.7),
 			rotation: Quat::from_euler(EulerRot::XYZ, 0.1, PI + 0.1, -1.3),
@@ -91,27 +102,37 @@ pub fn svin(
 
 		let drill_entity = drill(
 			&drill_transform,
-			Some(svin_entity),
+			Some(unit_entity),
+			faction,
 			game_assets,
 			commands
 		);
 
-		commands.entity(svin_entity)
+		commands.entity(unit_entity)
 			.add_child(drill_entity)
-			.insert(HandheldOwner { handheld_entity: drill_entity });
+			.insert((
+				HandheldOwner { handheld_entity: drill_entity },
+				ResourceCollector {
+					limit : definition.capacity,
+					mining_interval : definition.mining_interval,
+					base_building_entity : base_entity
+				},
+			));
 	}
-	
-	Some((svin_entity, svin_collider))
+
+	Some((unit_entity, unit_collider))
 }
 
 pub fn tealite(
 	transform	: Transform,
+	amount		: usize,
 	game_assets	: &GameAssets,
 	commands	: &mut Commands
 ) -> Entity {
 	commands.spawn((
 		Name::new("Tealite"),
 		CollectableResource::Tealite,
+		ResourceDeposit::new(amount),
 		SceneBundle {
 			scene : game_assets.tealite.clone(),
 			transform,
@@ -129,12 +150,14 @@ pub fn tealite(
 
 pub fn purplite(
 	transform	: Transform,
+	amount		: usize,
 	game_assets	: &GameAssets,
 	commands	: &mut Commands
 ) -> Entity {
 	commands.spawn((
 		Name::new("Purplite"),
 		CollectableResource::Purplite,
+		ResourceDeposit::new(amount),
 		SceneBundle {
 			scene : game_assets.purplite.clone(),
 			transform,
@@ -150,14 +173,89 @@ pub fn purplite(
 	)).id()
 }
 
+/// Shards are stacked around the first one like petals, so every shard except the first is attached to the first shard
+/// and `parent_entity` has to be the first shard in that case. First shard is attached to player camera or npc itself
+pub fn shard(
+	resource_type	: CollectableResource,
+	shard_index		: usize,
+	collection_limit: usize,
+	held_by_player	: bool,
+	parent_entity	: Entity,
+	game_assets		: &GameAssets,
+	commands		: &mut Commands,
+) -> Entity {
+	let asset_to_spawn = match resource_type {
+		CollectableResource::Tealite => game_assets.tealite_shard.clone_weak(),
+		CollectableResource::Purplite => game_assets.purplite_shard.clone_weak(),
+	};
+
+	let mut rng = rand::thread_rng();
+
+	let shard_rotation = Quat::from_euler(
+		EulerRot::XYZ,
+		rng.gen_range(0.0 .. PI / 4.0),
+		rng.gen_range(0.0 .. TAU),
+		rng.gen_range(0.0 .. PI / 6.0)
+	);
+
+	let shard_translation = if shard_index > 0 {
+		let angle_x = rng.gen_range(0.0 .. PI / 4.0);
+		let angle_y = (TAU / collection_limit as f32) * shard_index as f32;
+		let angle_z = rng.gen_range(0.0 .. PI / 6.0);
+		let penta_rotation = Quat::from_euler(EulerRot::XYZ, angle_x, angle_y, angle_z);
+
+		penta_rotation.mul_vec3(Vec3::new(0.0, 0.0, 0.05))
+	} else if held_by_player {
+		Vec3::new(-0.20, 0.0, -0.7)
+	} else {
+		Vec3::new(0.0, 0.4, -0.5)
+	};
+
+	let shard_entity = commands.spawn((
+		Name::new("Shard"),
+		SceneBundle {
+			scene : asset_to_spawn,
+			transform : Transform {
+				translation : shard_translation,
+				rotation : shard_rotation,
+				..default()
+			},
+			..default()
+		},
+	)).id();
+
+	commands.entity(parent_entity).add_child(shard_entity);
+
+	shard_entity
+}
+
 pub fn base_building(
 	transform	: Transform,
+	faction		: Faction,
+	game_assets	: &GameAssets,
+	commands	: &mut Commands,
+) -> Entity {
+	let base_entity = base_building_site(transform, faction, game_assets, commands);
+
+	commands.entity(base_entity).insert((
+		BaseBuilding,
+		NpcSpawner,
+		ProductionQueue::default(),
+	));
+
+	base_entity
+}
+
+/// Base building that can't spawn npcs or accept resources yet, npcs can still walk up to its interact locators
+pub fn base_building_site(
+	transform	: Transform,
+	faction		: Faction,
 	game_assets	: &GameAssets,
 	commands	: &mut Commands,
 ) -> Entity {
 	commands.spawn((
 		Name::new("Base Building"),
-		BaseBuilding,
+		faction,
 		NpcInteractable,
 		SceneBundle {
 			scene : game_assets.base_building.clone(),
@@ -167,21 +265,50 @@ pub fn base_building(
 		RigidBody::Fixed,
 		AsyncSceneCollider::default(),
 		NavmeshObstacleContainer,
-		NpcSpawner,
 		LocatorsContainer,
 		Selectable { hover_only : false, indicator_offset : Vec3::Y * 6.5, ..default() },
 		Culling::default(),
 	)).id()
 }
 
+/// Collider is offset to stand on the ground, so mesh and collider go into a child
+pub fn obstacle(
+	transform	: Transform,
+	size		: Vec3,
+	meshes		: &mut Assets<Mesh>,
+	materials	: &mut Assets<StandardMaterial>,
+	commands	: &mut Commands,
+) -> Entity {
+	let mesh_entity = commands.spawn((
+		PbrBundle {
+			mesh : meshes.add(Cuboid::from_size(size)),
+			material : materials.add(Color::DARK_GRAY),
+			transform : Transform::from_translation(Vec3::Y * size.y / 2.0),
+			..default()
+		},
+		Collider::cuboid(size.x / 2.0, size.y / 2.0, size.z / 2.0),
+	)).id();
+
+	commands.spawn((
+		Name::new("Obstacle"),
+		SpatialBundle::from_transform(transform),
+		RigidBody::Fixed,
+		NavmeshObstacleContainer,
+	))
+	.add_child(mesh_entity)
+	.id()
+}
+
 pub fn drill(
 	transform		: &Transform,
 	owner_entity	: Option<Entity>,
+	faction			: Faction,
 	game_assets		: &GameAssets,
 	commands		: &mut Commands,
 ) -> Entity {
 	commands.spawn((
 		Name::new("Drill Miller Falls"),
+		faction,
 		SceneBundle {
 			scene : game_assets.drill_miller_falls.clone_weak(),
 			transform: *transform,
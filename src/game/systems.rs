@@ -7,6 +7,7 @@ use bevy :: {
 use bevy_rapier3d :: prelude :: *;
 use bevy_fps_controller :: controller :: *;
 use bevy_vector_shapes :: prelude :: *;
+use bevy_panorbit_camera :: PanOrbitCamera;
 
 use super :: *;
 
@@ -16,14 +17,45 @@ use crate :: ai :: {
 	PolyanyaResource,
 	Locator,
 	Occupied,
+	RallyPoint,
+	NpcTaskRally,
+	MovePath,
+	NpcTaskResourceCollection,
+	NpcTaskConstruction,
+	NpcTaskReturnCargo,
+	NpcTaskPatrol,
+	NpcTaskFollow,
+	HoldPosition,
+	NpcCommandQueue,
 };
 
-use crate :: resource_collection :: CollectedResources;
+use crate :: resource_collection :: FactionResources;
+
+use crate :: faction :: Faction;
+
+use crate :: units :: { UnitDefinitions, UnitType };
+
+use crate :: buildings :: { BuildingDefinitions, BuildingType, Placement };
+
+use crate :: map :: GroundBounds;
 
 use crate :: utils :: *;
 
 use std :: f32 :: consts :: PI;
 
+const BUILD_MENU_KEYS : [KeyCode; 9] = [
+	KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3,
+	KeyCode::Digit4, KeyCode::Digit5, KeyCode::Digit6,
+	KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+];
+
+const CONTROL_GROUP_KEYS : [KeyCode; 10] = [
+	KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3,
+	KeyCode::Digit4, KeyCode::Digit5, KeyCode::Digit6,
+	KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+	KeyCode::Digit0,
+];
+
 pub fn player_state_control(
 		main_entities		: Res<MainEntities>,
 		q_camera			: Query<&Camera>,
@@ -51,7 +83,7 @@ pub fn player_state_control(
 }
 
 pub fn player_input_control(
-		build_menu_state: ResMut<BuildMenuState>,
+		input_context	: Res<State<InputContext>>,
 		q_window		: Query<&Window, Changed<Window>>,
 	mut q_fps_controller: Query<&mut FpsController>,
 ) {
@@ -67,7 +99,7 @@ pub fn player_input_control(
 		}
 	}
 
-	if build_menu_state.active {
+	if *input_context.get() == InputContext::BuildMenu {
 		enable_movement = false;
 	}
 
@@ -104,18 +136,33 @@ pub fn toggle_orbit_camera(
 		key				: Res<ButtonInput<KeyCode>>,
 		main_entities	: Res<MainEntities>,
 	mut q_camera		: Query<&mut Camera>,
+	mut q_window		: Query<&mut Window>,
+	mut next_camera_mode: ResMut<NextState<CameraMode>>,
 	mut commands		: Commands,
 ) {
 	if key.pressed(KeyCode::ShiftLeft) && key.just_pressed(KeyCode::KeyO) {
 		{
 			let Ok(mut player_camera) = q_camera.get_mut(main_entities.player_camera) else { panic!("player camera is not present in game world!") };
 
+			// pan-orbit camera is pointed with cursor, first person camera is aimed with crosshair
+			let mut window = q_window.single_mut();
+
 			if player_camera.is_active {
 				player_camera.is_active = false;
 				commands.entity(main_entities.player).remove::<LogicalPlayer>();
+
+				window.cursor.grab_mode = CursorGrabMode::None;
+				window.cursor.visible = true;
+
+				next_camera_mode.set(CameraMode::Rts);
 			} else {
 				player_camera.is_active = true;
 				commands.entity(main_entities.player).insert(LogicalPlayer);
+
+				window.cursor.grab_mode = CursorGrabMode::Locked;
+				window.cursor.visible = false;
+
+				next_camera_mode.set(CameraMode::FirstPerson);
 			}
 		}
 
@@ -130,14 +177,84 @@ pub fn toggle_orbit_camera(
 	}
 }
 
+/// Top-down camera controls: WASD and cursor at window edge move camera over the map, zoom and pitch are kept within limits
+pub fn rts_camera_control(
+		key				: Res<ButtonInput<KeyCode>>,
+		time			: Res<Time>,
+		main_entities	: Res<MainEntities>,
+		ground			: Res<GroundBounds>,
+		q_window		: Query<&Window>,
+	mut q_orbit_camera	: Query<&mut PanOrbitCamera>,
+) {
+	let Ok(mut orbit_camera) = q_orbit_camera.get_mut(main_entities.orbit_camera) else { panic!("orbit camera has no PanOrbitCamera component!") };
+
+	let window = q_window.single();
+
+	// x is right and y is forward on screen
+	let mut direction = Vec2::ZERO;
+
+	if key.pressed(KeyCode::KeyW) { direction.y += 1.0 }
+	if key.pressed(KeyCode::KeyS) { direction.y -= 1.0 }
+	if key.pressed(KeyCode::KeyD) { direction.x += 1.0 }
+	if key.pressed(KeyCode::KeyA) { direction.x -= 1.0 }
+
+	if let Some(cursor) = window.cursor_position() {
+		if cursor.x < RTS_EDGE_SCROLL_MARGIN { direction.x -= 1.0 }
+		if cursor.x > window.width() - RTS_EDGE_SCROLL_MARGIN { direction.x += 1.0 }
+		if cursor.y < RTS_EDGE_SCROLL_MARGIN { direction.y += 1.0 }
+		if cursor.y > window.height() - RTS_EDGE_SCROLL_MARGIN { direction.y -= 1.0 }
+	}
+
+	if direction != Vec2::ZERO {
+		// moving along the ground in camera's yaw, farther camera moves faster
+		let rotation = Quat::from_rotation_y(orbit_camera.target_yaw);
+		let right = rotation * Vec3::X;
+		let forward = rotation * Vec3::NEG_Z;
+
+		let speed = RTS_PAN_SPEED * orbit_camera.target_radius.max(1.0);
+		let offset = (right * direction.x + forward * direction.y).normalize() * speed * time.delta_seconds();
+
+		let focus = orbit_camera.target_focus + offset;
+
+		orbit_camera.target_focus = Vec3::new(
+			focus.x.clamp(ground.min.x, ground.max.x),
+			focus.y,
+			focus.z.clamp(ground.min.y, ground.max.y),
+		);
+	}
+
+	orbit_camera.target_radius = orbit_camera.target_radius.clamp(RTS_ZOOM_MIN, RTS_ZOOM_MAX);
+	orbit_camera.target_pitch = orbit_camera.target_pitch.clamp(RTS_PITCH_MIN, RTS_PITCH_MAX);
+}
+
 pub fn camera_raypick(
 		rapier_context	: Res<RapierContext>,
 		q_parent		: Query<&Parent>,
-	mut q_camera_raypick: Query<(&mut Raypick, &GlobalTransform), With<Camera>>,
+		q_window		: Query<&Window>,
+		q_interaction	: Query<&Interaction>,
+	mut q_camera_raypick: Query<(&mut Raypick, &Camera, &GlobalTransform)>,
 ) {
-	for (mut raypick, camera_transform) in q_camera_raypick.iter_mut() {
+	let window = q_window.single();
+
+	let over_ui = cursor_over_ui(&q_interaction);
+
+	for (mut raypick, camera, camera_transform) in q_camera_raypick.iter_mut() {
 		raypick.entity = None;
 
+		if !camera.is_active { continue }
+
+		let (cast_pos, cast_dir, cast_len) = if raypick.from_cursor {
+			// clicks on ui must not go through it into the world
+			if over_ui { continue }
+
+			let Some(cursor) = window.cursor_position() else { continue };
+			let Some(ray) = camera.viewport_to_world(camera_transform, cursor) else { continue };
+
+			(ray.origin, *ray.direction, RTS_RAYPICK_LENGTH)
+		} else {
+			(camera_transform.translation(), camera_transform.forward(), 100.0) // meters
+		};
+
 		let raycast_callback = |hit_entity: Entity, intersection: RayIntersection| -> bool {
 			if raypick.to_ignore.contains(&hit_entity) { return true }
 
@@ -154,10 +271,6 @@ pub fn camera_raypick(
 			true
 		};
 
-		let cast_pos = camera_transform.translation();
-		let cast_dir = camera_transform.forward();
-		let cast_len = 100.0; // meters
-
 		rapier_context.intersections_with_ray(
 			cast_pos,
 			cast_dir,
@@ -169,16 +282,33 @@ pub fn camera_raypick(
 	}
 }
 
+/// Cursor is over ui element that handles clicks itself, like minimap
+fn cursor_over_ui(q_interaction: &Query<&Interaction>) -> bool {
+	q_interaction.iter().any(|interaction| *interaction != Interaction::None)
+}
+
 pub fn selectable_control(
 		mouse_button	: Res<ButtonInput<MouseButton>>,
 		key				: Res<ButtonInput<KeyCode>>,
+		time			: Res<Time>,
 		main_entities	: Res<MainEntities>,
 		q_raypick		: Query<&Raypick, Without<Selectable>>,
-		q_selectable	: Query<(Entity, &Selectable, Option<&Selected>)>,
+		q_camera		: Query<(&Camera, &GlobalTransform)>,
+		q_faction		: Query<&Faction>,
+		q_selectable	: Query<(Entity, &Selectable, Option<&Faction>, Option<&Selected>)>,
+		q_unit			: Query<(Entity, &UnitType, &Faction, &GlobalTransform)>,
+	mut selection_box	: ResMut<SelectionBox>,
 	mut commands		: Commands,
 ) {
+	let Ok((player_camera, player_camera_transform)) = q_camera.get(main_entities.player_camera) else { panic!("player camera is not present in game world!") };
+
+	// pan-orbit camera has its own selection with cursor, see selection_box_control
+	if !player_camera.is_active { return }
+
 	let Ok(camera_raypick) = q_raypick.get(main_entities.player_camera) else { panic!("player camera has no raypick!") };
 
+	let Ok(player_faction) = q_faction.get(main_entities.player) else { panic!("player entity has no Faction component!") };
+
 	let raypicked_entity_opt = camera_raypick.entity;
 
 	let just_pressed = mouse_button.just_pressed(MouseButton::Left);
@@ -187,9 +317,21 @@ pub fn selectable_control(
 	let remove = key.pressed(KeyCode::AltLeft);
 	let add = key.pressed(KeyCode::ShiftLeft);
 
-	for (selectable_entity, selectable, selected) in q_selectable.iter() {
+	if just_pressed && !remove {
+		if let Some(raypicked_entity) = raypicked_entity_opt {
+			if let Some(same_type) = double_click(raypicked_entity, time.elapsed_seconds(), player_faction, player_camera, player_camera_transform, &q_unit, &mut selection_box) {
+				select(&same_type, add, &q_selectable, &mut commands);
+				return;
+			}
+		}
+	}
+
+	for (selectable_entity, selectable, faction, selected) in q_selectable.iter() {
 		if selectable.hover_only { continue }
 
+		// other factions can only be hovered
+		if faction != Some(player_faction) { continue }
+
 		let selected = selected.is_some();
 
 		if let Some(raypicked_entity) = raypicked_entity_opt {
@@ -208,12 +350,305 @@ pub fn selectable_control(
 	}
 }
 
+/// Remembers clicked entity and if it's the second click on the same unit of player's faction in a short time
+/// returns all units of its type that are in view of the camera
+fn double_click(
+		clicked_entity	: Entity,
+		seconds			: f32,
+		player_faction	: &Faction,
+		camera			: &Camera,
+		camera_transform: &GlobalTransform,
+		q_unit			: &Query<(Entity, &UnitType, &Faction, &GlobalTransform)>,
+		selection_box	: &mut SelectionBox,
+) -> Option<Vec<Entity>> {
+	let last_click = selection_box.last_click.replace((seconds, clicked_entity));
+
+	let Some((last_seconds, last_entity)) = last_click else { return None };
+
+	if last_entity != clicked_entity || seconds - last_seconds > DOUBLE_CLICK_TIME { return None }
+
+	let Ok((_, unit_type, faction, _)) = q_unit.get(clicked_entity) else { return None };
+
+	if faction != player_faction { return None }
+
+	// third click starts over
+	selection_box.last_click = None;
+
+	let Some(viewport_size) = camera.logical_viewport_size() else { return None };
+
+	let viewport = Rect::from_corners(Vec2::ZERO, viewport_size);
+
+	Some(
+		q_unit.iter()
+			.filter(|(_, other_type, other_faction, _)| *other_type == unit_type && *other_faction == player_faction)
+			.filter(|(_, _, _, transform)| {
+				camera.world_to_viewport(camera_transform, transform.translation()).map_or(false, |position| viewport.contains(position))
+			})
+			.map(|(entity, _, _, _)| entity)
+			.collect()
+	)
+}
+
+/// Selects given entities, everything else gets deselected unless `add` is set
+fn select(
+		entities		: &[Entity],
+		add				: bool,
+		q_selectable	: &Query<(Entity, &Selectable, Option<&Faction>, Option<&Selected>)>,
+		commands		: &mut Commands,
+) {
+	if !add {
+		for (selectable_entity, _, _, selected) in q_selectable.iter() {
+			if selected.is_some() && !entities.contains(&selectable_entity) {
+				commands.entity(selectable_entity).remove::<Selected>();
+			}
+		}
+	}
+
+	for entity in entities.iter() {
+		commands.entity(*entity).insert(Selected);
+	}
+}
+
+/// Selection with cursor in pan-orbit camera: click picks selectable closest to cursor, drag selects everything inside the marquee.
+/// Marquee picks only units if there are any inside, buildings otherwise. Shift adds to selection, Alt removes from it
+pub fn selection_box_control(
+		mouse_button	: Res<ButtonInput<MouseButton>>,
+		key				: Res<ButtonInput<KeyCode>>,
+		time			: Res<Time>,
+		main_entities	: Res<MainEntities>,
+		input_context	: Res<State<InputContext>>,
+		q_window		: Query<&Window>,
+		q_camera		: Query<(&Camera, &GlobalTransform)>,
+		q_faction		: Query<&Faction>,
+		q_selectable	: Query<(Entity, &Selectable, Option<&Faction>, Option<&Selected>)>,
+		q_transform		: Query<&GlobalTransform>,
+		q_unit			: Query<(Entity, &UnitType, &Faction, &GlobalTransform)>,
+		q_raypick		: Query<&Raypick>,
+		q_interaction	: Query<&Interaction>,
+	mut selection_box	: ResMut<SelectionBox>,
+	mut commands		: Commands,
+) {
+	let Ok((orbit_camera, orbit_camera_transform)) = q_camera.get(main_entities.orbit_camera) else { panic!("orbit camera is not present in game world!") };
+
+	// left click places buildings while placement is active
+	if !orbit_camera.is_active || *input_context.get() == InputContext::Placement {
+		selection_box.start = None;
+		return;
+	}
+
+	let window = q_window.single();
+
+	let Some(cursor) = window.cursor_position() else { return };
+
+	if mouse_button.just_pressed(MouseButton::Left) && !cursor_over_ui(&q_interaction) {
+		selection_box.start = Some(cursor);
+	}
+
+	let Some(start) = selection_box.start else { return };
+
+	if !mouse_button.just_released(MouseButton::Left) { return }
+
+	selection_box.start = None;
+
+	let Ok(player_faction) = q_faction.get(main_entities.player) else { panic!("player entity has no Faction component!") };
+
+	let remove = key.pressed(KeyCode::AltLeft);
+	let add = key.pressed(KeyCode::ShiftLeft);
+
+	let on_screen = q_selectable.iter()
+		.filter(|(_, selectable, _, _)| !selectable.hover_only)
+		.filter_map(|(entity, selectable, faction, _)| {
+			let transform = q_transform.get(entity).ok()?;
+			let position = orbit_camera.world_to_viewport(orbit_camera_transform, transform.translation() + selectable.indicator_offset * 0.5)?;
+
+			Some(OnScreen { entity, position, faction : faction.copied(), unit : q_unit.contains(entity) })
+		})
+		.collect::<Vec<OnScreen>>();
+
+	let picked = if start.distance(cursor) < SELECTION_DRAG_THRESHOLD {
+		let Ok(raypick) = q_raypick.get(main_entities.orbit_camera) else { panic!("orbit camera has no raypick!") };
+
+		// own entity under cursor, otherwise the one closest to it so small units are easier to click
+		let under_cursor = raypick.entity.filter(|raypicked_entity| on_screen.iter()
+			.any(|selectable| selectable.entity == *raypicked_entity && selectable.faction.as_ref() == Some(player_faction))
+		);
+
+		match under_cursor.or_else(|| nearest_on_screen(cursor, player_faction, &on_screen)) {
+			Some(entity) if !remove => double_click(entity, time.elapsed_seconds(), player_faction, orbit_camera, orbit_camera_transform, &q_unit, &mut selection_box)
+				.unwrap_or(vec![entity]),
+			Some(entity) => vec![entity],
+			None => Vec::new(),
+		}
+	} else {
+		inside_marquee(Rect::from_corners(start, cursor), player_faction, &on_screen)
+	};
+
+	if remove {
+		for entity in picked.iter() {
+			commands.entity(*entity).remove::<Selected>();
+		}
+	} else {
+		select(&picked, add, &q_selectable, &mut commands);
+	}
+}
+
+/// Player's own selectable projected closest to cursor, if it's within `SELECTION_CLICK_RADIUS`
+pub fn nearest_on_screen(
+		cursor			: Vec2,
+		player_faction	: &Faction,
+		on_screen		: &[OnScreen],
+) -> Option<Entity> {
+	on_screen.iter()
+		.filter(|selectable| selectable.faction.as_ref() == Some(player_faction))
+		.map(|selectable| (selectable.entity, selectable.position.distance(cursor)))
+		.filter(|(_, distance)| *distance < SELECTION_CLICK_RADIUS)
+		.min_by(|(_, a), (_, b)| a.total_cmp(b))
+		.map(|(entity, _)| entity)
+}
+
+/// Player's own selectables inside the marquee: units if there are any among them, buildings otherwise
+pub fn inside_marquee(
+		marquee			: Rect,
+		player_faction	: &Faction,
+		on_screen		: &[OnScreen],
+) -> Vec<Entity> {
+	let inside = on_screen.iter()
+		.filter(|selectable| selectable.faction.as_ref() == Some(player_faction) && marquee.contains(selectable.position));
+
+	let units_inside = inside.clone().any(|selectable| selectable.unit);
+
+	inside
+		.filter(|selectable| selectable.unit == units_inside)
+		.map(|selectable| selectable.entity)
+		.collect()
+}
+
+pub fn selection_box_draw(
+		main_entities	: Res<MainEntities>,
+		selection_box	: Res<SelectionBox>,
+		q_window		: Query<&Window>,
+	mut q_style			: Query<&mut Style>,
+	mut commands		: Commands,
+) {
+	let window = q_window.single();
+
+	let (Some(start), Some(cursor)) = (selection_box.start, window.cursor_position()) else {
+		commands.entity(main_entities.selection_box).insert(Visibility::Hidden);
+		return;
+	};
+
+	let Ok(mut style) = q_style.get_mut(main_entities.selection_box) else { panic!("MainEntities::selection_box points to non existing entity! There is no selection box ui!") };
+
+	let marquee = Rect::from_corners(start, cursor);
+
+	style.left		= Val::Px(marquee.min.x);
+	style.top		= Val::Px(marquee.min.y);
+	style.width		= Val::Px(marquee.width());
+	style.height	= Val::Px(marquee.height());
+
+	commands.entity(main_entities.selection_box).insert(Visibility::Visible);
+}
+
+/// Selects every svin of player's faction that has nothing to do, with Shift they are added to selection
+pub fn select_idle_svins(
+		key				: Res<ButtonInput<KeyCode>>,
+		main_entities	: Res<MainEntities>,
+		q_faction		: Query<&Faction>,
+		q_selectable	: Query<(Entity, &Selectable, Option<&Faction>, Option<&Selected>)>,
+		q_idle			: Query<(Entity, &UnitType, &Faction, Option<&NpcCommandQueue>), (
+			Without<NpcTaskRally>,
+			Without<MovePath>,
+			Without<NpcTaskResourceCollection>,
+			Without<NpcTaskConstruction>,
+			Without<NpcTaskReturnCargo>,
+			Without<NpcTaskPatrol>,
+			Without<NpcTaskFollow>,
+			Without<HoldPosition>
+		)>,
+	mut commands		: Commands,
+) {
+	if !key.just_pressed(KeyCode::KeyI) { return }
+
+	let Ok(player_faction) = q_faction.get(main_entities.player) else { panic!("player entity has no Faction component!") };
+
+	let idle_svins = q_idle.iter()
+		.filter(|(_, unit_type, faction, command_queue)| **unit_type == UnitType::Svin && *faction == player_faction && command_queue.map_or(true, |command_queue| command_queue.is_empty()))
+		.map(|(entity, _, _, _)| entity)
+		.collect::<Vec<Entity>>();
+
+	select(&idle_svins, key.pressed(KeyCode::ShiftLeft), &q_selectable, &mut commands);
+}
+
+/// Ctrl + digit stores selected entities of player's faction as a control group, digit selects the group again and
+/// Shift + digit adds it to selection. Pressing digit twice quickly centers pan-orbit camera on the group
+pub fn control_groups_control(
+		key				: Res<ButtonInput<KeyCode>>,
+		time			: Res<Time>,
+		main_entities	: Res<MainEntities>,
+		q_faction		: Query<&Faction>,
+		q_selectable	: Query<(Entity, &Selectable, Option<&Faction>, Option<&Selected>)>,
+		q_transform		: Query<&GlobalTransform>,
+	mut q_orbit_camera	: Query<&mut PanOrbitCamera>,
+	mut control_groups	: ResMut<ControlGroups>,
+	mut commands		: Commands,
+) {
+	let Some(group_index) = CONTROL_GROUP_KEYS.iter().position(|group_key| key.just_pressed(*group_key)) else { return };
+
+	let Ok(player_faction) = q_faction.get(main_entities.player) else { panic!("player entity has no Faction component!") };
+
+	if key.pressed(KeyCode::ControlLeft) {
+		control_groups.groups[group_index] = q_selectable.iter()
+			.filter(|(_, _, faction, selected)| selected.is_some() && *faction == Some(player_faction))
+			.map(|(entity, _, _, _)| entity)
+			.collect();
+
+		control_groups.last_recall = None;
+		return;
+	}
+
+	if control_groups.groups[group_index].is_empty() { return }
+
+	let seconds = time.elapsed_seconds();
+
+	let double_tap = matches!(control_groups.last_recall, Some((last_seconds, last_index)) if last_index == group_index && seconds - last_seconds < DOUBLE_TAP_TIME);
+
+	control_groups.last_recall = Some((seconds, group_index));
+
+	let group = &control_groups.groups[group_index];
+
+	select(group, key.pressed(KeyCode::ShiftLeft), &q_selectable, &mut commands);
+
+	if !double_tap { return }
+
+	let positions = group.iter().filter_map(|entity| q_transform.get(*entity).ok()).map(|transform| transform.translation()).collect::<Vec<Vec3>>();
+
+	if positions.is_empty() { return }
+
+	let center = positions.iter().sum::<Vec3>() / positions.len() as f32;
+
+	let Ok(mut orbit_camera) = q_orbit_camera.get_mut(main_entities.orbit_camera) else { panic!("orbit camera has no PanOrbitCamera component!") };
+
+	orbit_camera.target_focus = center;
+}
+
+/// Drops despawned entities from control groups
+pub fn control_groups_prune(
+	mut removed_selectables	: RemovedComponents<Selectable>,
+	mut control_groups		: ResMut<ControlGroups>,
+) {
+	for entity in removed_selectables.read() {
+		control_groups.remove(entity);
+	}
+}
+
 pub fn selectable_draw(
 		mouse_button	: Res<ButtonInput<MouseButton>>,
 		time			: Res<Time>,
 		main_entities	: Res<MainEntities>,
+		camera_mode		: Res<State<CameraMode>>,
 		q_raypick		: Query<(&Raypick, &GlobalTransform), Without<Selectable>>,
-		q_selectable	: Query<(Entity, &GlobalTransform, &Selectable, Option<&Selected>)>,
+		q_faction		: Query<&Faction>,
+		q_selectable	: Query<(Entity, &GlobalTransform, &Selectable, Option<&Faction>, Option<&Selected>)>,
 	mut painter			: ShapePainter,
 ) {
 	fn draw_bubble(
@@ -290,7 +725,9 @@ pub fn selectable_draw(
 		painter.arc(0.6, -PI / 2.0, PI / 2.0);
 	}
 
-	let Ok((camera_raypick, camera_transform)) = q_raypick.get(main_entities.player_camera) else { panic!("player camera has no raypick!") };
+	let Ok((camera_raypick, camera_transform)) = q_raypick.get(main_entities.raypick_camera(*camera_mode.get())) else { panic!("camera has no raypick!") };
+
+	let Ok(player_faction) = q_faction.get(main_entities.player) else { panic!("player entity has no Faction component!") };
 
 	let seconds = time.elapsed_seconds();
 
@@ -299,13 +736,15 @@ pub fn selectable_draw(
 
 	// hover
 	if let Some(raypicked_entity) = camera_raypick.entity {
-		if let Ok((_, selectable_transform, selectable, selected)) = q_selectable.get(raypicked_entity) {
+		if let Ok((_, selectable_transform, selectable, faction, selected)) = q_selectable.get(raypicked_entity) {
 			let color = if selectable.hover_only {
 				if pressed {
 					Color::hex("4c79ff").unwrap()
 				} else {
 					Color::hex("7df0f8").unwrap()
 				}
+			} else if let Some(faction) = faction.filter(|faction| *faction != player_faction) {
+				faction.color()
 			} else {
 				if selected.is_some() {
 					Color::hex("26a8ff").unwrap()
@@ -318,7 +757,7 @@ pub fn selectable_draw(
 	}
 
 	// selectable
-	for (selectable_entity, selectable_transform, selectable, selected) in q_selectable.iter() {
+	for (selectable_entity, selectable_transform, selectable, faction, selected) in q_selectable.iter() {
 		if selectable.hover_only { continue }
 
 		let selected = selected.is_some();
@@ -330,82 +769,164 @@ pub fn selectable_draw(
 		};
 
 		if selected && !hovered_entity {
-			draw_selection(&mut painter, seconds, selectable_transform, selectable.indicator_offset, camera_transform, Color::GREEN);
+			let color = faction.map_or(Color::GREEN, |faction| faction.color());
+
+			draw_selection(&mut painter, seconds, selectable_transform, selectable.indicator_offset, camera_transform, color);
 		}
 	}
 }
 
+/// `b` opens build menu if nothing but base buildings is selected and closes it, it also gets closed once something else is selected
+pub fn build_menu_toggle(
+		key_input		: Res<ButtonInput<KeyCode>>,
+		input_context	: Res<State<InputContext>>,
+		q_selected_other: Query<Entity, (With<Selected>, Without<BaseBuilding>)>,
+	mut next_context	: ResMut<NextState<InputContext>>,
+) {
+	let menu_allowed = q_selected_other.is_empty();
+	let menu_open = *input_context.get() == InputContext::BuildMenu;
+
+	if key_input.just_pressed(KeyCode::KeyB) && (menu_open || menu_allowed) {
+		next_context.set(if menu_open { InputContext::Gameplay } else { InputContext::BuildMenu });
+	} else if menu_open && !menu_allowed {
+		next_context.set(InputContext::Gameplay);
+	}
+}
+
+/// Runs only while build menu is open, digit keys are control groups otherwise
 pub fn build_menu_control(
 		key_input		: Res<ButtonInput<KeyCode>>,
-		rapier_context	: Res<RapierContext>,
-		polyanya		: Res<PolyanyaResource>,
-		game_assets		: Res<GameAssets>,
-	mut	collected_resources	: ResMut<CollectedResources>,
+		main_entities	: Res<MainEntities>,
+		unit_definitions: Res<UnitDefinitions>,
+		building_definitions: Res<BuildingDefinitions>,
+		q_faction		: Query<&Faction>,
+	mut	faction_resources	: ResMut<FactionResources>,
 	mut build_menu_state: ResMut<BuildMenuState>,
-		q_selected_base	: Query<Entity, (With<Selected>, With<BaseBuilding>)>,
-		q_selected_other: Query<Entity, (With<Selected>, Without<BaseBuilding>)>,
-		q_children		: Query<&Children>,
-		q_locator		: Query<(&Locator, &GlobalTransform, Option<&Occupied>)>,
-	mut commands		: Commands
+	mut placement		: ResMut<Placement>,
+	mut next_context	: ResMut<NextState<InputContext>>,
+	mut q_selected_base	: Query<&mut ProductionQueue, (With<Selected>, With<BaseBuilding>)>,
 ) {
-	let menu_allowed = !q_selected_base.is_empty() && q_selected_other.is_empty();
+	let base_selected = !q_selected_base.is_empty();
+
+	let units = UnitType::ALL.into_iter().filter(|_| base_selected).map(BuildMenuEntry::Unit);
+	let buildings = BuildingType::ALL.into_iter().map(BuildMenuEntry::Building);
+
+	let entries = units.chain(buildings).collect::<Vec<BuildMenuEntry>>();
 
-	// toggle text ui visibilitiy
-	if key_input.just_pressed(KeyCode::KeyB) {
-		build_menu_state.active ^= menu_allowed;
+	if build_menu_state.entries != entries {
+		build_menu_state.entries = entries;
 	}
 
-	if !menu_allowed && build_menu_state.active {
-		build_menu_state.active = false;
+	let Ok(player_faction) = q_faction.get(main_entities.player) else { panic!("player entity has no Faction component!") };
+
+	let collected_resources = faction_resources.get_mut(*player_faction);
+
+	// cancel last queued unit, what was paid for it is returned in full even if it was being built already
+	if key_input.just_pressed(KeyCode::Backspace) {
+		for mut production_queue in q_selected_base.iter_mut() {
+			let Some(item) = production_queue.items.pop() else { continue };
+
+			collected_resources.refund(&item.paid);
+		}
 	}
 
-	if !menu_allowed { return }
+	let Some(entry) = BUILD_MENU_KEYS.iter()
+		.zip(build_menu_state.entries.iter())
+		.find(|(key, _)| key_input.just_pressed(**key))
+		.map(|(_, entry)| *entry)
+	else { return };
 
-	if key_input.just_pressed(KeyCode::Digit1) {
-		if !collected_resources.is_enough(&SVIN_PRICE) { println!("NOT ENOUGH RESOURCES COLLECTED TO BUILD SVIN!"); }
+	let unit_type = match entry {
+		BuildMenuEntry::Unit(unit_type) => unit_type,
+		BuildMenuEntry::Building(building_type) => {
+			let definition = building_definitions.get(building_type);
 
-		for selected_base_entity in q_selected_base.iter() {
-			if !collected_resources.is_enough(&SVIN_PRICE) { break }
+			if !collected_resources.is_enough(&definition.cost) {
+				println!("NOT ENOUGH RESOURCES COLLECTED TO BUILD {}!", definition.name.to_uppercase());
+				return
+			}
 
-			collected_resources.deduct(&SVIN_PRICE);
+			// resources are deducted once building is actually placed
+			placement.building = Some(building_type);
+			next_context.set(InputContext::Placement);
 
-			for locator_entity in q_children.iter_descendants(selected_base_entity) {
-				let Ok((locator, locator_transform_global, occupied)) = q_locator.get(locator_entity) else { continue };
+			return
+		}
+	};
 
-				if *locator != Locator::Spawn || occupied.is_some() { continue }
+	let definition = unit_definitions.get(unit_type);
+	let price = &definition.cost;
 
-				let locator_transform = locator_transform_global.compute_transform();
+	// every selected base queues one unit
+	for mut production_queue in q_selected_base.iter_mut() {
+		if production_queue.is_full() {
+			println!("PRODUCTION QUEUE IS FULL, CAN'T QUEUE MORE THAN {} UNITS!", PRODUCTION_QUEUE_LIMIT);
+			continue;
+		}
 
-				if spawn::svin(
-					&locator_transform,
-					SVIN_CARRYING_CAPACITY,
-					&game_assets,
-					true, // with_drill
-					Some(selected_base_entity),
-					Some(&polyanya.mesh),
-					Some(&rapier_context),
-					&mut commands
-				).is_some()
-				{
-					break;
-				}
-			}
+		if !collected_resources.is_enough(price) {
+			println!("NOT ENOUGH RESOURCES COLLECTED TO BUILD {}!", definition.name.to_uppercase());
+			break;
 		}
+
+		collected_resources.deduct(price);
+
+		production_queue.push(unit_type, definition.build_time, *price);
 	}
 }
 
 pub fn build_menu_draw(
 		main_entities	: Res<MainEntities>,
-		build_menu_state: ResMut<BuildMenuState>,
+		unit_definitions: Res<UnitDefinitions>,
+		building_definitions: Res<BuildingDefinitions>,
+		build_menu_state: Res<BuildMenuState>,
+		input_context	: Res<State<InputContext>>,
+		placement		: Res<Placement>,
+		q_selected_base	: Query<&ProductionQueue, (With<Selected>, With<BaseBuilding>)>,
     mut text_ui			: Query<&mut Text>,
 	mut commands		: Commands
 ) {
 	let Ok(mut text_ui) = text_ui.get_mut(main_entities.build_menu) else { panic!("MainEntities::text_ui points to non existing entity! There is no text ui!") };
-	if build_menu_state.active {
-		text_ui.sections[0].value = concat!(
-			"[Build Menu]\n",
-			"- [1] Svin: 15 purplite\n"
-		).into();
+	if let (InputContext::Placement, Some(building_type)) = (input_context.get(), placement.building) {
+		text_ui.sections[0].value = format!(
+			"[Placing {}]\n- [LMB] place\n- [Shift + LMB] place and continue\n- [R] rotate\n- [RMB] cancel\n",
+			building_definitions.get(building_type).name
+		);
+
+		commands.entity(main_entities.build_menu).insert(Visibility::Visible);
+	} else if *input_context.get() == InputContext::BuildMenu {
+		let mut menu_text = String::from("[Build Menu]\n");
+
+		for (i, entry) in build_menu_state.entries.iter().enumerate() {
+			let (name, cost) = match entry {
+				BuildMenuEntry::Unit(unit_type) => {
+					let definition = unit_definitions.get(*unit_type);
+					(&definition.name, definition.cost)
+				},
+				BuildMenuEntry::Building(building_type) => {
+					let definition = building_definitions.get(*building_type);
+					(&definition.name, definition.cost)
+				},
+			};
+
+			menu_text += &format!("- [{}] {}: {}\n", i + 1, name, cost);
+		}
+
+		for production_queue in q_selected_base.iter() {
+			menu_text += &format!("\n[Production Queue {}/{}] [Backspace] cancel last\n", production_queue.items.len(), PRODUCTION_QUEUE_LIMIT);
+
+			for item in production_queue.items.iter() {
+				let name = &unit_definitions.get(item.unit_type).name;
+
+				if item.remaining > 0.0 {
+					menu_text += &format!("- {}: {:.1}s\n", name, item.remaining);
+				} else {
+					menu_text += &format!("- {}: waiting for free spawn point\n", name);
+				}
+			}
+		}
+
+		text_ui.sections[0].value = menu_text;
 
 		commands.entity(main_entities.build_menu).insert(Visibility::Visible);
 	} else {
@@ -413,6 +934,57 @@ pub fn build_menu_draw(
 	}
 }
 
+/// Builds the first unit in every queue, a finished unit stays in queue until one of spawn locators is free
+pub fn production_control(
+		time			: Res<Time>,
+		rapier_context	: Res<RapierContext>,
+		polyanya		: Res<PolyanyaResource>,
+		game_assets		: Res<GameAssets>,
+		unit_definitions: Res<UnitDefinitions>,
+		q_children		: Query<&Children>,
+		q_locator		: Query<(&Locator, &GlobalTransform, Option<&Occupied>)>,
+	mut q_production	: Query<(Entity, &Faction, &mut ProductionQueue, Option<&RallyPoint>), With<BaseBuilding>>,
+	mut commands		: Commands
+) {
+	for (base_entity, faction, mut production_queue, rally_point) in q_production.iter_mut() {
+		let Some(item) = production_queue.items.first_mut() else { continue };
+
+		if item.remaining > 0.0 {
+			item.remaining = (item.remaining - time.delta_seconds()).max(0.0);
+			continue;
+		}
+
+		let unit_type = item.unit_type;
+
+		for locator_entity in q_children.iter_descendants(base_entity) {
+			let Ok((locator, locator_transform_global, occupied)) = q_locator.get(locator_entity) else { continue };
+
+			if *locator != Locator::Spawn || occupied.is_some() { continue }
+
+			let locator_transform = locator_transform_global.compute_transform();
+
+			let Some((unit_entity, _)) = spawn::unit(
+				&locator_transform,
+				unit_type,
+				unit_definitions.get(unit_type),
+				*faction,
+				&game_assets,
+				Some(base_entity),
+				Some(&polyanya),
+				Some(&rapier_context),
+				&mut commands
+			) else { continue };
+
+			if let Some(rally_point) = rally_point {
+				commands.entity(unit_entity).insert(NpcTaskRally(*rally_point));
+			}
+
+			production_queue.items.remove(0);
+			break;
+		}
+	}
+}
+
 pub fn culling_control(
 		main_entities			: Res<MainEntities>,
 		q_camera				: Query<(&GlobalTransform, &Camera), Without<Culling>>,
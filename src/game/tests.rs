@@ -0,0 +1,325 @@
+use bevy :: {
+	prelude :: *,
+	ecs :: system :: RunSystemOnce,
+	scene :: ron,
+};
+
+use super :: {
+	systems,
+	BaseBuilding,
+	BatchOfResources,
+	BuildMenuState,
+	ControlGroups,
+	InputContext,
+	MainEntities,
+	OnScreen,
+	ProductionQueue,
+	Selectable,
+	Selected,
+};
+
+use crate :: test_support :: TestApp;
+
+use crate :: ai :: {
+	HoldPosition,
+	Locator,
+	MovePath,
+	NpcCommand,
+	NpcCommandQueue,
+	NpcTaskRally,
+	NpcTaskResourceCollection,
+	RallyPoint,
+};
+
+use crate :: faction :: Faction;
+
+use crate :: units :: { UnitDefinitions, UnitType };
+
+use crate :: buildings :: { BuildingDefinitions, Placement };
+
+use crate :: resource_collection :: { CollectableResource, CollectedResources, FactionResources };
+
+#[test]
+fn queued_unit_waits_for_build_time_and_free_spawn_locator() {
+	let mut test_app = TestApp::new();
+
+	let base_entity = test_app.base();
+
+	let build_time = test_app.unit_definitions().get(UnitType::Scout).build_time;
+
+	let blockers = test_app.locators(base_entity, Locator::Spawn).iter()
+		.map(|(_, locator_transform)| test_app.spawn_blocker(locator_transform.translation()))
+		.collect::<Vec<Entity>>();
+
+	// let physics pick up blockers
+	test_app.step(2);
+
+	let Some(mut production_queue) = test_app.app.world.get_mut::<ProductionQueue>(base_entity) else { panic!("base building has no production queue!") };
+	production_queue.push(UnitType::Scout, build_time, BatchOfResources::default());
+
+	test_app.step_seconds(build_time / 2.0);
+
+	let production_queue = test_app.app.world.get::<ProductionQueue>(base_entity).unwrap();
+	assert!(production_queue.items[0].remaining > 0.0, "scout was built faster than its build time");
+
+	test_app.step_seconds(build_time);
+
+	assert!(test_app.svins().is_empty(), "scout was spawned on a blocked locator");
+
+	let production_queue = test_app.app.world.get::<ProductionQueue>(base_entity).unwrap();
+	assert_eq!(production_queue.items.len(), 1, "finished scout was dropped from queue while locators were blocked");
+	assert_eq!(production_queue.items[0].remaining, 0.0);
+
+	for blocker_entity in blockers {
+		test_app.despawn(blocker_entity);
+	}
+
+	let spawned = test_app.step_until(5.0, |world| world.query::<&UnitType>().iter(world).count() == 1);
+	assert!(spawned, "scout wasn't spawned after spawn locators got free");
+
+	let scout_entity = test_app.svins()[0];
+	assert_eq!(test_app.app.world.get::<UnitType>(scout_entity), Some(&UnitType::Scout));
+
+	assert!(test_app.app.world.get::<ProductionQueue>(base_entity).unwrap().items.is_empty());
+}
+
+#[test]
+fn unit_produced_with_rally_point_on_deposit_starts_mining() {
+	let mut test_app = TestApp::new();
+
+	let base_entity = test_app.base();
+	let tealite_entity = test_app.deposit(CollectableResource::Tealite);
+
+	let deposit_position = test_app.app.world.get::<GlobalTransform>(tealite_entity).unwrap().translation();
+
+	test_app.app.world.entity_mut(base_entity).insert(RallyPoint {
+		position		: deposit_position,
+		target_entity	: Some(tealite_entity),
+	});
+
+	let Some(mut production_queue) = test_app.app.world.get_mut::<ProductionQueue>(base_entity) else { panic!("base building has no production queue!") };
+	production_queue.push(UnitType::Svin, 0.0, BatchOfResources::default());
+
+	let spawned = test_app.step_until(5.0, |world| world.query_filtered::<(), With<NpcTaskResourceCollection>>().iter(world).count() == 1);
+	assert!(spawned, "svin produced with rally point on deposit didn't start mining");
+
+	let svin_entity = test_app.svins()[0];
+
+	let Some(task) = test_app.task(svin_entity) else { unreachable!() };
+	assert_eq!(task.resource_entity, Some(tealite_entity));
+
+	assert!(test_app.app.world.get::<NpcTaskRally>(svin_entity).is_none());
+	assert!(test_app.app.world.get::<MovePath>(svin_entity).is_some(), "svin has no path to deposit");
+}
+
+/// World with just enough for selection systems: player of the given faction and keys pressed this frame
+fn selection_world(player_faction: Faction, pressed: &[KeyCode]) -> World {
+	let mut world = World::new();
+
+	insert_player(&mut world, player_faction, pressed);
+
+	world
+}
+
+fn insert_player(world: &mut World, player_faction: Faction, pressed: &[KeyCode]) {
+	let mut key = ButtonInput::<KeyCode>::default();
+	for key_code in pressed.iter() {
+		key.press(*key_code);
+	}
+	world.insert_resource(key);
+
+	let player = world.spawn(player_faction).id();
+
+	world.insert_resource(MainEntities {
+		player_camera	: world.spawn_empty().id(),
+		ui_camera		: world.spawn_empty().id(),
+		orbit_camera	: world.spawn_empty().id(),
+		player,
+		player_handheld	: world.spawn_empty().id(),
+		build_menu		: world.spawn_empty().id(),
+		selection_box	: world.spawn_empty().id(),
+	});
+}
+
+#[test]
+fn idle_svin_hotkey_selects_only_own_svins_with_nothing_to_do() {
+	let mut world = selection_world(Faction::Blue, &[KeyCode::KeyI]);
+
+	let unit = |world: &mut World, unit_type: UnitType, faction: Faction| world.spawn((unit_type, faction, Selectable::default())).id();
+
+	let idle_entity = unit(&mut world, UnitType::Svin, Faction::Blue);
+	let idle_without_queue_entity = unit(&mut world, UnitType::Svin, Faction::Blue);
+	world.entity_mut(idle_entity).insert(NpcCommandQueue::default());
+
+	let mining_entity = unit(&mut world, UnitType::Svin, Faction::Blue);
+	world.entity_mut(mining_entity).insert((NpcTaskResourceCollection::default(), NpcCommandQueue::default()));
+
+	let queued_entity = unit(&mut world, UnitType::Svin, Faction::Blue);
+	world.entity_mut(queued_entity).insert(NpcCommandQueue([NpcCommand::Move(Vec3::ZERO)].into()));
+
+	let holding_entity = unit(&mut world, UnitType::Svin, Faction::Blue);
+	world.entity_mut(holding_entity).insert(HoldPosition);
+
+	let scout_entity = unit(&mut world, UnitType::Scout, Faction::Blue);
+	let enemy_entity = unit(&mut world, UnitType::Svin, Faction::Red);
+
+	// without shift the rest of selection is dropped
+	let selected_base_entity = world.spawn((Faction::Blue, Selectable::default(), Selected)).id();
+
+	world.run_system_once(systems::select_idle_svins);
+
+	let selected = |entity: Entity| world.get::<Selected>(entity).is_some();
+
+	assert!(selected(idle_entity));
+	assert!(selected(idle_without_queue_entity));
+
+	assert!(!selected(mining_entity), "svin with a task is not idle");
+	assert!(!selected(queued_entity), "svin with queued commands is not idle");
+	assert!(!selected(holding_entity), "svin holding position is not idle");
+	assert!(!selected(scout_entity), "only svins are selected");
+	assert!(!selected(enemy_entity), "units of other factions can't be selected");
+	assert!(!selected(selected_base_entity), "previous selection should be dropped without shift");
+}
+
+#[test]
+fn idle_svin_hotkey_with_shift_keeps_selection() {
+	let mut world = selection_world(Faction::Blue, &[KeyCode::KeyI, KeyCode::ShiftLeft]);
+
+	let idle_entity = world.spawn((UnitType::Svin, Faction::Blue, Selectable::default())).id();
+	let selected_base_entity = world.spawn((Faction::Blue, Selectable::default(), Selected)).id();
+
+	world.run_system_once(systems::select_idle_svins);
+
+	assert!(world.get::<Selected>(idle_entity).is_some());
+	assert!(world.get::<Selected>(selected_base_entity).is_some());
+}
+
+#[test]
+fn box_selection_picks_only_player_faction() {
+	let mut world = World::new();
+
+	let [own_unit, enemy_unit, own_building, neutral, far_unit, enemy_building, far_enemy_unit, far_own_building] = [(); 8].map(|_| world.spawn_empty().id());
+
+	let on_screen = [
+		(own_unit,			Vec2::new(10.0, 10.0),		Some(Faction::Blue),	true),
+		(enemy_unit,		Vec2::new(20.0, 20.0),		Some(Faction::Red),		true),
+		(own_building,		Vec2::new(30.0, 30.0),		Some(Faction::Blue),	false),
+		(neutral,			Vec2::new(40.0, 40.0),		None,					false),
+		(far_unit,			Vec2::new(500.0, 500.0),	Some(Faction::Blue),	true),
+		(enemy_building,	Vec2::new(220.0, 220.0),	Some(Faction::Red),		false),
+		(far_enemy_unit,	Vec2::new(210.0, 210.0),	Some(Faction::Red),		true),
+		(far_own_building,	Vec2::new(230.0, 230.0),	Some(Faction::Blue),	false),
+	].map(|(entity, position, faction, unit)| OnScreen { entity, position, faction, unit });
+
+	// own units go before buildings, units of other factions are ignored
+	let picked = systems::inside_marquee(Rect::new(0.0, 0.0, 100.0, 100.0), &Faction::Blue, &on_screen);
+	assert_eq!(picked, vec![own_unit]);
+
+	// enemy unit inside doesn't stop player's building from being picked
+	let picked = systems::inside_marquee(Rect::new(200.0, 200.0, 300.0, 300.0), &Faction::Blue, &on_screen);
+	assert_eq!(picked, vec![far_own_building]);
+
+	// same boxes for another faction
+	let picked = systems::inside_marquee(Rect::new(0.0, 0.0, 300.0, 300.0), &Faction::Red, &on_screen);
+	assert_eq!(picked, vec![enemy_unit, far_enemy_unit]);
+
+	// click right at enemy unit picks own one a bit further away
+	assert_eq!(systems::nearest_on_screen(Vec2::new(18.0, 18.0), &Faction::Blue, &on_screen), Some(own_unit));
+	assert_eq!(systems::nearest_on_screen(Vec2::new(400.0, 100.0), &Faction::Blue, &on_screen), None);
+}
+
+#[test]
+fn despawned_entities_are_dropped_from_control_groups() {
+	let mut test_app = TestApp::new();
+
+	let kept_entity = test_app.app.world.spawn(Selectable::default()).id();
+	let despawned_entity = test_app.app.world.spawn(Selectable::default()).id();
+
+	{
+		let mut control_groups = test_app.app.world.resource_mut::<ControlGroups>();
+		control_groups.groups[0] = vec![kept_entity, despawned_entity];
+		control_groups.groups[3] = vec![despawned_entity];
+	}
+
+	test_app.step(1);
+
+	test_app.app.world.despawn(despawned_entity);
+
+	test_app.step(1);
+
+	let control_groups = test_app.app.world.resource::<ControlGroups>();
+	assert_eq!(control_groups.groups[0], vec![kept_entity]);
+	assert!(control_groups.groups[3].is_empty(), "despawned entity is still in control group");
+}
+
+/// Presses only the given keys for one frame
+fn tap(app: &mut App, keys: &[KeyCode]) {
+	let mut key = app.world.resource_mut::<ButtonInput<KeyCode>>();
+	key.reset_all();
+	for key_code in keys.iter() {
+		key.press(*key_code);
+	}
+
+	app.update();
+
+	// context set this frame is applied at the start of the next one
+	app.world.resource_mut::<ButtonInput<KeyCode>>().reset_all();
+	app.update();
+}
+
+#[test]
+fn digits_queue_units_only_while_build_menu_is_open() {
+	let mut app = App::new();
+
+	insert_player(&mut app.world, Faction::Blue, &[]);
+
+	let Ok(unit_definitions) = ron::de::from_str::<UnitDefinitions>(include_str!("../../assets/units/default.units.ron")) else { panic!("failed to parse unit definitions") };
+	let Ok(building_definitions) = ron::de::from_str::<BuildingDefinitions>(include_str!("../../assets/buildings/default.buildings.ron")) else { panic!("failed to parse building definitions") };
+
+	let mut faction_resources = FactionResources::default();
+	*faction_resources.get_mut(Faction::Blue) = CollectedResources { purplite : 1000, tealite : 1000 };
+
+	// same input wiring as in GamePlugin
+	app
+		.init_state::<InputContext>()
+		.insert_resource(unit_definitions)
+		.insert_resource(building_definitions)
+		.insert_resource(faction_resources)
+		.insert_resource(Time::<()>::default())
+		.init_resource::<BuildMenuState>()
+		.init_resource::<Placement>()
+		.init_resource::<ControlGroups>()
+		.add_systems(Update, (
+			systems::build_menu_toggle.run_if(not(in_state(InputContext::Placement))),
+			systems::build_menu_control.after(systems::build_menu_toggle).run_if(in_state(InputContext::BuildMenu)),
+			systems::control_groups_control.run_if(in_state(InputContext::Gameplay)),
+		))
+	;
+
+	let base_entity = app.world.spawn((BaseBuilding, Faction::Blue, ProductionQueue::default(), Selectable::default(), Selected)).id();
+
+	let queued = |app: &App| app.world.get::<ProductionQueue>(base_entity).map_or(0, |production_queue| production_queue.items.len());
+
+	// digit is a control group key while menu is closed
+	tap(&mut app, &[KeyCode::ControlLeft, KeyCode::Digit1]);
+
+	assert_eq!(queued(&app), 0, "unit was queued with build menu closed");
+	assert_eq!(app.world.resource::<ControlGroups>().groups[0], vec![base_entity]);
+
+	tap(&mut app, &[KeyCode::KeyB]);
+	assert_eq!(*app.world.resource::<State<InputContext>>().get(), InputContext::BuildMenu);
+
+	// units come first while base is selected, held Ctrl doesn't store control group in menu
+	tap(&mut app, &[KeyCode::ControlLeft, KeyCode::Digit1]);
+	tap(&mut app, &[KeyCode::Digit2]);
+
+	assert_eq!(queued(&app), 2);
+	assert_eq!(app.world.resource::<ControlGroups>().groups[0], vec![base_entity]);
+	assert!(app.world.resource::<ControlGroups>().groups[1].is_empty());
+
+	tap(&mut app, &[KeyCode::KeyB]);
+	assert_eq!(*app.world.resource::<State<InputContext>>().get(), InputContext::Gameplay);
+
+	tap(&mut app, &[KeyCode::Digit1]);
+	assert_eq!(queued(&app), 2, "unit was queued after build menu was closed");
+}
@@ -4,7 +4,12 @@ use bevy :: {
 	transform :: TransformSystem,
 };
 
-use super :: game :: GameState;
+use bevy_hanabi :: prelude :: EffectAsset;
+
+use super :: game :: {
+	GameState,
+	MainEntities,
+};
 
 pub mod systems;
 
@@ -16,9 +21,9 @@ impl Plugin for HandheldPlugin {
 			.add_systems(Update, (
 				systems::setup_aabb,
 				systems::setup_animation_player,
-				systems::player_animation_control,
+				systems::player_animation_control.run_if(resource_exists::<MainEntities>),
 				systems::animation_control,
-				systems::drilling_particles_control,
+				systems::drilling_particles_control.run_if(resource_exists::<Assets<EffectAsset>>),
 				systems::aim_point_raypick.after(bevy_fps_controller::controller::fps_controller_render),
 				systems::collision_reaction,
 			).run_if(in_state(GameState::Main)))
@@ -0,0 +1,63 @@
+use bevy :: {
+	prelude :: *,
+	app :: ScheduleRunnerPlugin,
+	audio :: AudioPlugin,
+	render :: {
+		RenderPlugin,
+		settings :: WgpuSettings,
+	},
+	time :: TimeUpdateStrategy,
+	window :: ExitCondition,
+	winit :: WinitPlugin,
+};
+
+use super :: game :: GameState;
+
+use std :: time :: Duration;
+
+mod systems;
+
+/// Runs the economy without window and gpu. `MinimalPlugins` are not enough here because locators, colliders
+/// and drill aim points all come from gltf scenes, so we keep `DefaultPlugins` but without winit, audio and wgpu backends.
+/// Every update advances time by exactly `TIMESTEP` which keeps simulation independent from how fast the machine is
+pub struct HeadlessPlugin;
+
+pub const TIMESTEP : f32 = 1.0 / 60.0;
+
+pub const REPORT_INTERVAL : u32 = 600;
+
+impl Plugin for HeadlessPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.add_plugins(
+				DefaultPlugins
+					.set(WindowPlugin {
+						primary_window: None,
+						exit_condition: ExitCondition::DontExit,
+						close_when_requested: false,
+					})
+					.set(RenderPlugin {
+						render_creation: WgpuSettings { backends: None, ..default() }.into(),
+						..default()
+					})
+					.disable::<WinitPlugin>()
+					.disable::<AudioPlugin>()
+			)
+			.add_plugins(ScheduleRunnerPlugin::run_loop(Duration::ZERO))
+
+			.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(TIMESTEP)))
+			.insert_resource(SimulationTicks::default())
+
+			.add_systems(OnEnter(GameState::Loaded), (
+				systems::setup,
+			))
+
+			.add_systems(Last, (
+				systems::simulation_control,
+			).run_if(in_state(GameState::Main)))
+		;
+	}
+}
+
+#[derive(Resource, Default)]
+pub struct SimulationTicks(pub u32);
@@ -0,0 +1,124 @@
+use bevy :: {
+	prelude :: *,
+	app :: AppExit,
+};
+
+use super :: *;
+
+use crate :: options :: LaunchOptions;
+
+use crate :: assets :: GameAssets;
+
+use crate :: map :: {
+	Map,
+	MapUnits,
+	Terrain,
+	Level,
+	spawn as map_spawn,
+};
+
+use crate :: setup :: {
+	terrain_navmesh,
+	spawn as setup_spawn,
+};
+
+use crate :: resource_collection :: FactionResources;
+
+use crate :: faction :: Faction;
+
+pub fn setup(
+		launch_options	: Res<LaunchOptions>,
+		game_assets		: Res<GameAssets>,
+		maps			: Res<Assets<Map>>,
+		images			: Res<Assets<Image>>,
+	mut game_state		: ResMut<NextState<GameState>>,
+	mut meshes			: ResMut<Assets<Mesh>>,
+	mut materials		: ResMut<Assets<StandardMaterial>>,
+	mut commands		: Commands,
+) {
+	let Some(map) = maps.get(&game_assets.map) else { panic!("map should be loaded before setup!") };
+
+	let heightmap = game_assets.heightmap.as_ref().and_then(|handle| images.get(handle));
+
+	let terrain = Terrain::new(&map.ground, map.terrain.as_ref(), heightmap);
+
+	let levels = map.levels.iter().map(|map_level| Level::new(map_level, &terrain)).collect::<Vec<Level>>();
+
+	setup_spawn::ground(&terrain, &mut meshes, &mut materials, &mut commands);
+
+	// on top of units from the map, --svins are spawned at first base and split evenly between all deposits
+	let deposits_num = map.deposits.len();
+
+	let extra_units = if map.bases.is_empty() || deposits_num == 0 {
+		Vec::new()
+	} else {
+		(0 .. deposits_num)
+			.map(|deposit| MapUnits {
+				base	: 0,
+				deposit,
+				count	: launch_options.svins / deposits_num + (deposit < launch_options.svins % deposits_num) as usize,
+			})
+			.collect()
+	};
+
+	map_spawn::map_contents(map, &extra_units, &terrain, &levels, &game_assets, &mut meshes, &mut materials, &mut commands);
+
+	let (polyanya, navmesh_tiles) = terrain_navmesh(&terrain, levels);
+
+	commands.insert_resource(polyanya);
+
+	commands.insert_resource(navmesh_tiles);
+
+	commands.insert_resource(map.ground);
+
+	commands.insert_resource(terrain);
+
+	game_state.set(GameState::Main);
+}
+
+pub fn simulation_control(
+		launch_options		: Res<LaunchOptions>,
+		faction_resources	: Res<FactionResources>,
+	mut ticks				: ResMut<SimulationTicks>,
+	mut app_exit			: EventWriter<AppExit>,
+) {
+	// simulation is judged by what all factions collected together
+	let collected_resources = faction_resources.total();
+
+	ticks.0 += 1;
+
+	if ticks.0 % REPORT_INTERVAL == 0 {
+		println!("tick {}: purplite {} tealite {}", ticks.0, collected_resources.purplite, collected_resources.tealite);
+	}
+
+	let Some(max_ticks) = launch_options.ticks else { return };
+
+	if ticks.0 < max_ticks { return }
+
+	println!(
+		"simulation finished after {} ticks ({:.1} seconds): purplite {} tealite {}",
+		ticks.0,
+		ticks.0 as f32 * TIMESTEP,
+		collected_resources.purplite,
+		collected_resources.tealite
+	);
+
+	// stockpiles are what is left after factions paid for their units and buildings, e.g. when commanders are compared
+	for faction in Faction::ALL {
+		let stockpile = faction_resources.get(faction);
+
+		if stockpile.purplite + stockpile.tealite == 0 { continue }
+
+		println!("  {:?} stockpile: purplite {} tealite {}", faction, stockpile.purplite, stockpile.tealite);
+	}
+
+	if let Some(min_collected) = launch_options.min_collected {
+		let total_collected = collected_resources.purplite + collected_resources.tealite;
+
+		if total_collected < min_collected {
+			panic!("collected {} resources in total but at least {} were expected!", total_collected, min_collected);
+		}
+	}
+
+	app_exit.send(AppExit);
+}
@@ -27,23 +27,72 @@ use resource_collection :: ResourceCollectionPlugin;
 mod game;
 use game :: GamePlugin;
 
+mod faction;
+use faction :: FactionPlugin;
+
+mod units;
+use units :: UnitsPlugin;
+
+mod buildings;
+use buildings :: BuildingsPlugin;
+
+mod map;
+use map :: MapPlugin;
+
+mod save;
+use save :: SaveLoadPlugin;
+
+mod commander;
+use commander :: CommanderPlugin;
+
+mod minimap;
+use minimap :: MinimapPlugin;
+
+mod headless;
+use headless :: HeadlessPlugin;
+
+mod options;
+use options :: LaunchOptions;
+
 mod utils;
 
+#[cfg(test)]
+mod test_support;
+
 fn main() {
-	App::new()
-		// svincraft plugins
-		.add_plugins((
-			SetupPlugin,
-			AssetsPlugin,
-			HandheldPlugin,
-			ResourceCollectionPlugin,
-			AiPlugin,
-			GamePlugin,
-		))
-
-		// third party plugins
-		.add_plugins((
-			RapierPhysicsPlugin::<NoUserData>::default(),
+	let launch_options = LaunchOptions::from_args();
+
+	let mut app = App::new();
+
+	if launch_options.headless {
+		app.add_plugins(HeadlessPlugin);
+	} else {
+		app.add_plugins(SetupPlugin);
+	}
+
+	// svincraft plugins
+	app.add_plugins((
+		AssetsPlugin,
+		MapPlugin,
+		UnitsPlugin,
+		BuildingsPlugin,
+		HandheldPlugin,
+		ResourceCollectionPlugin,
+		AiPlugin,
+		GamePlugin,
+		FactionPlugin,
+		SaveLoadPlugin,
+		CommanderPlugin,
+		MinimapPlugin,
+	));
+
+	// third party plugins
+	app.add_plugins(
+		RapierPhysicsPlugin::<NoUserData>::default(),
+	);
+
+	if !launch_options.headless {
+		app.add_plugins((
 			RapierDebugRenderPlugin { enabled: false, ..default() },
 			PanOrbitCameraPlugin,
 			FpsControllerPlugin,
@@ -57,7 +106,10 @@ fn main() {
 			bevy::diagnostic::SystemInformationDiagnosticsPlugin,
 
 			PerfUiPlugin,
-		))
+		));
+	}
 
+	app
+		.insert_resource(launch_options)
 		.run();
 }
@@ -0,0 +1,163 @@
+use bevy :: prelude :: *;
+
+use super :: {
+	LevelSide,
+	MapLevel,
+	Terrain,
+};
+
+/// Map level put on terrain: deck and ramps with world heights. Spawned as static colliders and walked on through its own navmesh layer
+#[derive(Clone, Debug)]
+pub struct Level {
+	pub deck			: Rect,
+	/// world height of the deck
+	pub height			: f32,
+	/// ground under the deck stays walkable
+	pub bridge			: bool,
+	pub ramps			: Vec<Ramp>,
+}
+
+/// Slope from the ground up to the side of level's deck
+#[derive(Clone, Debug)]
+pub struct Ramp {
+	/// footprint on XZ plane, one of its sides lies on the side of the deck
+	pub area			: Rect,
+	/// middle of the side touching the ground
+	pub foot			: Vec2,
+	/// middle of the side touching the deck
+	pub top				: Vec2,
+	pub foot_height		: f32,
+	pub top_height		: f32,
+}
+
+impl Level {
+	/// Deck height is relative to terrain under its center and every ramp starts on terrain under its foot
+	pub fn new(map_level: &MapLevel, terrain: &Terrain) -> Self {
+		let deck = Rect::from_corners(map_level.min, map_level.max);
+
+		let height = terrain.height_at(deck.center()) + map_level.height;
+
+		let ramps = map_level.ramps.iter()
+			.map(|map_ramp| {
+				let (half_width, length) = (map_ramp.width / 2.0, map_ramp.length);
+
+				let (area, top, foot) = match map_ramp.side {
+					LevelSide::MinX | LevelSide::MaxX => {
+						let z = deck.center().y + map_ramp.offset;
+						let x = if map_ramp.side == LevelSide::MinX { deck.min.x } else { deck.max.x };
+						let outwards = if map_ramp.side == LevelSide::MinX { -length } else { length };
+
+						(
+							Rect::from_corners(Vec2::new(x, z - half_width), Vec2::new(x + outwards, z + half_width)),
+							Vec2::new(x, z),
+							Vec2::new(x + outwards, z),
+						)
+					},
+					LevelSide::MinZ | LevelSide::MaxZ => {
+						let x = deck.center().x + map_ramp.offset;
+						let z = if map_ramp.side == LevelSide::MinZ { deck.min.y } else { deck.max.y };
+						let outwards = if map_ramp.side == LevelSide::MinZ { -length } else { length };
+
+						(
+							Rect::from_corners(Vec2::new(x - half_width, z), Vec2::new(x + half_width, z + outwards)),
+							Vec2::new(x, z),
+							Vec2::new(x, z + outwards),
+						)
+					},
+				};
+
+				Ramp {
+					area,
+					foot,
+					top,
+					foot_height	: terrain.height_at(foot),
+					top_height	: height,
+				}
+			})
+			.collect();
+
+		Self {
+			deck,
+			height,
+			bridge : map_level.bridge,
+			ramps,
+		}
+	}
+
+	/// Height of the deck or ramp at point on XZ plane, `None` if level doesn't cover it
+	pub fn surface_height(&self, point: Vec2) -> Option<f32> {
+		if self.deck.contains(point) { return Some(self.height) }
+
+		self.ramps.iter()
+			.find(|ramp| ramp.area.contains(point))
+			.map(|ramp| ramp.height_at(point))
+	}
+
+	/// Deck outline in the same order as navmesh tiles are, with corners of ramps inserted where they touch it,
+	/// so that ramp triangulated on its own shares its top side with the deck
+	pub fn deck_outline(&self) -> Vec<Vec2> {
+		let (min, max) = (self.deck.min, self.deck.max);
+
+		let corners = [
+			Vec2::new(min.x, min.y),
+			Vec2::new(min.x, max.y),
+			Vec2::new(max.x, max.y),
+			Vec2::new(max.x, min.y),
+		];
+
+		let mut outline = Vec::new();
+
+		for (i, corner) in corners.iter().enumerate() {
+			let next_corner = corners[(i + 1) % corners.len()];
+
+			outline.push(*corner);
+
+			let side = next_corner - *corner;
+
+			// ramp corners lying on this side, ordered from this corner to the next one
+			let mut on_side = self.ramps.iter()
+				.flat_map(|ramp| [ramp.area.min, ramp.area.max, Vec2::new(ramp.area.min.x, ramp.area.max.y), Vec2::new(ramp.area.max.x, ramp.area.min.y)])
+				.filter(|point| (*point - *corner).perp_dot(side).abs() < f32::EPSILON * side.length())
+				.map(|point| ((point - *corner).dot(side) / side.length_squared(), point))
+				.filter(|(along, _)| *along > 0.0 && *along < 1.0)
+				.collect::<Vec<(f32, Vec2)>>();
+
+			on_side.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+			on_side.dedup_by(|(a, _), (b, _)| (*a - *b).abs() < f32::EPSILON);
+
+			outline.extend(on_side.into_iter().map(|(_, point)| point));
+		}
+
+		outline
+	}
+}
+
+impl Ramp {
+	/// Unit vector on XZ plane pointing up the ramp
+	pub fn direction(&self) -> Vec2 {
+		(self.top - self.foot).normalize_or_zero()
+	}
+
+	pub fn length(&self) -> f32 {
+		self.top.distance(self.foot)
+	}
+
+	/// Height of the ramp surface at point, rises evenly from foot to top
+	pub fn height_at(&self, point: Vec2) -> f32 {
+		let along = ((point - self.foot).dot(self.direction()) / self.length()).clamp(0.0, 1.0);
+
+		self.foot_height + (self.top_height - self.foot_height) * along
+	}
+
+	/// Ramp outline in the same order as navmesh tiles are
+	pub fn outline(&self) -> Vec<Vec2> {
+		let (min, max) = (self.area.min, self.area.max);
+
+		vec![
+			Vec2::new(min.x, min.y),
+			Vec2::new(min.x, max.y),
+			Vec2::new(max.x, max.y),
+			Vec2::new(max.x, min.y),
+		]
+	}
+}
@@ -0,0 +1,202 @@
+use bevy :: prelude :: *;
+
+use serde :: Deserialize;
+
+use super :: resource_collection :: CollectableResource;
+
+use super :: faction :: Faction;
+
+use super :: commander :: StrategyKind;
+
+use super :: assets :: RonAssetLoader;
+
+pub mod spawn;
+
+mod terrain;
+pub use terrain :: { Terrain, GROUND_GROUP };
+
+mod levels;
+pub use levels :: { Level, Ramp };
+
+#[cfg(test)]
+mod tests;
+
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.init_asset::<Map>()
+			.register_asset_loader(RonAssetLoader::<Map>::new(&["map.ron"]))
+		;
+	}
+}
+
+pub const DEFAULT_MAP_PATH : &str = "maps/default.map.ron";
+
+/// Level description loaded from `assets/maps/*.map.ron`. Everything here is spawned once when game enters
+/// `GameState::Loaded`, see `spawn::map_contents`
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct Map {
+	pub ground			: GroundBounds,
+	/// flat ground if not set. Positions of everything on the map are relative to terrain height
+	#[serde(default)]
+	pub terrain			: Option<MapTerrain>,
+	pub player_spawn	: Vec3,
+	/// faction of the local player, only its units and buildings can be selected
+	#[serde(default)]
+	pub player_faction	: Faction,
+	#[serde(default)]
+	pub bases			: Vec<MapBase>,
+	#[serde(default)]
+	pub deposits		: Vec<MapDeposit>,
+	#[serde(default)]
+	pub obstacles		: Vec<MapObstacle>,
+	/// bridges and plateaus walked on above the ground
+	#[serde(default)]
+	pub levels			: Vec<MapLevel>,
+	#[serde(default)]
+	pub units			: Vec<MapUnits>,
+	/// factions played by computer, see `commander` module
+	#[serde(default)]
+	pub commanders		: Vec<MapCommander>,
+}
+
+/// Walkable rectangle on XZ plane. Ground plane and navmesh outer edges are built from it
+#[derive(Resource, Deserialize, Clone, Copy, Debug)]
+pub struct GroundBounds {
+	pub min		: Vec2,
+	pub max		: Vec2,
+}
+
+impl GroundBounds {
+	pub fn size(&self) -> Vec2 {
+		self.max - self.min
+	}
+
+	pub fn center(&self) -> Vec2 {
+		(self.min + self.max) / 2.0
+	}
+
+	pub fn outer_edges(&self) -> [Vec2; 4] {
+		[
+			Vec2::new(self.min.x, self.min.y),
+			Vec2::new(self.min.x, self.max.y),
+			Vec2::new(self.max.x, self.max.y),
+			Vec2::new(self.max.x, self.min.y),
+		]
+	}
+}
+
+/// Heights come from heightmap image if it's set, otherwise terrain is generated from hills
+#[derive(Deserialize, Clone, Debug)]
+pub struct MapTerrain {
+	/// path to 8 bit grayscale image in assets, one pixel per height sample
+	#[serde(default)]
+	pub heightmap		: Option<String>,
+	/// height of white pixels of heightmap
+	#[serde(default)]
+	pub max_height		: f32,
+	/// height samples along each side of generated terrain
+	#[serde(default = "MapTerrain::default_resolution")]
+	pub resolution		: usize,
+	#[serde(default)]
+	pub hills			: Vec<MapHill>,
+	/// slopes steeper than this many degrees are excluded from navmesh
+	#[serde(default = "MapTerrain::default_max_slope")]
+	pub max_slope		: f32,
+}
+
+impl MapTerrain {
+	fn default_resolution() -> usize { 129 }
+
+	fn default_max_slope() -> f32 { 30.0 }
+}
+
+/// Round hill of generated terrain, overlapping hills add up
+#[derive(Deserialize, Clone, Debug)]
+pub struct MapHill {
+	/// center on XZ plane
+	pub position		: Vec2,
+	pub radius			: f32,
+	pub height			: f32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MapBase {
+	pub position		: Vec3,
+	/// rotation around Y axis in degrees
+	#[serde(default)]
+	pub rotation		: f32,
+	/// units spawned by base belong to the same faction, `player_faction` if not set
+	#[serde(default)]
+	pub faction			: Option<Faction>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MapDeposit {
+	pub resource		: CollectableResource,
+	pub position		: Vec3,
+	pub amount			: usize,
+}
+
+/// Static box that blocks both physics and navmesh
+#[derive(Deserialize, Debug)]
+pub struct MapObstacle {
+	pub position		: Vec3,
+	pub size			: Vec3,
+	/// rotation around Y axis in degrees
+	#[serde(default)]
+	pub rotation		: f32,
+}
+
+/// Walkable deck above the ground, either a bridge or top of a plateau, reached from the ground by ramps. Decks don't overlap each other
+/// and nothing is built on them
+#[derive(Deserialize, Clone, Debug)]
+pub struct MapLevel {
+	/// deck corners on XZ plane
+	pub min				: Vec2,
+	pub max				: Vec2,
+	/// deck height above terrain under its center
+	pub height			: f32,
+	/// ground under bridge stays walkable, under plateau it's blocked
+	#[serde(default)]
+	pub bridge			: bool,
+	#[serde(default)]
+	pub ramps			: Vec<MapRamp>,
+}
+
+/// Ramp from the ground up to a side of the deck, its foot is on terrain wherever it ends
+#[derive(Deserialize, Clone, Debug)]
+pub struct MapRamp {
+	pub side			: LevelSide,
+	/// ramp center along the side, from the middle of the side
+	#[serde(default)]
+	pub offset			: f32,
+	pub width			: f32,
+	/// how far from the deck ramp reaches the ground
+	pub length			: f32,
+}
+
+#[derive(Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum LevelSide {
+	MinX,
+	MaxX,
+	MinZ,
+	MaxZ,
+}
+
+/// Svins spawned by base with index `base` that start mining deposit with index `deposit` right away
+#[derive(Deserialize, Debug)]
+pub struct MapUnits {
+	pub base			: usize,
+	pub deposit			: usize,
+	pub count			: usize,
+}
+
+/// Faction controlled by a commander using given strategy. Ignored for player's faction unless game runs headless
+#[derive(Deserialize, Debug)]
+pub struct MapCommander {
+	pub faction			: Faction,
+	pub strategy		: StrategyKind,
+}
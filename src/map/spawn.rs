@@ -0,0 +1,140 @@
+use bevy :: {
+	prelude :: *,
+	utils :: HashMap,
+};
+
+use bevy_rapier3d :: prelude :: *;
+
+use super :: *;
+
+use crate :: assets :: GameAssets;
+
+use crate :: ai :: NpcSpawnTaskResourceCollection;
+
+use crate :: game :: spawn as game_spawn;
+
+/// Thickness of bridge decks and ramps
+const LEVEL_SLAB_THICKNESS : f32 = 0.5;
+
+/// Spawns bases, deposits, obstacles and levels described in map and queues initial units at bases. Map positions are relative to terrain height.
+/// `extra_units` are queued the same way as map units, headless mode uses them for `--svins`
+pub fn map_contents(
+	map			: &Map,
+	extra_units	: &[MapUnits],
+	terrain		: &Terrain,
+	levels		: &[Level],
+	game_assets	: &GameAssets,
+	meshes		: &mut Assets<Mesh>,
+	materials	: &mut Assets<StandardMaterial>,
+	commands	: &mut Commands,
+) {
+	let on_terrain = |position: Vec3| position + Vec3::Y * terrain.height_at(position.xz());
+
+	let bases = map.bases.iter()
+		.map(|base| {
+			let transform = Transform::from_translation(on_terrain(base.position)).with_rotation(Quat::from_rotation_y(base.rotation.to_radians()));
+
+			game_spawn::base_building(transform, base.faction.unwrap_or(map.player_faction), game_assets, commands)
+		})
+		.collect::<Vec<Entity>>();
+
+	let deposits = map.deposits.iter()
+		.map(|deposit| {
+			let transform = Transform::from_translation(on_terrain(deposit.position));
+
+			match deposit.resource {
+				CollectableResource::Tealite => game_spawn::tealite(transform, deposit.amount, game_assets, commands),
+				CollectableResource::Purplite => game_spawn::purplite(transform, deposit.amount, game_assets, commands),
+			}
+		})
+		.collect::<Vec<Entity>>();
+
+	for obstacle in map.obstacles.iter() {
+		let transform = Transform::from_translation(on_terrain(obstacle.position)).with_rotation(Quat::from_rotation_y(obstacle.rotation.to_radians()));
+
+		game_spawn::obstacle(transform, obstacle.size, meshes, materials, commands);
+	}
+
+	for level in levels.iter() {
+		self::level(level, terrain, meshes, materials, commands);
+	}
+
+	// one spawn task per base, svins are spawned in the same order batches are listed
+	let mut spawn_queues : HashMap<Entity, Vec<(usize, Entity)>> = HashMap::new();
+
+	for units in map.units.iter().chain(extra_units.iter()) {
+		if units.count == 0 { continue }
+
+		let (Some(base_entity), Some(deposit_entity)) = (bases.get(units.base), deposits.get(units.deposit)) else {
+			println!("skipping {} units: map has no base {} or no deposit {}!", units.count, units.base, units.deposit);
+			continue
+		};
+
+		spawn_queues.entry(*base_entity).or_default().push((units.count, *deposit_entity));
+	}
+
+	for (base_entity, queue) in spawn_queues {
+		commands.entity(base_entity).insert(NpcSpawnTaskResourceCollection { queue });
+	}
+}
+
+/// Deck and ramps as static boxes. They aren't navmesh obstacles, walkable area of the level is its own navmesh layer
+/// and ground under it is blocked when navmesh is built
+fn level(
+	level		: &Level,
+	terrain		: &Terrain,
+	meshes		: &mut Assets<Mesh>,
+	materials	: &mut Assets<StandardMaterial>,
+	commands	: &mut Commands,
+) {
+	let material = materials.add(Color::rgb(0.45, 0.4, 0.35));
+
+	// plateau reaches down to the lowest ground under it, bridge is a slab
+	let deck_bottom = if level.bridge {
+		level.height - LEVEL_SLAB_THICKNESS
+	} else {
+		[level.deck.min, level.deck.max, Vec2::new(level.deck.min.x, level.deck.max.y), Vec2::new(level.deck.max.x, level.deck.min.y), level.deck.center()].iter()
+			.map(|point| terrain.height_at(*point))
+			.fold(level.height, f32::min) - LEVEL_SLAB_THICKNESS
+	};
+
+	let deck_size = Vec3::new(level.deck.width(), level.height - deck_bottom, level.deck.height());
+
+	let level_entity = commands.spawn((
+		Name::new(if level.bridge { "Bridge" } else { "Plateau" }),
+		PbrBundle {
+			mesh : meshes.add(Cuboid::from_size(deck_size)),
+			material : material.clone(),
+			transform : Transform::from_xyz(level.deck.center().x, deck_bottom + deck_size.y / 2.0, level.deck.center().y),
+			..default()
+		},
+		RigidBody::Fixed,
+		Collider::cuboid(deck_size.x / 2.0, deck_size.y / 2.0, deck_size.z / 2.0),
+	)).id();
+
+	for ramp in level.ramps.iter() {
+		let (foot, top) = (ramp.foot.extend(ramp.foot_height).xzy(), ramp.top.extend(ramp.top_height).xzy());
+
+		// slab lies on the slope with its top face, local Z goes along the slope
+		let mut transform = Transform::from_translation((foot + top) / 2.0).looking_to(top - foot, Vec3::Y);
+		transform.translation -= transform.up() * LEVEL_SLAB_THICKNESS / 2.0;
+
+		// ramp's width goes across the slope, so it's the side of its area that isn't along the slope
+		let width = if ramp.direction().x.abs() > 0.5 { ramp.area.height() } else { ramp.area.width() };
+
+		let size = Vec3::new(width, LEVEL_SLAB_THICKNESS, foot.distance(top));
+
+		commands.spawn((
+			Name::new("Ramp"),
+			PbrBundle {
+				mesh : meshes.add(Cuboid::from_size(size)),
+				material : material.clone(),
+				transform,
+				..default()
+			},
+			RigidBody::Fixed,
+			Collider::cuboid(size.x / 2.0, size.y / 2.0, size.z / 2.0),
+		))
+		.set_parent(level_entity);
+	}
+}
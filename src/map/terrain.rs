@@ -0,0 +1,276 @@
+use bevy :: {
+	prelude :: *,
+	utils :: HashMap,
+	render :: {
+		mesh :: Indices,
+		render_resource :: PrimitiveTopology,
+		render_asset :: RenderAssetUsages,
+	},
+};
+
+use bevy_rapier3d :: prelude :: *;
+
+use super :: {
+	GroundBounds,
+	MapTerrain,
+};
+
+use std :: f32 :: consts :: PI;
+
+/// Ground colliders are only in this group, so shape queries that shouldn't count touching the ground can leave it out
+pub const GROUND_GROUP : Group = Group::GROUP_32;
+
+/// Height of the ground sampled on a regular grid over `GroundBounds`. Maps without terrain get a flat one,
+/// so everything can ask terrain for ground height without checking whether map has it
+#[derive(Resource, Clone, Debug)]
+pub struct Terrain {
+	pub ground			: GroundBounds,
+	/// samples row by row, columns go along X and rows along Z
+	pub heights			: Vec<f32>,
+	pub columns			: usize,
+	pub rows			: usize,
+	/// cells steeper than this are excluded from navmesh, in radians
+	pub max_slope		: f32,
+}
+
+impl Terrain {
+	/// Terrain described by map, heightmap image has to be loaded by now if map refers to one
+	pub fn new(ground: &GroundBounds, map_terrain: Option<&MapTerrain>, heightmap: Option<&Image>) -> Self {
+		let Some(map_terrain) = map_terrain else { return Self::flat(ground) };
+
+		if map_terrain.heightmap.is_none() { return Self::generate(ground, map_terrain) }
+
+		let Some(heightmap) = heightmap else { panic!("heightmap {:?} should be loaded before terrain is built!", map_terrain.heightmap) };
+
+		Self::from_heightmap(ground, map_terrain, heightmap)
+	}
+
+	pub fn flat(ground: &GroundBounds) -> Self {
+		Self {
+			ground			: *ground,
+			heights			: vec![0.0; 4],
+			columns			: 2,
+			rows			: 2,
+			max_slope		: PI / 2.0,
+		}
+	}
+
+	/// Hills from map summed on top of flat ground
+	pub fn generate(ground: &GroundBounds, map_terrain: &MapTerrain) -> Self {
+		let samples = map_terrain.resolution.max(2);
+
+		let mut terrain = Self {
+			ground			: *ground,
+			heights			: vec![0.0; samples * samples],
+			columns			: samples,
+			rows			: samples,
+			max_slope		: map_terrain.max_slope.to_radians(),
+		};
+
+		for row in 0 .. terrain.rows {
+			for column in 0 .. terrain.columns {
+				let point = terrain.sample_position(column, row);
+
+				terrain.heights[row * terrain.columns + column] = map_terrain.hills.iter()
+					.map(|hill| {
+						let distance = hill.position.distance(point);
+
+						if distance >= hill.radius { return 0.0 }
+
+						// cosine bump, flat on top and at the foot
+						hill.height * (1.0 + (PI * distance / hill.radius).cos()) / 2.0
+					})
+					.sum();
+			}
+		}
+
+		terrain
+	}
+
+	/// Heightmap pixels go along X and Z the same way as samples, white is `max_height` and black is zero
+	pub fn from_heightmap(ground: &GroundBounds, map_terrain: &MapTerrain, heightmap: &Image) -> Self {
+		let Ok(heightmap) = heightmap.clone().try_into_dynamic() else { panic!("heightmap should be an 8 bit grayscale or rgba image!") };
+
+		let heightmap = heightmap.into_luma8();
+
+		let (columns, rows) = (heightmap.width().max(2) as usize, heightmap.height().max(2) as usize);
+
+		let heights = (0 .. rows)
+			.flat_map(|row| (0 .. columns).map(move |column| (column, row)))
+			.map(|(column, row)| {
+				let x = (column as u32).min(heightmap.width() - 1);
+				let y = (row as u32).min(heightmap.height() - 1);
+
+				heightmap.get_pixel(x, y).0[0] as f32 / 255.0 * map_terrain.max_height
+			})
+			.collect();
+
+		Self {
+			ground			: *ground,
+			heights,
+			columns,
+			rows,
+			max_slope		: map_terrain.max_slope.to_radians(),
+		}
+	}
+
+	pub fn is_flat(&self) -> bool {
+		self.heights.iter().all(|height| *height == self.heights[0])
+	}
+
+	pub fn cell_size(&self) -> Vec2 {
+		self.ground.size() / Vec2::new((self.columns - 1) as f32, (self.rows - 1) as f32)
+	}
+
+	fn sample(&self, column: usize, row: usize) -> f32 {
+		self.heights[row * self.columns + column]
+	}
+
+	fn sample_position(&self, column: usize, row: usize) -> Vec2 {
+		self.ground.min + Vec2::new(column as f32, row as f32) * self.cell_size()
+	}
+
+	/// Ground height on the same triangles as terrain mesh and collider, points outside of ground get height of the closest edge
+	pub fn height_at(&self, point: Vec2) -> f32 {
+		let last = Vec2::new((self.columns - 1) as f32, (self.rows - 1) as f32);
+
+		let local = ((point - self.ground.min) / self.cell_size()).clamp(Vec2::ZERO, last);
+
+		let cell = local.floor().min(last - 1.0);
+		let (column, row) = (cell.x as usize, cell.y as usize);
+
+		let fraction = local - cell;
+
+		let h00 = self.sample(column, row);
+		let h01 = self.sample(column + 1, row);
+		let h10 = self.sample(column, row + 1);
+		let h11 = self.sample(column + 1, row + 1);
+
+		// cells are split along the diagonal from (column, row) to (column + 1, row + 1), same as rapier heightfield
+		if fraction.y >= fraction.x {
+			h00 + fraction.y * (h10 - h00) + fraction.x * (h11 - h10)
+		} else {
+			h00 + fraction.x * (h01 - h00) + fraction.y * (h11 - h01)
+		}
+	}
+
+	/// Steepest slope of the cell in radians
+	pub fn cell_slope(&self, column: usize, row: usize) -> f32 {
+		let cell_size = self.cell_size();
+
+		let h00 = self.sample(column, row);
+		let h01 = self.sample(column + 1, row);
+		let h10 = self.sample(column, row + 1);
+		let h11 = self.sample(column + 1, row + 1);
+
+		let gradient = Vec2::new(
+			(h01 - h00).abs().max((h11 - h10).abs()) / cell_size.x,
+			(h10 - h00).abs().max((h11 - h01).abs()) / cell_size.y,
+		);
+
+		gradient.length().atan()
+	}
+
+	/// Cells steeper than `max_slope` merged into rectangles, runs of cells in a row are merged first
+	/// and then extended over following rows while the run stays the same
+	pub fn steep_areas(&self) -> Vec<Rect> {
+		let mut areas : Vec<Rect> = Vec::new();
+
+		// areas that reached the previous row by their first and last column
+		let mut open_areas : HashMap<(usize, usize), usize> = HashMap::new();
+
+		for row in 0 .. self.rows - 1 {
+			let mut still_open = HashMap::new();
+
+			let mut column = 0;
+
+			while column < self.columns - 1 {
+				if self.cell_slope(column, row) <= self.max_slope {
+					column += 1;
+					continue
+				}
+
+				let start = column;
+
+				while column < self.columns - 1 && self.cell_slope(column, row) > self.max_slope {
+					column += 1;
+				}
+
+				let max = self.sample_position(column, row + 1);
+
+				let area_index = if let Some(area_index) = open_areas.get(&(start, column)) {
+					areas[*area_index].max = max;
+					*area_index
+				} else {
+					areas.push(Rect::from_corners(self.sample_position(start, row), max));
+					areas.len() - 1
+				};
+
+				still_open.insert((start, column), area_index);
+			}
+
+			open_areas = still_open;
+		}
+
+		areas
+	}
+
+	pub fn mesh(&self) -> Mesh {
+		let cell_size = self.cell_size();
+
+		let mut positions = Vec::with_capacity(self.heights.len());
+		let mut normals = Vec::with_capacity(self.heights.len());
+		let mut uvs = Vec::with_capacity(self.heights.len());
+
+		for row in 0 .. self.rows {
+			for column in 0 .. self.columns {
+				let point = self.sample_position(column, row);
+
+				positions.push([point.x, self.sample(column, row), point.y]);
+
+				// central differences, one sided on the edges
+				let (left, right) = (column.saturating_sub(1), (column + 1).min(self.columns - 1));
+				let (back, front) = (row.saturating_sub(1), (row + 1).min(self.rows - 1));
+
+				let dx = (self.sample(right, row) - self.sample(left, row)) / ((right - left) as f32 * cell_size.x);
+				let dz = (self.sample(column, front) - self.sample(column, back)) / ((front - back) as f32 * cell_size.y);
+
+				normals.push(Vec3::new(-dx, 1.0, -dz).normalize().to_array());
+
+				uvs.push([column as f32 / (self.columns - 1) as f32, row as f32 / (self.rows - 1) as f32]);
+			}
+		}
+
+		let mut indices = Vec::with_capacity((self.columns - 1) * (self.rows - 1) * 6);
+
+		for row in 0 .. self.rows - 1 {
+			for column in 0 .. self.columns - 1 {
+				let i00 = (row * self.columns + column) as u32;
+				let i01 = i00 + 1;
+				let i10 = i00 + self.columns as u32;
+				let i11 = i10 + 1;
+
+				indices.extend([i00, i10, i11, i00, i11, i01]);
+			}
+		}
+
+		Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+			.with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+			.with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+			.with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+			.with_inserted_indices(Indices::U32(indices))
+	}
+
+	/// Heightfield centered on ground, has to be spawned at ground center
+	pub fn collider(&self) -> Collider {
+		// rapier takes heights column by column with rows along Z
+		let heights = (0 .. self.columns)
+			.flat_map(|column| (0 .. self.rows).map(move |row| (column, row)))
+			.map(|(column, row)| self.sample(column, row))
+			.collect();
+
+		let size = self.ground.size();
+
+		Collider::heightfield(heights, self.rows, self.columns, Vec3::new(size.x, 1.0, size.y))
+	}
+}
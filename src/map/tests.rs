@@ -0,0 +1,128 @@
+use bevy :: scene :: ron;
+
+use super :: *;
+
+fn parse(map: &str) -> Map {
+	match ron::de::from_str::<Map>(map) {
+		Ok(map) => map,
+		Err(error) => panic!("failed to parse map: {}", error),
+	}
+}
+
+#[test]
+fn default_map_is_valid() {
+	let map = parse(include_str!("../../assets/maps/default.map.ron"));
+
+	assert!(map.ground.min.x < map.ground.max.x && map.ground.min.y < map.ground.max.y);
+	assert!(!map.bases.is_empty());
+	assert!(map.deposits.iter().any(|deposit| deposit.resource == CollectableResource::Tealite));
+	assert!(map.deposits.iter().any(|deposit| deposit.resource == CollectableResource::Purplite));
+
+	for units in map.units.iter() {
+		assert!(units.base < map.bases.len() && units.deposit < map.deposits.len());
+	}
+}
+
+#[test]
+fn optional_map_sections_can_be_omitted() {
+	let map = parse("(ground: (min: (-10.0, -20.0), max: (30.0, 40.0)), player_spawn: (0.0, 1.0, 0.0))");
+
+	assert!(map.bases.is_empty() && map.deposits.is_empty() && map.obstacles.is_empty() && map.units.is_empty() && map.commanders.is_empty());
+	assert_eq!(map.player_faction, Faction::default());
+
+	assert_eq!(map.ground.size(), Vec2::new(40.0, 60.0));
+	assert_eq!(map.ground.center(), Vec2::new(10.0, 10.0));
+	assert_eq!(map.ground.outer_edges()[2], Vec2::new(30.0, 40.0));
+}
+
+#[test]
+fn bases_belong_to_player_faction_unless_specified() {
+	let map = parse("(ground: (min: (-10.0, -10.0), max: (10.0, 10.0)), player_spawn: (0.0, 1.0, 0.0), player_faction: Green, bases: [(position: (0.0, 0.0, 0.0)), (position: (5.0, 0.0, 5.0), faction: Some(Red))])");
+
+	assert_eq!(map.player_faction, Faction::Green);
+	assert_eq!(map.bases[0].faction, None);
+	assert_eq!(map.bases[1].faction, Some(Faction::Red));
+}
+
+#[test]
+fn duel_map_commanders_have_bases() {
+	let map = parse(include_str!("../../assets/maps/duel.map.ron"));
+
+	assert!(!map.commanders.is_empty());
+
+	for commander in map.commanders.iter() {
+		assert_ne!(commander.faction, map.player_faction, "duel map is played by commanders only");
+		assert!(map.bases.iter().any(|base| base.faction == Some(commander.faction)), "{:?} commander has no base", commander.faction);
+	}
+
+	for units in map.units.iter() {
+		assert!(units.base < map.bases.len() && units.deposit < map.deposits.len());
+	}
+}
+
+#[test]
+fn hills_map_terrain_blocks_only_steep_hills() {
+	let map = parse(include_str!("../../assets/maps/hills.map.ron"));
+
+	let Some(map_terrain) = map.terrain.as_ref() else { panic!("hills map should have terrain!") };
+
+	let terrain = Terrain::new(&map.ground, Some(map_terrain), None);
+
+	assert!(!terrain.is_flat());
+
+	for hill in map_terrain.hills.iter() {
+		assert!((terrain.height_at(hill.position) - hill.height).abs() < 1.0, "top of the hill at {:?} should be at its height", hill.position);
+	}
+
+	assert_eq!(terrain.height_at(Vec2::ZERO), 0.0, "base should stand on flat ground");
+
+	let steep_areas = terrain.steep_areas();
+
+	let is_steep = |point: Vec2| steep_areas.iter().any(|area| area.contains(point));
+
+	// slope of a hill peaks halfway between its top and foot
+	let steepest_point = |hill: &MapHill| hill.position + Vec2::X * hill.radius / 2.0;
+
+	let (gentle, steep) = (&map_terrain.hills[0], &map_terrain.hills[3]);
+
+	assert!(!is_steep(steepest_point(gentle)), "gentle hill should stay walkable");
+	assert!(is_steep(steepest_point(steep)), "steep hill should be excluded from navmesh");
+}
+
+#[test]
+fn maps_without_terrain_are_flat() {
+	let map = parse(include_str!("../../assets/maps/default.map.ron"));
+
+	let terrain = Terrain::new(&map.ground, map.terrain.as_ref(), None);
+
+	assert!(terrain.is_flat());
+	assert!(terrain.steep_areas().is_empty());
+	assert_eq!(terrain.height_at(map.ground.max + 10.0), 0.0);
+}
+
+#[test]
+fn levels_map_ramps_connect_decks_to_the_ground() {
+	let map = parse(include_str!("../../assets/maps/levels.map.ron"));
+
+	let terrain = Terrain::new(&map.ground, map.terrain.as_ref(), None);
+
+	assert!(map.levels.iter().any(|map_level| map_level.bridge) && map.levels.iter().any(|map_level| !map_level.bridge));
+
+	for map_level in map.levels.iter() {
+		let level = Level::new(map_level, &terrain);
+
+		assert!(!level.ramps.is_empty(), "level at {:?} can't be reached without ramps", level.deck);
+
+		let outline = level.deck_outline();
+
+		for ramp in level.ramps.iter() {
+			assert_eq!(level.surface_height(ramp.top), Some(level.height));
+			assert_eq!(level.surface_height(ramp.foot), Some(terrain.height_at(ramp.foot)));
+
+			// deck and ramp navmeshes are stitched through corners of ramp's top side
+			for corner in ramp.outline().into_iter().filter(|corner| level.deck.contains(*corner)) {
+				assert!(outline.contains(&corner), "deck outline should have ramp corner {:?}", corner);
+			}
+		}
+	}
+}
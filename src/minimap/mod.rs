@@ -0,0 +1,87 @@
+use bevy :: prelude :: *;
+
+use super :: game :: {
+	GameState,
+	MainEntities,
+};
+
+use super :: map :: GroundBounds;
+
+pub mod spawn;
+	mod systems;
+
+#[cfg(test)]
+mod tests;
+
+/// Minimap in the corner of the screen. It's an image drawn from positions of bases, units and deposits every frame,
+/// left click on it moves rts camera there and right click commands selected units like right click in the world does
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			// minimap needs ui and player, so none of it runs in headless mode
+			.add_systems(OnEnter(GameState::Main), systems::setup_minimap.run_if(resource_exists::<MainEntities>))
+
+			.add_systems(Update, (
+				systems::minimap_control,
+				systems::minimap_draw,
+			).run_if(in_state(GameState::Main)).run_if(resource_exists::<Minimap>))
+		;
+	}
+}
+
+/// Width and height of minimap image in pixels
+pub const MINIMAP_RESOLUTION : u32 = 128;
+
+/// Width and height of minimap on screen in logical pixels
+pub const MINIMAP_SIZE : f32 = 220.0;
+
+/// Distance from minimap to bottom right corner of the window
+pub const MINIMAP_MARGIN : f32 = 10.0;
+
+pub const MINIMAP_BORDER : f32 = 2.0;
+
+pub const MINIMAP_BACKGROUND_COLOR : Color = Color::rgb(0.08, 0.1, 0.08);
+pub const MINIMAP_GROUND_COLOR : Color = Color::rgb(0.2, 0.25, 0.18);
+
+#[derive(Resource)]
+pub struct Minimap {
+	pub image		: Handle<Image>,
+	pub node		: Entity,
+}
+
+/// Maps ground to minimap image pixels keeping its aspect ratio, longer side of the ground fills the whole image
+#[derive(Clone, Copy, Debug)]
+pub struct MinimapProjection {
+	ground_min		: Vec2,
+	/// pixels per meter
+	scale			: f32,
+	/// shorter side of the ground is centered
+	offset			: Vec2,
+}
+
+impl MinimapProjection {
+	pub fn new(ground: &GroundBounds, resolution: u32) -> Self {
+		let size = ground.size();
+		let scale = resolution as f32 / size.max_element();
+
+		Self {
+			ground_min	: ground.min,
+			scale,
+			offset		: (Vec2::splat(resolution as f32) - size * scale) / 2.0,
+		}
+	}
+
+	/// Pixel coordinates of a world position, x goes along world X and y along world Z
+	pub fn to_pixel(&self, position: Vec3) -> Vec2 {
+		(position.xz() - self.ground_min) * self.scale + self.offset
+	}
+
+	/// Point on the ground under a pixel
+	pub fn to_world(&self, pixel: Vec2) -> Vec3 {
+		let position = (pixel - self.offset) / self.scale + self.ground_min;
+
+		Vec3::new(position.x, 0.0, position.y)
+	}
+}
@@ -0,0 +1,28 @@
+use bevy :: prelude :: *;
+
+use super :: *;
+
+pub fn minimap(
+	image		: Handle<Image>,
+	commands	: &mut Commands
+) -> Entity {
+	commands.spawn((
+		Name::new("Minimap"),
+		ImageBundle {
+			style : Style {
+				position_type	: PositionType::Absolute,
+				bottom			: Val::Px(MINIMAP_MARGIN),
+				right			: Val::Px(MINIMAP_MARGIN),
+				width			: Val::Px(MINIMAP_SIZE),
+				height			: Val::Px(MINIMAP_SIZE),
+				border			: UiRect::all(Val::Px(MINIMAP_BORDER)),
+				..default()
+			},
+			image : UiImage::new(image),
+			..default()
+		},
+		BorderColor(Color::DARK_GRAY),
+		// tells cursor raypick and selection that cursor is over ui
+		Interaction::default(),
+	)).id()
+}
@@ -0,0 +1,228 @@
+use bevy :: {
+	prelude :: *,
+	render :: {
+		render_asset :: RenderAssetUsages,
+		render_resource :: { Extent3d, TextureDimension, TextureFormat },
+		texture :: ImageSampler,
+	},
+};
+
+use bevy_panorbit_camera :: PanOrbitCamera;
+
+use super :: *;
+
+use crate :: game :: { BaseBuilding, CameraMode, InputContext };
+
+use crate :: ai :: { NpcMovable, NpcCommandTarget, PolyanyaResource };
+
+use crate :: map :: Terrain;
+
+use crate :: buildings :: { BuildingType, UnderConstruction };
+
+use crate :: resource_collection :: CollectableResource;
+
+use crate :: faction :: Faction;
+
+pub fn setup_minimap(
+	mut images		: ResMut<Assets<Image>>,
+	mut commands	: Commands,
+) {
+	let mut image = Image::new_fill(
+		Extent3d { width : MINIMAP_RESOLUTION, height : MINIMAP_RESOLUTION, depth_or_array_layers : 1 },
+		TextureDimension::D2,
+		&MINIMAP_BACKGROUND_COLOR.as_rgba_u8(),
+		TextureFormat::Rgba8UnormSrgb,
+		RenderAssetUsages::all(),
+	);
+
+	// sharp pixels instead of blurry dots
+	image.sampler = ImageSampler::nearest();
+
+	let image_handle = images.add(image);
+
+	let node_entity = spawn::minimap(image_handle.clone(), &mut commands);
+
+	commands.insert_resource(Minimap {
+		image	: image_handle,
+		node	: node_entity,
+	});
+}
+
+/// Left click or drag on minimap moves rts camera, right click sends `NpcCommandTarget` to selected units and bases
+pub fn minimap_control(
+		mouse_button	: Res<ButtonInput<MouseButton>>,
+		minimap			: Res<Minimap>,
+		ground			: Res<GroundBounds>,
+		terrain			: Res<Terrain>,
+		polyanya		: Res<PolyanyaResource>,
+		main_entities	: Res<MainEntities>,
+		camera_mode		: Res<State<CameraMode>>,
+		input_context	: Res<State<InputContext>>,
+		q_window		: Query<&Window>,
+		q_node			: Query<(&Node, &GlobalTransform, &Interaction)>,
+	mut q_orbit_camera	: Query<&mut PanOrbitCamera>,
+	mut command_targets	: EventWriter<NpcCommandTarget>,
+) {
+	let window = q_window.single();
+
+	// locked cursor of first person camera can't click anything
+	if !window.cursor.visible { return }
+
+	let Some(cursor) = window.cursor_position() else { return };
+
+	let Ok((node, node_transform, interaction)) = q_node.get(minimap.node) else { panic!("Minimap::node points to non existing entity! There is no minimap ui!") };
+
+	// image is inside of the border
+	let rect = node.logical_rect(node_transform).inset(-MINIMAP_BORDER);
+
+	if !rect.contains(cursor) { return }
+
+	let projection = MinimapProjection::new(&ground, MINIMAP_RESOLUTION);
+
+	let position = projection.to_world((cursor - rect.min) / rect.size() * MINIMAP_RESOLUTION as f32);
+
+	// pressed only if left button went down over minimap, dragging selection box over it doesn't move camera
+	if *interaction == Interaction::Pressed && *camera_mode.get() == CameraMode::Rts {
+		let Ok(mut orbit_camera) = q_orbit_camera.get_mut(main_entities.orbit_camera) else { panic!("orbit camera has no PanOrbitCamera component!") };
+
+		orbit_camera.target_focus = position;
+	}
+
+	// same as in the world, orders aren't given while build menu is open or building is being placed
+	// minimap shows everything from above, so units are sent on top of a plateau or bridge and not under it
+	if mouse_button.just_pressed(MouseButton::Right) && *input_context.get() == InputContext::Gameplay {
+		command_targets.send(NpcCommandTarget {
+			entity	: None,
+			position : Vec3::new(position.x, polyanya.top_height(position.xz(), &terrain), position.z),
+		});
+	}
+}
+
+pub fn minimap_draw(
+		minimap			: Res<Minimap>,
+		ground			: Res<GroundBounds>,
+		main_entities	: Res<MainEntities>,
+		camera_mode		: Res<State<CameraMode>>,
+		q_camera		: Query<(&Camera, &GlobalTransform)>,
+		q_transform		: Query<&GlobalTransform>,
+		q_deposit		: Query<(&GlobalTransform, &CollectableResource)>,
+		q_building		: Query<(&GlobalTransform, &Faction, Option<&BuildingType>, Has<UnderConstruction>), Or<(With<BaseBuilding>, With<BuildingType>)>>,
+		q_unit			: Query<(&GlobalTransform, &Faction), With<NpcMovable>>,
+	mut images			: ResMut<Assets<Image>>,
+) {
+	let Some(image) = images.get_mut(&minimap.image) else { return };
+
+	let projection = MinimapProjection::new(&ground, MINIMAP_RESOLUTION);
+
+	let mut canvas = Canvas { data : &mut image.data, resolution : MINIMAP_RESOLUTION as i32 };
+
+	canvas.fill(MINIMAP_BACKGROUND_COLOR);
+
+	let ground_min = projection.to_pixel(Vec3::new(ground.min.x, 0.0, ground.min.y));
+	let ground_max = projection.to_pixel(Vec3::new(ground.max.x, 0.0, ground.max.y));
+
+	canvas.rect(ground_min, ground_max, MINIMAP_GROUND_COLOR);
+
+	for (deposit_transform, resource_type) in q_deposit.iter() {
+		let color = match resource_type {
+			CollectableResource::Tealite	=> Color::hex("7df0f8").unwrap(),
+			CollectableResource::Purplite	=> Color::hex("b05cff").unwrap(),
+		};
+
+		canvas.square(projection.to_pixel(deposit_transform.translation()), 1, color);
+	}
+
+	for (building_transform, faction, building_type, under_construction) in q_building.iter() {
+		let half_size = if building_type == Some(&BuildingType::Wall) { 1 } else { 3 };
+
+		let color = if under_construction { faction.color() * 0.5 } else { faction.color() };
+
+		canvas.square(projection.to_pixel(building_transform.translation()), half_size, color);
+	}
+
+	for (unit_transform, faction) in q_unit.iter() {
+		canvas.square(projection.to_pixel(unit_transform.translation()), 0, faction.color());
+	}
+
+	if let Ok(player_transform) = q_transform.get(main_entities.player) {
+		canvas.square(projection.to_pixel(player_transform.translation()), 1, Color::WHITE);
+	}
+
+	if *camera_mode.get() != CameraMode::Rts { return }
+
+	// part of the ground rts camera sees, corners that look above horizon are left out
+	let Ok((orbit_camera, orbit_camera_transform)) = q_camera.get(main_entities.orbit_camera) else { panic!("orbit camera is not present in game world!") };
+
+	let Some(viewport_size) = orbit_camera.logical_viewport_size() else { return };
+
+	let footprint = [Vec2::ZERO, Vec2::new(viewport_size.x, 0.0), viewport_size, Vec2::new(0.0, viewport_size.y)]
+		.into_iter()
+		.filter_map(|corner| {
+			let ray = orbit_camera.viewport_to_world(orbit_camera_transform, corner)?;
+			let distance = ray.intersect_plane(Vec3::ZERO, Plane3d::new(Vec3::Y))?;
+
+			Some(projection.to_pixel(ray.get_point(distance)))
+		})
+		.collect::<Vec<Vec2>>();
+
+	for i in 0 .. footprint.len() {
+		canvas.line(footprint[i], footprint[(i + 1) % footprint.len()], Color::WHITE);
+	}
+}
+
+/// Rgba8 pixels of minimap image, everything outside of the image is clipped
+struct Canvas<'a> {
+	data		: &'a mut [u8],
+	resolution	: i32,
+}
+
+impl Canvas<'_> {
+	fn put(&mut self, x: i32, y: i32, color: [u8; 4]) {
+		if x < 0 || y < 0 || x >= self.resolution || y >= self.resolution { return }
+
+		let index = ((y * self.resolution + x) * 4) as usize;
+
+		self.data[index .. index + 4].copy_from_slice(&color);
+	}
+
+	fn fill(&mut self, color: Color) {
+		for pixel in self.data.chunks_exact_mut(4) {
+			pixel.copy_from_slice(&color.as_rgba_u8());
+		}
+	}
+
+	fn rect(&mut self, min: Vec2, max: Vec2, color: Color) {
+		let color = color.as_rgba_u8();
+
+		for y in min.y as i32 .. max.y.ceil() as i32 {
+			for x in min.x as i32 .. max.x.ceil() as i32 {
+				self.put(x, y, color);
+			}
+		}
+	}
+
+	fn square(&mut self, center: Vec2, half_size: i32, color: Color) {
+		let color = color.as_rgba_u8();
+
+		let (cx, cy) = (center.x as i32, center.y as i32);
+
+		for y in cy - half_size ..= cy + half_size {
+			for x in cx - half_size ..= cx + half_size {
+				self.put(x, y, color);
+			}
+		}
+	}
+
+	fn line(&mut self, from: Vec2, to: Vec2, color: Color) {
+		let color = color.as_rgba_u8();
+
+		// one pixel per step, points outside of the image are skipped by put
+		let steps = (to - from).abs().max_element().ceil().max(1.0) as i32;
+
+		for step in 0 ..= steps {
+			let point = from.lerp(to, step as f32 / steps as f32);
+
+			self.put(point.x as i32, point.y as i32, color);
+		}
+	}
+}
@@ -0,0 +1,31 @@
+use bevy :: prelude :: *;
+
+use super :: *;
+
+#[test]
+fn projection_keeps_aspect_ratio_and_centers_shorter_side() {
+	let ground = GroundBounds { min : Vec2::new(-100.0, -50.0), max : Vec2::new(100.0, 50.0) };
+
+	let projection = MinimapProjection::new(&ground, 128);
+
+	let expected = [
+		(Vec3::new(-100.0, 0.0, -50.0), Vec2::new(0.0, 32.0)),
+		(Vec3::new(100.0, 0.0, 50.0), Vec2::new(128.0, 96.0)),
+		(Vec3::ZERO, Vec2::splat(64.0)),
+	];
+
+	for (position, pixel) in expected {
+		assert!(projection.to_pixel(position).distance(pixel) < 0.001, "{:?} is projected to {:?} instead of {:?}", position, projection.to_pixel(position), pixel);
+	}
+}
+
+#[test]
+fn projection_maps_pixel_back_to_ground() {
+	let ground = GroundBounds { min : Vec2::new(0.0, 0.0), max : Vec2::new(60.0, 120.0) };
+
+	let projection = MinimapProjection::new(&ground, 128);
+
+	let position = Vec3::new(12.5, 0.0, 80.0);
+
+	assert!(projection.to_world(projection.to_pixel(position)).distance(position) < 0.001);
+}
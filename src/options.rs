@@ -0,0 +1,77 @@
+use bevy :: prelude :: *;
+
+use super :: map :: DEFAULT_MAP_PATH;
+
+use std :: {
+	path :: PathBuf,
+	str :: FromStr,
+};
+
+/// Options passed through command line, see `--help` for the list
+#[derive(Resource, Clone)]
+pub struct LaunchOptions {
+	pub headless		: bool,
+	pub ticks			: Option<u32>,
+	pub svins			: usize,
+	pub min_collected	: Option<usize>,
+	pub load			: Option<PathBuf>,
+	pub map				: String,
+}
+
+impl Default for LaunchOptions {
+	fn default() -> Self {
+		Self {
+			headless		: false,
+			ticks			: None,
+			svins			: 8,
+			min_collected	: None,
+			load			: None,
+			map				: DEFAULT_MAP_PATH.to_string(),
+		}
+	}
+}
+
+const HELP : &str = concat!(
+	"Usage: svincraft [OPTIONS]\n",
+	"  --headless             run simulation without window and renderer\n",
+	"  --ticks <N>            exit after N simulation ticks (headless only)\n",
+	"  --svins <N>            number of svins mining at start (headless only, default 8)\n",
+	"  --min-collected <N>    fail if less than N resources were collected when simulation ends (headless only)\n",
+	"  --map <FILE>           map to play, path is relative to assets folder (default maps/default.map.ron)\n",
+	"  --load <FILE>          load game state from FILE saved earlier with F5\n",
+	"  --help                 print this message\n",
+);
+
+impl LaunchOptions {
+	pub fn from_args() -> Self {
+		let mut options = Self::default();
+
+		let mut args = std::env::args().skip(1);
+
+		while let Some(arg) = args.next() {
+			match arg.as_str() {
+				"--headless"		=> options.headless = true,
+				"--ticks"			=> options.ticks = Some(parse_value(&arg, args.next())),
+				"--svins"			=> options.svins = parse_value(&arg, args.next()),
+				"--min-collected"	=> options.min_collected = Some(parse_value(&arg, args.next())),
+				"--map"				=> options.map = parse_value(&arg, args.next()),
+				"--load"			=> options.load = Some(parse_value(&arg, args.next())),
+				"--help"			=> {
+					print!("{}", HELP);
+					std::process::exit(0);
+				},
+				_ => println!("Warning: unknown argument {}! See --help for the list of supported arguments", arg),
+			}
+		}
+
+		options
+	}
+}
+
+fn parse_value<T: FromStr>(arg: &str, value: Option<String>) -> T {
+	let Some(value) = value else { panic!("{} expects a value!", arg) };
+
+	let Ok(parsed) = value.parse::<T>() else { panic!("failed to parse value {:?} of {}!", value, arg) };
+
+	parsed
+}
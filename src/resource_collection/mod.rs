@@ -1,10 +1,20 @@
-use bevy :: prelude :: *;
+use bevy :: {
+	prelude :: *,
+	utils :: HashMap,
+};
+
+use serde :: Deserialize;
 
 use super :: game :: {
 	GameState,
+	MainEntities,
 	BatchOfResources,
 };
 
+use super :: ai;
+
+use super :: faction :: Faction;
+
 use super :: utils :: *;
 
 pub mod systems;
@@ -14,24 +24,77 @@ pub struct ResourceCollectionPlugin;
 impl Plugin for ResourceCollectionPlugin {
 	fn build(&self, app: &mut App) {
 		app
-			.insert_resource(CollectedResources::default())
+			.register_type::<CollectableResource>()
+			.register_type::<ResourceDeposit>()
+			.register_type::<CollectedResources>()
+			.register_type::<FactionResources>()
+			.register_type::<HashMap<Faction, CollectedResources>>()
+			.register_type::<ActiveCollecting>()
+			.register_type::<ResourceCollector>()
+			.register_type::<ResourceCollectionStage>()
+
+			.insert_resource(FactionResources::default())
 			.add_systems(Update, (
 				systems::resource_collecting_control,
 				systems::resource_delivery_control,
+				systems::resource_deposit_control.after(ai::systems::retarget_task_resource_collection),
+			).run_if(in_state(GameState::Main)))
+
+			.add_systems(Update, (
 				systems::collected_resource_ui,
 				systems::player_slowing_control,
-			).run_if(in_state(GameState::Main)))
+			).run_if(in_state(GameState::Main)).run_if(resource_exists::<MainEntities>))
 		;
 	}
 }
 
-#[derive(Component, PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(Component, Reflect, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[reflect(Component)]
 pub enum CollectableResource {
 	Tealite,
 	Purplite,
 }
 
-#[derive(Resource, Default)]
+pub const DEPOSIT_DEFAULT_AMOUNT : usize = 300;
+
+/// How many shards can still be drilled out of a tealite or purplite crystal. Once `remaining` reaches zero
+/// the deposit gets despawned by `resource_deposit_control`
+#[derive(Component, Reflect, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct ResourceDeposit {
+	pub remaining	: usize,
+	pub initial		: usize,
+}
+
+impl ResourceDeposit {
+	pub fn new(amount: usize) -> Self {
+		Self {
+			remaining	: amount,
+			initial		: amount,
+		}
+	}
+
+	pub fn depleted(&self) -> bool {
+		self.remaining == 0
+	}
+
+	pub fn fraction(&self) -> f32 {
+		if self.initial == 0 { return 0.0 }
+
+		self.remaining as f32 / self.initial as f32
+	}
+
+	pub fn take_one(&mut self) -> bool {
+		if self.depleted() { return false }
+
+		self.remaining -= 1;
+
+		true
+	}
+}
+
+/// Stockpile of one faction, resources are spent from it on units and buildings
+#[derive(Reflect, Default, Clone, Copy, Debug)]
 pub struct CollectedResources {
 	pub purplite	: usize,
 	pub tealite		: usize,
@@ -47,9 +110,44 @@ impl CollectedResources {
 		self.purplite -= amount.purplite;
 		self.tealite -= amount.tealite;
 	}
+
+	pub fn refund(&mut self, amount: &BatchOfResources) {
+		self.purplite += amount.purplite;
+		self.tealite += amount.tealite;
+	}
+}
+
+/// Every faction has its own stockpile, resources delivered to a base go to the faction of the handheld that delivered them
+#[derive(Resource, Reflect, Default)]
+#[reflect(Resource)]
+pub struct FactionResources {
+	pub by_faction	: HashMap<Faction, CollectedResources>,
+}
+
+impl FactionResources {
+	pub fn get(&self, faction: Faction) -> CollectedResources {
+		self.by_faction.get(&faction).copied().unwrap_or_default()
+	}
+
+	pub fn get_mut(&mut self, faction: Faction) -> &mut CollectedResources {
+		self.by_faction.entry(faction).or_default()
+	}
+
+	/// sum of all stockpiles
+	pub fn total(&self) -> CollectedResources {
+		let mut total = CollectedResources::default();
+
+		for collected in self.by_faction.values() {
+			total.purplite += collected.purplite;
+			total.tealite += collected.tealite;
+		}
+
+		total
+	}
 }
 
-#[derive(Component, Default)]
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct ActiveCollecting {
 	pub last_update_timestamp	: Option<f32>,
 		purplite_shard_entities	: Vec<Entity>,
@@ -100,13 +198,16 @@ pub struct ResourceUiEntities {
 	pub tealite_text	: Entity,
 }
 
-#[derive(Component, Default)]
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct ResourceCollector {
 	pub base_building_entity : Option<Entity>,
 	pub limit		: usize,
+	/// seconds it takes to drill out one shard
+	pub mining_interval : f32,
 }
 
-#[derive(Default)]
+#[derive(Reflect, Default, PartialEq, Eq, Clone, Copy, Debug)]
 pub enum ResourceCollectionStage {
 	#[default]
 	MovingToResource,
@@ -10,6 +10,7 @@ use crate:: game :: {
 	MainEntities,
 	Raypick,
 	BaseBuilding,
+	spawn as game_spawn,
 };
 
 use crate :: handheld :: {
@@ -17,17 +18,15 @@ use crate :: handheld :: {
 	CurrentHandheld,
 };
 
-use rand :: Rng;
-
-use std :: f32 :: consts :: { PI, TAU };
+use crate :: faction :: Faction;
 
 pub fn resource_collecting_control(
-		main_entities		: Res<MainEntities>,
+		main_entities		: Option<Res<MainEntities>>,
 		game_assets			: Res<GameAssets>,
 		time				: Res<Time>,
 		q_resource_collector: Query<&ResourceCollector>,
 		q_handheld_raypick	: Query<(&Raypick, &Handheld), With<CurrentHandheld>>,
-		q_collectable		: Query<&CollectableResource>,
+	mut q_collectable		: Query<(&CollectableResource, &mut ResourceDeposit)>,
 	mut	q_active_collecting	: Query<&mut ActiveCollecting>,
 	mut commands			: Commands
 ) {
@@ -36,11 +35,16 @@ pub fn resource_collecting_control(
 
 		let Ok(resource_collector) = q_resource_collector.get(handheld_owner) else { continue };
 
+		// shards collected by player are attached to player camera. There is no player in headless mode
+		let player_camera = main_entities.as_ref()
+			.filter(|main_entities| main_entities.player == handheld_owner)
+			.map(|main_entities| main_entities.player_camera);
+
 		// nothing to do without raypicked entity
 		let Some(raypicked_entity) = raypick.entity else { continue };
 
 		// nothing to do if raypicked entity is not collectable
-		let Ok(collectable) = q_collectable.get(raypicked_entity) else { continue };
+		let Ok((collectable, mut deposit)) = q_collectable.get_mut(raypicked_entity) else { continue };
 
 		if !handheld.activated() {
 			if let Ok(mut active_collecting) = q_active_collecting.get_mut(handheld_owner) {
@@ -75,63 +79,29 @@ pub fn resource_collecting_control(
 		};
 
 		// return if update timeout hasnt finished yet
-		if elapsed_seconds - last_update_timestamp < 1.0 {
+		if elapsed_seconds - last_update_timestamp < resource_collector.mining_interval {
 			continue
 		}
 
-		let asset_to_spawn = match *collectable {
-			CollectableResource::Tealite => game_assets.tealite_shard.clone_weak(),
-			CollectableResource::Purplite => game_assets.purplite_shard.clone_weak(),
-		};
-
-		let mut rng = rand::thread_rng();
-
-
-		let shard_rotation = Quat::from_euler(
-			EulerRot::XYZ,
-			rng.gen_range(0.0 .. PI / 4.0),
-			rng.gen_range(0.0 .. TAU),
-			rng.gen_range(0.0 .. PI / 6.0)
-		);
-
-		let shard_translation = if collected_shards_num > 0 {
-			let angle_x = rng.gen_range(0.0 .. PI / 4.0);
-			let angle_y = (TAU / resource_collector.limit as f32) * collected_shards_num as f32;
-			let angle_z = rng.gen_range(0.0 .. PI / 6.0);
-			let penta_rotation = Quat::from_euler(EulerRot::XYZ, angle_x, angle_y, angle_z);
+		// deposit can run out while several collectors are drilling it at the same time
+		if !deposit.take_one() { continue }
 
-			penta_rotation.mul_vec3(Vec3::new(0.0, 0.0, 0.05))
-		} else if handheld_owner == main_entities.player {
-			Vec3::new(-0.20, 0.0, -0.7)
-		} else {
-			Vec3::new(0.0, 0.4, -0.5)
-		};
-
-		let new_shard_entity = commands.spawn((
-			Name::new("Shard"),
-			SceneBundle {
-				scene : asset_to_spawn,
-				transform : Transform {
-					translation : shard_translation,
-					rotation : shard_rotation,
-					..default()
-				},
-				..default()
-			},
-		)).id();
-
-		if collected_shards_num > 0 {
+		let shard_parent = if collected_shards_num > 0 {
 			let Some(first_shard) = active_collecting.get_first_shard_entity() else { panic!("there should be at least 1 shard of type {:?}!", *collectable) };
-			commands.entity(first_shard).add_child(new_shard_entity);
+			first_shard
 		} else {
-			let attach_target = if handheld_owner == main_entities.player {
-				main_entities.player_camera
-			} else {
-				handheld_owner
-			};
+			player_camera.unwrap_or(handheld_owner)
+		};
 
-			commands.entity(attach_target).add_child(new_shard_entity);
-		}
+		let new_shard_entity = game_spawn::shard(
+			*collectable,
+			collected_shards_num,
+			resource_collector.limit,
+			player_camera.is_some(),
+			shard_parent,
+			&game_assets,
+			&mut commands
+		);
 
 		active_collecting.add_new(*collectable, new_shard_entity);
 
@@ -140,20 +110,22 @@ pub fn resource_collecting_control(
 }
 
 pub fn resource_delivery_control(
-		q_handheld_raypick	: Query<(&Raypick, &Handheld), With<CurrentHandheld>>,
+		q_handheld_raypick	: Query<(&Raypick, &Handheld, &Faction), With<CurrentHandheld>>,
 		q_active_collecting	: Query<&ActiveCollecting>,
-		q_base_building		: Query<&BaseBuilding>,
-	mut	collected_resources	: ResMut<CollectedResources>,
+		q_base_building		: Query<&Faction, With<BaseBuilding>>,
+	mut	faction_resources	: ResMut<FactionResources>,
 	mut commands			: Commands
 ) {
-	for (raypick, handheld) in q_handheld_raypick.iter() {
+	for (raypick, handheld, faction) in q_handheld_raypick.iter() {
 		let Some(handheld_owner) = handheld.owner else { continue };
 
 		let Ok(active_collecting) = q_active_collecting.get(handheld_owner) else { continue };
 
-		// return if raypicked entity is not a base building or there is no raypicked entity
+		// return if raypicked entity is not a base building of the same faction or there is no raypicked entity
 		if let Some(raypicked_entity) = raypick.entity {
-			let Ok(_) = q_base_building.get(raypicked_entity) else { continue };
+			let Ok(base_faction) = q_base_building.get(raypicked_entity) else { continue };
+
+			if base_faction != faction { continue }
 		} else {
 			continue;
 		}
@@ -161,6 +133,8 @@ pub fn resource_delivery_control(
 		let purplite_shard_entities = active_collecting.get_shard_entities(CollectableResource::Purplite);
 		let tealite_shard_entities = active_collecting.get_shard_entities(CollectableResource::Tealite);
 
+		let collected_resources = faction_resources.get_mut(*faction);
+
 		collected_resources.purplite += purplite_shard_entities.len();
 		collected_resources.tealite += tealite_shard_entities.len();
 
@@ -171,6 +145,31 @@ pub fn resource_delivery_control(
 	}
 }
 
+pub fn resource_deposit_control(
+	mut q_deposit	: Query<(Entity, &ResourceDeposit, &mut Transform), Changed<ResourceDeposit>>,
+	mut commands	: Commands,
+) {
+	const MIN_SCALE : f32 = 0.6;
+	const SHRINK_STEPS : f32 = 4.0;
+
+	for (deposit_entity, deposit, mut transform) in q_deposit.iter_mut() {
+		if deposit.depleted() {
+			// npcs mining this deposit were already sent elsewhere by retarget_task_resource_collection
+			commands.entity(deposit_entity).despawn_recursive();
+
+			continue;
+		}
+
+		// shrink crystal in steps instead of every shard to not trigger transform updates of the whole scene too often
+		let step = (deposit.fraction() * SHRINK_STEPS).ceil() / SHRINK_STEPS;
+		let scale = Vec3::splat(MIN_SCALE + (1.0 - MIN_SCALE) * step);
+
+		if transform.scale != scale {
+			transform.scale = scale;
+		}
+	}
+}
+
 pub fn player_slowing_control(
 		main_entities		: Res<MainEntities>,
 		time				: Res<Time>,
@@ -207,12 +206,17 @@ pub fn player_slowing_control(
 
 pub fn collected_resource_ui(
 		resource_icon_entities	: Res<ResourceUiEntities>,
-		collected_resources		: Res<CollectedResources>,
+		faction_resources		: Res<FactionResources>,
 		main_entities			: Res<MainEntities>,
+		q_faction				: Query<&Faction>,
 		q_camera_projection		: Query<&Projection, Changed<Projection>>,
 	mut q_text					: Query<&mut Text>,
 	mut q_transform				: Query<&mut Transform>,
 ) {
+	let Ok(player_faction) = q_faction.get(main_entities.player) else { panic!("player entity has no Faction component!") };
+
+	let collected_resources = faction_resources.get(*player_faction);
+
 	if let Ok(mut purplite_text) = q_text.get_mut(resource_icon_entities.purplite_text) {
 		let text = &mut purplite_text.sections[0].value;
 		let num_str = collected_resources.purplite.to_string();
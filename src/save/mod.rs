@@ -0,0 +1,60 @@
+use bevy :: prelude :: *;
+
+use super :: game :: {
+	GameState,
+	MainEntities,
+	ProductionItem,
+};
+
+use super :: ai :: NpcCommand;
+
+use std :: collections :: VecDeque;
+
+use std :: path :: PathBuf;
+
+mod systems;
+
+#[cfg(test)]
+mod tests;
+
+/// Saves bases, placed buildings, deposits, units with their tasks and queued commands and `FactionResources` into a ron scene through `Reflect`
+/// and restores them with `game::spawn` and `buildings::spawn` functions, so gltf scenes, colliders and locators are set up the same way as in game
+pub struct SaveLoadPlugin;
+
+impl Plugin for SaveLoadPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			// field types of saved components have to be registered too, bevy doesn't do it for us
+			.register_type::<Option<Entity>>()
+			.register_type::<Vec<Entity>>()
+			.register_type::<Option<Quat>>()
+			.register_type::<Vec<Vec3>>()
+			.register_type::<Vec<ProductionItem>>()
+			.register_type::<VecDeque<NpcCommand>>()
+
+			.insert_resource(SaveLoadRequest::default())
+
+			.add_systems(OnEnter(GameState::Main), (
+				systems::load_on_start,
+			))
+
+			.add_systems(Update, (
+				systems::save_game,
+				systems::load_game,
+			).chain().run_if(in_state(GameState::Main)))
+
+			.add_systems(Update, (
+				systems::save_load_input.before(systems::save_game),
+			).run_if(in_state(GameState::Main)).run_if(resource_exists::<MainEntities>))
+		;
+	}
+}
+
+pub const QUICKSAVE_PATH : &str = "quicksave.ron";
+
+/// Save and load happen on the next update after request is set
+#[derive(Resource, Default)]
+pub struct SaveLoadRequest {
+	pub save		: Option<PathBuf>,
+	pub load		: Option<PathBuf>,
+}
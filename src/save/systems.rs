@@ -0,0 +1,401 @@
+use bevy :: {
+	prelude :: *,
+	scene :: {
+		ron,
+		serde :: SceneDeserializer,
+	},
+	utils :: HashMap,
+};
+
+use serde :: de :: DeserializeSeed;
+
+use super :: *;
+
+use crate :: options :: LaunchOptions;
+
+use crate :: assets :: GameAssets;
+
+use crate :: units :: { UnitDefinitions, UnitType };
+
+use crate :: buildings :: {
+	BuildingDefinitions,
+	BuildingType,
+	UnderConstruction,
+	spawn as buildings_spawn,
+};
+
+use crate :: game :: {
+	BaseBuilding,
+	ProductionQueue,
+	spawn as game_spawn,
+};
+
+use crate :: handheld :: {
+	Handheld,
+	HandheldOwner,
+};
+
+use crate :: ai :: {
+	NpcMovable,
+	NpcTaskMove,
+	NpcTaskMoveFinished,
+	MovePath,
+	NpcTaskResourceCollection,
+	NpcTaskConstruction,
+	NpcCommand,
+	NpcCommandQueue,
+	RallyPoint,
+};
+
+use crate :: faction :: Faction;
+
+use crate :: resource_collection :: {
+	ActiveCollecting,
+	CollectableResource,
+	FactionResources,
+	ResourceCollectionStage,
+	ResourceCollector,
+	ResourceDeposit,
+};
+
+use std :: any :: TypeId;
+
+type SavedEntityFilter = Or<(With<BaseBuilding>, With<BuildingType>, With<CollectableResource>, With<NpcMovable>)>;
+
+pub fn load_on_start(
+		launch_options	: Res<LaunchOptions>,
+	mut request			: ResMut<SaveLoadRequest>,
+) {
+	request.load = launch_options.load.clone();
+}
+
+pub fn save_load_input(
+		key			: Res<ButtonInput<KeyCode>>,
+	mut request		: ResMut<SaveLoadRequest>,
+) {
+	if key.just_pressed(KeyCode::F5) {
+		request.save = Some(QUICKSAVE_PATH.into());
+	}
+
+	if key.just_pressed(KeyCode::F9) {
+		request.load = Some(QUICKSAVE_PATH.into());
+	}
+}
+
+pub fn save_game(world: &mut World) {
+	let Some(path) = world.resource_mut::<SaveLoadRequest>().save.take() else { return };
+
+	let mut q_saved = world.query_filtered::<Entity, SavedEntityFilter>();
+	let saved_entities = q_saved.iter(world).collect::<Vec<Entity>>();
+
+	let scene = DynamicSceneBuilder::from_world(world)
+		.deny_all()
+		.allow::<Transform>()
+		.allow::<Faction>()
+		.allow::<BaseBuilding>()
+		.allow::<ProductionQueue>()
+		.allow::<RallyPoint>()
+		.allow::<BuildingType>()
+		.allow::<UnderConstruction>()
+		.allow::<CollectableResource>()
+		.allow::<ResourceDeposit>()
+		.allow::<NpcMovable>()
+		.allow::<UnitType>()
+		.allow::<ResourceCollector>()
+		.allow::<ActiveCollecting>()
+		.allow::<NpcTaskResourceCollection>()
+		.allow::<NpcTaskConstruction>()
+		.allow::<MovePath>()
+		.allow::<NpcTaskMove>()
+		.allow::<NpcCommandQueue>()
+		.deny_all_resources()
+		.allow_resource::<FactionResources>()
+		.extract_entities(saved_entities.iter().copied())
+		.extract_resources()
+		.build();
+
+	let serialized = match scene.serialize_ron(&world.resource::<AppTypeRegistry>().0) {
+		Ok(serialized) => serialized,
+		Err(error) => {
+			println!("failed to serialize game state: {}", error);
+			return
+		}
+	};
+
+	match std::fs::write(&path, serialized) {
+		Ok(_) => println!("saved {} entities to {:?}", saved_entities.len(), path),
+		Err(error) => println!("failed to write save file {:?}: {}", path, error),
+	}
+}
+
+pub fn load_game(
+		type_registry		: Res<AppTypeRegistry>,
+		game_assets			: Res<GameAssets>,
+		unit_definitions	: Res<UnitDefinitions>,
+		building_definitions: Res<BuildingDefinitions>,
+		q_saved				: Query<Entity, SavedEntityFilter>,
+		q_handheld_owner	: Query<&HandheldOwner>,
+		q_handheld			: Query<&Handheld>,
+	mut request				: ResMut<SaveLoadRequest>,
+	mut faction_resources	: ResMut<FactionResources>,
+	mut meshes				: ResMut<Assets<Mesh>>,
+	mut materials			: ResMut<Assets<StandardMaterial>>,
+	mut commands			: Commands,
+) {
+	let Some(path) = request.load.take() else { return };
+
+	let serialized = match std::fs::read_to_string(&path) {
+		Ok(serialized) => serialized,
+		Err(error) => {
+			println!("failed to read save file {:?}: {}", path, error);
+			return
+		}
+	};
+
+	let scene = {
+		let type_registry = type_registry.read();
+		let scene_deserializer = SceneDeserializer { type_registry: &type_registry };
+
+		let deserialized = ron::de::Deserializer::from_str(&serialized)
+			.map_err(|error| error.to_string())
+			.and_then(|mut deserializer| scene_deserializer.deserialize(&mut deserializer).map_err(|error| error.to_string()));
+
+		match deserialized {
+			Ok(scene) => scene,
+			Err(error) => {
+				println!("failed to parse save file {:?}: {}", path, error);
+				return
+			}
+		}
+	};
+
+	// every unit and building belongs to a faction, save without it is broken and nothing is replaced by it
+	let without_faction = scene.entities.iter().find(|saved| {
+		let owned = find_saved::<BuildingType>(&saved.components).is_some()
+			|| find_saved::<BaseBuilding>(&saved.components).is_some()
+			|| find_saved::<NpcMovable>(&saved.components).is_some();
+
+		owned && find_saved::<Faction>(&saved.components).is_none()
+	});
+
+	if let Some(saved) = without_faction {
+		println!("failed to load save file {:?}: entity {:?} has no faction", path, saved.entity);
+		return
+	}
+
+	// everything that was there before gets replaced by what's in the save
+	for entity in q_saved.iter() {
+		// drilling particles are not attached to drill so they have to be despawned separately
+		let particles_entity = q_handheld_owner.get(entity).ok()
+			.and_then(|handheld_owner| q_handheld.get(handheld_owner.handheld_entity).ok())
+			.and_then(|handheld| handheld.drilling_particles_entity);
+
+		if let Some(particles_entity) = particles_entity {
+			commands.entity(particles_entity).despawn_recursive();
+		}
+
+		commands.entity(entity).despawn_recursive();
+	}
+
+	// saved entity -> respawned entity, so that svins can be pointed at new bases and deposits
+	let mut entity_map : HashMap<Entity, Entity> = HashMap::new();
+
+	for saved in scene.entities.iter() {
+		let Some(transform) = find_saved::<Transform>(&saved.components) else { continue };
+
+		// buildings placed by player, bases among them, and bases that came with the map
+		let new_entity = if let Some(building_type) = find_saved::<BuildingType>(&saved.components) {
+			let Some(faction) = find_saved::<Faction>(&saved.components) else { continue };
+
+			let definition = building_definitions.get(building_type);
+
+			match find_saved::<UnderConstruction>(&saved.components) {
+				Some(construction) => {
+					let site_entity = buildings_spawn::construction_site(building_type, transform, definition, faction, &game_assets, &mut meshes, &mut materials, &mut commands);
+
+					commands.entity(site_entity).insert(construction);
+
+					site_entity
+				},
+				None => buildings_spawn::building(building_type, transform, definition, faction, &game_assets, &mut meshes, &mut materials, &mut commands),
+			}
+		} else if find_saved::<BaseBuilding>(&saved.components).is_some() {
+			let Some(faction) = find_saved::<Faction>(&saved.components) else { continue };
+
+			game_spawn::base_building(transform, faction, &game_assets, &mut commands)
+		} else if let Some(resource_type) = find_saved::<CollectableResource>(&saved.components) {
+			let Some(deposit) = find_saved::<ResourceDeposit>(&saved.components) else { continue };
+
+			// resource_deposit_control will scale crystal down according to what's left in it
+			let deposit_entity = match resource_type {
+				CollectableResource::Tealite => game_spawn::tealite(transform, deposit.initial, &game_assets, &mut commands),
+				CollectableResource::Purplite => game_spawn::purplite(transform, deposit.initial, &game_assets, &mut commands),
+			};
+
+			commands.entity(deposit_entity).insert(deposit);
+
+			deposit_entity
+		} else {
+			continue
+		};
+
+		// queued units are already paid for
+		if let Some(production_queue) = find_saved::<ProductionQueue>(&saved.components) {
+			commands.entity(new_entity).insert(production_queue);
+		}
+
+		entity_map.insert(saved.entity, new_entity);
+	}
+
+	// rally point can target a deposit that was respawned after the base
+	for saved in scene.entities.iter() {
+		let Some(mut rally_point) = find_saved::<RallyPoint>(&saved.components) else { continue };
+		let Some(base_entity) = entity_map.get(&saved.entity) else { continue };
+
+		rally_point.target_entity = rally_point.target_entity.and_then(|entity| entity_map.get(&entity).copied());
+
+		commands.entity(*base_entity).insert(rally_point);
+	}
+
+	let mut svins_num = 0;
+
+	for saved in scene.entities.iter() {
+		if find_saved::<NpcMovable>(&saved.components).is_none() { continue }
+
+		let Some(transform) = find_saved::<Transform>(&saved.components) else { continue };
+
+		let Some(unit_type) = find_saved::<UnitType>(&saved.components) else {
+			println!("skipping saved npc {:?} without unit type", saved.entity);
+			continue
+		};
+
+		let definition = unit_definitions.get(unit_type);
+		let Some(faction) = find_saved::<Faction>(&saved.components) else { continue };
+
+		// spawn::unit expects a locator on the ground and lifts unit above it, ground can be anywhere on terrain
+		let ground_height = transform.translation.y - definition.collider.half_height - 0.01;
+
+		let spawn_transform = Transform::from_xyz(transform.translation.x, ground_height, transform.translation.z).with_rotation(transform.rotation);
+
+		let base_entity = find_saved::<ResourceCollector>(&saved.components)
+			.and_then(|collector| collector.base_building_entity)
+			.and_then(|entity| entity_map.get(&entity).copied());
+
+		let Some((svin_entity, _svin_collider)) = game_spawn::unit(
+			&spawn_transform,
+			unit_type,
+			definition,
+			faction,
+			&game_assets,
+			base_entity,
+			None,
+			None,
+			&mut commands
+		) else { continue };
+
+		svins_num += 1;
+
+		if let Some(mut command_queue) = find_saved::<NpcCommandQueue>(&saved.components) {
+			// commands pointing at deposits or buildings that are not in the save are dropped
+			command_queue.retain_mut(|command| match command {
+				NpcCommand::Mine(entity) | NpcCommand::Build(entity) => {
+					let Some(new_entity) = entity_map.get(&*entity).copied() else { return false };
+
+					*entity = new_entity;
+
+					true
+				},
+				// npcs are respawned after buildings and deposits, there is nothing to tell whom npc was going to follow
+				NpcCommand::Follow(_) => false,
+				_ => true,
+			});
+
+			commands.entity(svin_entity).insert(command_queue);
+		}
+
+		if let Some(active_collecting) = find_saved::<ActiveCollecting>(&saved.components) {
+			let mut restored_collecting = ActiveCollecting::default();
+
+			for resource_type in [CollectableResource::Tealite, CollectableResource::Purplite] {
+				for _ in 0 .. active_collecting.shards_num_by_type(resource_type) {
+					let shard_index = restored_collecting.total_shards_num();
+					let parent_entity = restored_collecting.get_first_shard_entity().unwrap_or(svin_entity);
+
+					let shard_entity = game_spawn::shard(
+						resource_type,
+						shard_index,
+						definition.capacity,
+						false, // held_by_player
+						parent_entity,
+						&game_assets,
+						&mut commands
+					);
+
+					restored_collecting.add_new(resource_type, shard_entity);
+				}
+			}
+
+			commands.entity(svin_entity).insert(restored_collecting);
+		}
+
+		let mut task = find_saved::<NpcTaskResourceCollection>(&saved.components);
+
+		if let Some(task) = task.as_mut() {
+			task.resource_entity = task.resource_entity.and_then(|entity| entity_map.get(&entity).copied());
+		}
+
+		// without a deposit svin can only finish its way to base, otherwise it stays idle and doesn't keep walking to where deposit was
+		if task.as_ref().is_some_and(|task| task.resource_entity.is_none() && task.stage != ResourceCollectionStage::MovingToBase) { continue }
+
+		let mut construction_task = find_saved::<NpcTaskConstruction>(&saved.components);
+
+		if let Some(task) = construction_task.as_mut() {
+			// same goes for construction site that is not in the save
+			let Some(building_entity) = entity_map.get(&task.building_entity).copied() else { continue };
+
+			task.building_entity = building_entity;
+		}
+
+		if let (Some(mut move_path), Some(task_move)) = (find_saved::<MovePath>(&saved.components), find_saved::<NpcTaskMove>(&saved.components)) {
+			move_path.forget_target_entity();
+
+			commands.entity(svin_entity).insert((move_path, task_move));
+		}
+
+		if let Some(task) = construction_task {
+			// worker that was drilling "arrives" again so that update_task_construction activates its drill
+			if find_saved::<MovePath>(&saved.components).is_none() {
+				commands.entity(svin_entity).insert(NpcTaskMoveFinished);
+			}
+
+			commands.entity(svin_entity).insert(task);
+
+			continue;
+		}
+
+		let Some(mut task) = task else { continue };
+
+		// drill is not spawned yet so it can't be activated here. Instead svin "arrives" at deposit again
+		// and update_task_resource_collection switches it to collecting and activates the drill
+		if task.stage == ResourceCollectionStage::CollectingResource {
+			task.stage = ResourceCollectionStage::MovingToResource;
+
+			commands.entity(svin_entity).insert(NpcTaskMoveFinished);
+		}
+
+		commands.entity(svin_entity).insert(task);
+	}
+
+	if let Some(saved_resources) = find_saved::<FactionResources>(&scene.resources) {
+		*faction_resources = saved_resources;
+	}
+
+	println!("loaded {} buildings and deposits and {} svins from {:?}", entity_map.len(), svins_num, path);
+}
+
+/// Deserialized components and resources are dynamic types, so they are matched by the type they represent
+fn find_saved<T: FromReflect>(saved: &[Box<dyn Reflect>]) -> Option<T> {
+	saved.iter()
+		.find(|value| value.get_represented_type_info().map(|info| info.type_id()) == Some(TypeId::of::<T>()))
+		.and_then(|value| T::from_reflect(value.as_reflect()))
+}
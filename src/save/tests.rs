@@ -0,0 +1,188 @@
+use bevy :: {
+	prelude :: *,
+	ecs :: system :: RunSystemOnce,
+};
+
+use super :: {
+	systems,
+	SaveLoadRequest,
+};
+
+use crate :: test_support :: TestApp;
+
+use crate :: ai :: {
+	NpcCommand,
+	NpcCommandQueue,
+	NpcMovable,
+};
+
+use crate :: game :: BaseBuilding;
+
+use crate :: faction :: Faction;
+
+use crate :: resource_collection :: {
+	ActiveCollecting,
+	CollectableResource,
+	FactionResources,
+	ResourceDeposit,
+};
+
+#[derive(PartialEq, Debug)]
+struct Snapshot {
+	bases				: usize,
+	svins				: usize,
+	carried_shards		: usize,
+	tealite_remaining	: Vec<usize>,
+	purplite_remaining	: Vec<usize>,
+	collected			: (usize, usize),
+}
+
+fn snapshot(world: &mut World) -> Snapshot {
+	let bases = world.query_filtered::<(), With<BaseBuilding>>().iter(world).count();
+	let svins = world.query_filtered::<(), With<NpcMovable>>().iter(world).count();
+
+	let carried_shards = world.query_filtered::<&ActiveCollecting, With<NpcMovable>>().iter(world)
+		.map(|active_collecting| active_collecting.total_shards_num())
+		.sum();
+
+	let mut remaining = |resource_type: CollectableResource| {
+		let mut remaining = world.query::<(&CollectableResource, &ResourceDeposit)>().iter(world)
+			.filter(|(deposit_type, _)| **deposit_type == resource_type)
+			.map(|(_, deposit)| deposit.remaining)
+			.collect::<Vec<usize>>();
+		remaining.sort();
+		remaining
+	};
+
+	let tealite_remaining = remaining(CollectableResource::Tealite);
+	let purplite_remaining = remaining(CollectableResource::Purplite);
+
+	let collected = world.resource::<FactionResources>().total();
+
+	Snapshot {
+		bases,
+		svins,
+		carried_shards,
+		tealite_remaining,
+		purplite_remaining,
+		collected : (collected.tealite, collected.purplite),
+	}
+}
+
+#[test]
+fn save_and_load_restores_svins_deposits_and_carried_shards() {
+	let mut test_app = TestApp::new();
+
+	let tealite_entity = test_app.deposit(CollectableResource::Tealite);
+	let purplite_entity = test_app.deposit(CollectableResource::Purplite);
+
+	test_app.queue_svins(&[(2, tealite_entity), (1, purplite_entity)]);
+
+	// save when something was delivered already and something is being carried
+	let ready_to_save = test_app.step_until(120.0, |world| {
+		world.resource::<FactionResources>().total().tealite > 0 &&
+		world.query::<&ActiveCollecting>().iter(world).any(|active_collecting| active_collecting.total_shards_num() > 0)
+	});
+	assert!(ready_to_save, "svins didn't deliver anything in time");
+
+	let save_path = std::env::temp_dir().join(format!("svincraft_save_test_{}.ron", std::process::id()));
+
+	let saved = snapshot(&mut test_app.app.world);
+
+	test_app.app.world.resource_mut::<SaveLoadRequest>().save = Some(save_path.clone());
+	test_app.app.world.run_system_once(systems::save_game);
+
+	assert!(save_path.exists(), "save file wasn't written");
+
+	// let the game go on so that there is something to overwrite by loading
+	test_app.step_seconds(10.0);
+
+	test_app.app.world.resource_mut::<SaveLoadRequest>().load = Some(save_path.clone());
+	test_app.app.world.run_system_once(systems::load_game);
+
+	std::fs::remove_file(&save_path).ok();
+
+	assert_eq!(snapshot(&mut test_app.app.world), saved);
+
+	// restored svins pick up where they left off
+	let collected_before = saved.collected.0 + saved.collected.1;
+	let delivered = test_app.step_until(90.0, |world| {
+		let collected = world.resource::<FactionResources>().total();
+		collected.tealite + collected.purplite > collected_before
+	});
+	assert!(delivered, "svins didn't deliver anything after loading");
+}
+
+#[test]
+fn save_and_load_keeps_queued_commands() {
+	let mut test_app = TestApp::new();
+
+	let tealite_entity = test_app.deposit(CollectableResource::Tealite);
+	let purplite_entity = test_app.deposit(CollectableResource::Purplite);
+
+	test_app.queue_svins(&[(1, tealite_entity)]);
+
+	let spawned = test_app.step_until(10.0, |world| world.query_filtered::<(), With<NpcMovable>>().iter(world).next().is_some());
+	assert!(spawned, "svin wasn't spawned");
+
+	let svin_entity = test_app.svins()[0];
+
+	let move_position = Vec3::new(5.0, 0.0, 5.0);
+
+	let Some(mut command_queue) = test_app.app.world.get_mut::<NpcCommandQueue>(svin_entity) else { panic!("svin has no command queue!") };
+	command_queue.extend([NpcCommand::Mine(purplite_entity), NpcCommand::ReturnCargo, NpcCommand::Move(move_position)]);
+
+	let save_path = std::env::temp_dir().join(format!("svincraft_save_queue_test_{}.ron", std::process::id()));
+
+	test_app.app.world.resource_mut::<SaveLoadRequest>().save = Some(save_path.clone());
+	test_app.app.world.run_system_once(systems::save_game);
+
+	test_app.app.world.resource_mut::<SaveLoadRequest>().load = Some(save_path.clone());
+	test_app.app.world.run_system_once(systems::load_game);
+
+	std::fs::remove_file(&save_path).ok();
+
+	let svins = test_app.svins();
+	assert_eq!(svins.len(), 1);
+
+	let Some(command_queue) = test_app.app.world.get::<NpcCommandQueue>(svins[0]) else { panic!("loaded svin has no command queue!") };
+	let restored = command_queue.iter().copied().collect::<Vec<NpcCommand>>();
+
+	assert_eq!(restored.len(), 3, "queued commands were lost: {:?}", restored);
+
+	// deposit is respawned by loading, so queued mining has to point at the new one
+	let NpcCommand::Mine(restored_purplite_entity) = restored[0] else { panic!("first queued command should be mining, got {:?}", restored[0]) };
+	assert_ne!(restored_purplite_entity, purplite_entity);
+	assert!(test_app.deposits(CollectableResource::Purplite).contains(&restored_purplite_entity));
+
+	assert_eq!(restored[1 ..], [NpcCommand::ReturnCargo, NpcCommand::Move(move_position)]);
+}
+
+#[test]
+fn loading_unit_without_faction_fails_and_keeps_the_game() {
+	let mut test_app = TestApp::new();
+
+	let tealite_entity = test_app.deposit(CollectableResource::Tealite);
+
+	test_app.queue_svins(&[(1, tealite_entity)]);
+
+	let spawned = test_app.step_until(10.0, |world| world.query_filtered::<(), With<NpcMovable>>().iter(world).next().is_some());
+	assert!(spawned, "svin wasn't spawned");
+
+	let svin_entity = test_app.svins()[0];
+
+	test_app.app.world.entity_mut(svin_entity).remove::<Faction>();
+
+	let save_path = std::env::temp_dir().join(format!("svincraft_save_faction_test_{}.ron", std::process::id()));
+
+	test_app.app.world.resource_mut::<SaveLoadRequest>().save = Some(save_path.clone());
+	test_app.app.world.run_system_once(systems::save_game);
+
+	test_app.app.world.resource_mut::<SaveLoadRequest>().load = Some(save_path.clone());
+	test_app.app.world.run_system_once(systems::load_game);
+
+	std::fs::remove_file(&save_path).ok();
+
+	assert_eq!(test_app.svins(), vec![svin_entity], "broken save shouldn't replace anything");
+	assert!(test_app.deposits(CollectableResource::Tealite).contains(&tealite_entity), "deposits shouldn't be respawned by broken save");
+}
@@ -5,7 +5,6 @@ use bevy :: {
 };
 
 use bevy_rapier3d :: prelude :: *;
-use polyanya :: Triangulation;
 use bevy_hanabi :: prelude :: *;
 use iyes_perf_ui :: prelude :: *;
 
@@ -19,13 +18,24 @@ use super :: assets :: {
 	GameAssets,
 };
 
-use super :: ai :: PolyanyaResource;
-
-use crate :: game :: spawn as game_spawn;
+use super :: ai :: {
+	PolyanyaResource,
+	navmesh :: { NavmeshTiles, NAVMESH_TILE_SIZE },
+	layers :: LevelNavmesh,
+};
 
 use crate :: handheld :: HandheldOwner;
 
-mod spawn;
+use crate :: units :: UnitDefinitions;
+
+use crate :: map :: {
+	Map,
+	Terrain,
+	Level,
+	spawn as map_spawn,
+};
+
+pub mod spawn;
 use spawn as setup_spawn;
 
 pub struct SetupPlugin;
@@ -64,11 +74,11 @@ impl Plugin for SetupPlugin {
 }
 
 
-pub const GROUND_SIZE : f32 = 1000.0;
-pub const GROUND_HSIZE : f32 = GROUND_SIZE / 2.0;
-
 fn setup(
 		cubemap		: Res<Cubemap>,
+		maps		: Res<Assets<Map>>,
+		images		: Res<Assets<Image>>,
+		unit_definitions: Res<UnitDefinitions>,
 	mut game_state	: ResMut<NextState<GameState>>,
 	mut game_assets	: ResMut<GameAssets>,
 	mut effects		: ResMut<Assets<EffectAsset>>,
@@ -76,34 +86,46 @@ fn setup(
 	mut materials	: ResMut<Assets<StandardMaterial>>,
 	mut commands	: Commands,
 ) {
+	let Some(map) = maps.get(&game_assets.map) else { panic!("map should be loaded before setup!") };
+
+	let heightmap = game_assets.heightmap.as_ref().and_then(|handle| images.get(handle));
+
+	let terrain = Terrain::new(&map.ground, map.terrain.as_ref(), heightmap);
+
+	let levels = map.levels.iter().map(|map_level| Level::new(map_level, &terrain)).collect::<Vec<Level>>();
+
 	setup_spawn::light(&mut commands);
 
-	setup_spawn::ground_plane(&mut meshes, &mut materials, &mut commands);
+	setup_spawn::ground(&terrain, &mut meshes, &mut materials, &mut commands);
 
-	game_spawn::base_building(Transform::IDENTITY, &game_assets, &mut commands);
+	map_spawn::map_contents(map, &[], &terrain, &levels, &game_assets, &mut meshes, &mut materials, &mut commands);
 
-	game_spawn::tealite(Transform::from_xyz(3.0, 0.0, 15.0), &game_assets, &mut commands);
-	game_spawn::purplite(Transform::from_xyz(-3.0, 0.0, 15.0), &game_assets, &mut commands);
+	let player_spawn = map.player_spawn + Vec3::Y * terrain.height_at(map.player_spawn.xz());
 
 	let resource_ui_entities = setup_spawn::resource_ui(&game_assets, &mut commands);
 
 
-	let player_entity = setup_spawn::player_entity(&mut commands);
+	let player_entity = setup_spawn::player_entity(player_spawn, &unit_definitions.player, &mut commands);
 
-	let drill_entity = setup_spawn::player_drill(player_entity, &game_assets, &mut commands);
+	let drill_entity = setup_spawn::player_drill(player_entity, map.player_faction, &game_assets, &mut commands);
 
-	commands.entity(player_entity).insert(HandheldOwner { handheld_entity: drill_entity });
+	commands.entity(player_entity).insert((
+		HandheldOwner { handheld_entity: drill_entity },
+		map.player_faction,
+	));
 
 	let crosshair_entity = setup_spawn::crosshair(&mut meshes, &mut materials, &mut commands);
 
 	let build_menu_entity = setup_spawn::build_menu(&mut commands);
 
+	let selection_box_entity = setup_spawn::selection_box(&mut commands);
+
 	// cameras
 	let player_camera_entity = setup_spawn::player_camera(player_entity, &cubemap, &mut commands);
 
 	let ui_camera_entity = setup_spawn::ui_camera(&mut commands);
 
-	let orbit_camera_entity = setup_spawn::orbit_camera(&cubemap, &mut commands);
+	let orbit_camera_entity = setup_spawn::orbit_camera(player_spawn, &cubemap, &mut commands);
 
 	commands.entity(player_camera_entity).push_children(&[
 		drill_entity,
@@ -115,19 +137,15 @@ fn setup(
 		crosshair_entity
 	]);
 
-	// basic navmesh for plane
-	let polyanya_triangulation = Triangulation::from_outer_edges(&[
-		Vec2::new(-GROUND_HSIZE, -GROUND_HSIZE),
-		Vec2::new(-GROUND_HSIZE,  GROUND_HSIZE),
-		Vec2::new( GROUND_HSIZE,  GROUND_HSIZE),
-		Vec2::new( GROUND_HSIZE, -GROUND_HSIZE),
-	]);
+	let (polyanya, navmesh_tiles) = terrain_navmesh(&terrain, levels);
+
+	commands.insert_resource(polyanya);
 
-	let Some(mut navmesh) = polyanya_triangulation.as_navmesh() else { panic!("navmesh building failed!") };
+	commands.insert_resource(navmesh_tiles);
 
-	navmesh.bake();
+	commands.insert_resource(map.ground);
 
-	commands.insert_resource(PolyanyaResource { mesh: navmesh });
+	commands.insert_resource(terrain);
 
 	// particle effects
 	let resource_drilling_effect = create_resource_drilling_effect(&mut effects);
@@ -160,12 +178,48 @@ fn setup(
 		ui_camera		: ui_camera_entity,
 		orbit_camera	: orbit_camera_entity,
 		build_menu		: build_menu_entity,
+		selection_box	: selection_box_entity,
 	});
 
 	// indicating that we're done with setup system and game is ready to run
 	game_state.set(GameState::Main);
 }
 
+/// Navmesh of terrain and levels without obstacles and tiles ground navmesh was built from. Steep slopes, ramps and ground
+/// under plateaus are blocked for the whole game, obstacles are added to tiles by `update_navmesh_obstacles` once their colliders appear
+pub fn terrain_navmesh(terrain: &Terrain, levels: Vec<Level>) -> (PolyanyaResource, NavmeshTiles) {
+	let mut navmesh_tiles = NavmeshTiles::new(&terrain.ground, NAVMESH_TILE_SIZE);
+
+	for area in terrain.steep_areas() {
+		navmesh_tiles.block_area(area);
+	}
+
+	// ground under bridge stays walkable, ramps are entered only through their links
+	for level in levels.iter() {
+		if !level.bridge {
+			navmesh_tiles.block_area(level.deck);
+		}
+
+		for ramp in level.ramps.iter() {
+			navmesh_tiles.block_area(ramp.area);
+		}
+	}
+
+	let Some(navmesh) = navmesh_tiles.rebuild_now() else { panic!("navmesh building failed!") };
+
+	let levels = levels.into_iter()
+		.map(|level| {
+			let deck = level.deck;
+
+			let Some(level_navmesh) = LevelNavmesh::new(level) else { panic!("navmesh building failed for level {:?}!", deck) };
+
+			level_navmesh
+		})
+		.collect();
+
+	(PolyanyaResource::new(navmesh, levels), navmesh_tiles)
+}
+
 fn create_resource_drilling_effect(effects: &mut Assets<EffectAsset>) -> Handle<EffectAsset> {
 	let mut size_gradient = Gradient::new();
 	size_gradient.add_key(0.0, Vec2::new(0.01, 0.002));
@@ -16,7 +16,11 @@ use bevy_fps_controller :: controller :: *;
 use bevy_panorbit_camera :: PanOrbitCamera;
 use bevy_scene_hook :: { SceneHook, HookedSceneBundle };
 
-use super :: GROUND_SIZE;
+use crate :: map :: { Terrain, GROUND_GROUP };
+
+use crate :: units :: PlayerDefinition;
+
+use crate :: faction :: Faction;
 
 use crate :: game :: {
 	PlayerState,
@@ -37,8 +41,6 @@ use crate :: resource_collection :: {
 
 use std :: f32 :: consts :: PI;
 
-pub const PLAYER_SPAWN_POINT: Vec3 = Vec3::new(0.0, 1.0, 20.0);
-
 pub fn light(commands: &mut Commands) {
 	commands.spawn((
 		DirectionalLightBundle {
@@ -67,21 +69,49 @@ pub fn light(commands: &mut Commands) {
 	));
 }
 
-pub fn ground_plane(
+/// Flat terrain is a plane with halfspace collider, otherwise terrain mesh with matching heightfield collider
+pub fn ground(
+	terrain		: &Terrain,
 	meshes		: &mut Assets<Mesh>,
 	materials	: &mut Assets<StandardMaterial>,
 	commands	: &mut Commands
 ) {
+	let ground = terrain.ground;
+
+	if terrain.is_flat() {
+		commands.spawn((
+			Name::new("Ground Plane"),
+			PbrBundle {
+				mesh : meshes.add(Plane3d::default().mesh().size(ground.size().x, ground.size().y)),
+				material : materials.add(Color::GRAY),
+				transform : Transform::from_xyz(ground.center().x, terrain.heights[0], ground.center().y),
+				..default()
+			},
+			RigidBody::Fixed,
+			Collider::halfspace(Vec3::Y).unwrap(),
+			CollisionGroups::new(GROUND_GROUP, Group::ALL),
+		));
+
+		return
+	}
+
+	// terrain mesh is in world coordinates while heightfield is centered on its entity, so collider goes into a child
 	commands.spawn((
-		Name::new("Ground Plane"),
+		Name::new("Terrain"),
 		PbrBundle {
-			mesh : meshes.add(Plane3d::default().mesh().size(GROUND_SIZE, GROUND_SIZE)),
+			mesh : meshes.add(terrain.mesh()),
 			material : materials.add(Color::GRAY),
 			..default()
 		},
 		RigidBody::Fixed,
-		Collider::halfspace(Vec3::Y).unwrap(),
-	));
+	))
+	.with_children(|parent| {
+		parent.spawn((
+			TransformBundle::from_transform(Transform::from_xyz(ground.center().x, 0.0, ground.center().y)),
+			terrain.collider(),
+			CollisionGroups::new(GROUND_GROUP, Group::ALL),
+		));
+	});
 }
 
 pub fn resource_ui(
@@ -170,7 +200,11 @@ pub fn resource_ui(
 	}
 }
 
-pub fn player_entity(commands	: &mut Commands) -> Entity {
+pub fn player_entity(
+	spawn_point	: Vec3,
+	definition	: &PlayerDefinition,
+	commands	: &mut Commands
+) -> Entity {
 	commands.spawn((
 		Collider::capsule(Vec3::Y * 0.5, Vec3::Y * 1.0, 0.5),
 		Friction {
@@ -197,12 +231,16 @@ pub fn player_entity(commands	: &mut Commands) -> Entity {
 		},
 		FpsController {
 			air_acceleration: 80.0,
+			// Ctrl + digit stores control group, ducking with it would move camera every time
+			key_crouch: KeyCode::KeyZ,
+			// F makes selected npcs follow player, flying by default would toggle with it
+			key_fly: KeyCode::KeyN,
 			..default()
 		},
 	))
 	.insert(PlayerState::default())
-	.insert(ResourceCollector { limit : 5, ..default() } )
-	.insert(TransformBundle::from_transform(Transform::from_translation(PLAYER_SPAWN_POINT)))
+	.insert(ResourceCollector { limit : definition.capacity, mining_interval : definition.mining_interval, ..default() } )
+	.insert(TransformBundle::from_transform(Transform::from_translation(spawn_point)))
 	.insert(CameraConfig {
 		height_offset: -0.8,
 		radius_scale: 0.75,
@@ -213,6 +251,7 @@ pub fn player_entity(commands	: &mut Commands) -> Entity {
 
 pub fn player_drill(
 	player_entity	: Entity,
+	faction			: Faction,
 	game_assets		: &GameAssets,
 	commands		: &mut Commands,
 ) -> Entity {
@@ -222,7 +261,7 @@ pub fn player_drill(
 		..default()
 	};
 
-	game_spawn::drill(&player_drill_transform, Some(player_entity), game_assets, commands)
+	game_spawn::drill(&player_drill_transform, Some(player_entity), faction, game_assets, commands)
 }
 
 pub fn crosshair(
@@ -326,13 +365,14 @@ pub fn ui_camera(
 }
 
 pub fn orbit_camera(
+	look_at		: Vec3,
 	cubemap		: &Cubemap,
 	commands	: &mut Commands
 ) -> Entity {
 	commands.spawn((
 		Camera3dBundle {
 			camera : Camera { hdr : true, is_active : false, ..default() },
-			transform : Transform::from_xyz(0.0, 6.0, 20.0).looking_at(PLAYER_SPAWN_POINT, Vec3::Y),
+			transform : Transform::from_xyz(look_at.x, look_at.y + 25.0, look_at.z + 15.0).looking_at(look_at, Vec3::Y),
 			tonemapping : Tonemapping::TonyMcMapface,
 			color_grading : ColorGrading {
 				post_saturation: 1.2,
@@ -341,7 +381,14 @@ pub fn orbit_camera(
 			exposure: Exposure { ev100: 6.0 },
 			..default()
 		},
-		PanOrbitCamera { ..default() },
+		// left mouse button is taken by selection, so orbiting and panning are done like in Blender
+		PanOrbitCamera {
+			button_orbit	: MouseButton::Middle,
+			button_pan		: MouseButton::Middle,
+			modifier_pan	: Some(KeyCode::ShiftLeft),
+			..default()
+		},
+		Raypick { from_cursor : true, ..default() },
 		BloomSettings::NATURAL,
 		// Skybox { image : cubemap.image_handle.clone_weak(), brightness : 150.0 },
 		EnvironmentMapLight {
@@ -368,4 +415,23 @@ pub fn build_menu(
 			..default()
 		}),
 	)).id()
-}
\ No newline at end of file
+}
+
+/// Marquee drawn while dragging selection in pan-orbit camera, `selection_box_draw` moves and resizes it
+pub fn selection_box(
+	commands		: &mut Commands
+) -> Entity {
+	commands.spawn(
+		NodeBundle {
+			style : Style {
+				position_type	: PositionType::Absolute,
+				border			: UiRect::all(Val::Px(1.0)),
+				..default()
+			},
+			background_color	: Color::hex("26a8ff").unwrap().with_a(0.15).into(),
+			border_color		: Color::hex("26a8ff").unwrap().into(),
+			visibility			: Visibility::Hidden,
+			..default()
+		},
+	).id()
+}
@@ -0,0 +1,350 @@
+//! Deterministic test harness around headless mode. Builds the same `App` as `--headless` does (base at the origin,
+//! tealite and purplite deposits, no svins), steps it with fixed timestep and keeps track of every svin's
+//! `ResourceCollectionStage` so scenario tests can assert on transitions, deliveries and stuck npcs.
+
+use bevy :: {
+	prelude :: *,
+	utils :: HashMap,
+};
+
+use bevy_rapier3d :: prelude :: *;
+
+use crate :: options :: LaunchOptions;
+
+use crate :: headless :: { HeadlessPlugin, TIMESTEP };
+
+use crate :: assets :: { AssetsPlugin, GameAssets };
+
+use crate :: map :: { MapPlugin, DEFAULT_MAP_PATH };
+
+use crate :: buildings :: BuildingsPlugin;
+
+use crate :: units :: { UnitsPlugin, UnitDefinitions, UnitType };
+
+use crate :: handheld :: HandheldPlugin;
+
+use crate :: save :: SaveLoadPlugin;
+
+use crate :: faction :: { FactionPlugin, Faction };
+
+use crate :: commander :: CommanderPlugin;
+
+use crate :: ai :: {
+	AiPlugin,
+	Locator,
+	NpcMovable,
+	NpcSpawnTaskResourceCollection,
+	NpcTaskResourceCollection,
+};
+
+use crate :: game :: {
+	GamePlugin,
+	GameState,
+	BaseBuilding,
+	spawn as game_spawn,
+};
+
+use crate :: resource_collection :: {
+	ResourceCollectionPlugin,
+	ResourceCollectionStage,
+	CollectableResource,
+	CollectedResources,
+	FactionResources,
+	ResourceDeposit,
+};
+
+use std :: time :: { Duration, Instant };
+
+const LOADING_TIMEOUT : Duration = Duration::from_secs(120);
+
+pub struct TestApp {
+	pub app : App,
+}
+
+#[derive(Default, Debug)]
+pub struct SvinRecord {
+	pub stages				: Vec<ResourceCollectionStage>,
+	pub ticks_in_stage		: u32,
+	pub max_ticks_in_stage	: u32,
+	pub deliveries			: usize,
+}
+
+#[derive(Resource, Default)]
+pub struct SvinTracker(pub HashMap<Entity, SvinRecord>);
+
+/// Deposits have to be spawned from a system like it happens in game, otherwise locators and navmesh obstacles
+/// get collected before the scene is spawned
+#[derive(Resource, Default)]
+struct DepositSpawnQueue(Vec<(CollectableResource, Vec3, usize)>);
+
+impl TestApp {
+	pub fn new() -> Self {
+		Self::with_map(DEFAULT_MAP_PATH)
+	}
+
+	/// Same app on another map from `assets/maps`, map's own units are spawned too
+	pub fn with_map(map: &str) -> Self {
+		let mut app = App::new();
+
+		app
+			.add_plugins(HeadlessPlugin)
+			.add_plugins((
+				AssetsPlugin,
+				MapPlugin,
+				UnitsPlugin,
+				BuildingsPlugin,
+				HandheldPlugin,
+				ResourceCollectionPlugin,
+				AiPlugin,
+				GamePlugin,
+				FactionPlugin,
+				SaveLoadPlugin,
+				CommanderPlugin,
+			))
+			.add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+
+			.insert_resource(LaunchOptions { headless: true, svins: 0, map: map.to_string(), ..default() })
+			.init_resource::<SvinTracker>()
+			.init_resource::<DepositSpawnQueue>()
+
+			.add_systems(Update, spawn_queued_deposits.run_if(in_state(GameState::Main)))
+			.add_systems(Last, track_svins.run_if(in_state(GameState::Main)))
+		;
+
+		app.finish();
+		app.cleanup();
+
+		let mut test_app = Self { app };
+
+		let loading_started = Instant::now();
+
+		while *test_app.app.world.resource::<State<GameState>>().get() != GameState::Main {
+			if loading_started.elapsed() > LOADING_TIMEOUT { panic!("assets were not loaded in {:?}!", LOADING_TIMEOUT) }
+
+			test_app.app.update();
+
+			std::thread::sleep(Duration::from_millis(1));
+		}
+
+		// let scenes spawn and navmesh get baked around them
+		test_app.step(10);
+
+		test_app
+	}
+
+	pub fn step(&mut self, ticks: u32) {
+		for _ in 0 .. ticks {
+			self.app.update();
+		}
+	}
+
+	pub fn step_seconds(&mut self, seconds: f32) {
+		self.step(seconds_to_ticks(seconds));
+	}
+
+	/// Returns `true` as soon as `condition` is met or `false` if it wasn't met after `max_seconds`
+	pub fn step_until(&mut self, max_seconds: f32, mut condition: impl FnMut(&mut World) -> bool) -> bool {
+		for _ in 0 .. seconds_to_ticks(max_seconds) {
+			if condition(&mut self.app.world) { return true }
+
+			self.app.update();
+		}
+
+		condition(&mut self.app.world)
+	}
+
+	pub fn base(&mut self) -> Entity {
+		let mut q_base = self.app.world.query_filtered::<Entity, With<BaseBuilding>>();
+
+		let Ok(base_entity) = q_base.get_single(&self.app.world) else { panic!("expected exactly one base building!") };
+
+		base_entity
+	}
+
+	pub fn deposits(&mut self, resource_type: CollectableResource) -> Vec<Entity> {
+		let mut q_deposit = self.app.world.query::<(Entity, &CollectableResource)>();
+
+		q_deposit.iter(&self.app.world)
+			.filter(|(_, deposit_type)| **deposit_type == resource_type)
+			.map(|(entity, _)| entity)
+			.collect()
+	}
+
+	pub fn deposit(&mut self, resource_type: CollectableResource) -> Entity {
+		let Some(deposit_entity) = self.deposits(resource_type).first().copied() else { panic!("there is no {:?} deposit!", resource_type) };
+
+		deposit_entity
+	}
+
+	pub fn spawn_deposit(&mut self, resource_type: CollectableResource, position: Vec3, amount: usize) -> Entity {
+		let existing = self.deposits(resource_type);
+
+		self.app.world.resource_mut::<DepositSpawnQueue>().0.push((resource_type, position, amount));
+
+		self.step(10);
+
+		let Some(new_deposit) = self.deposits(resource_type).into_iter().find(|entity| !existing.contains(entity)) else { panic!("{:?} deposit wasn't spawned!", resource_type) };
+
+		new_deposit
+	}
+
+	pub fn set_deposit_amount(&mut self, deposit_entity: Entity, amount: usize) {
+		self.app.world.entity_mut(deposit_entity).insert(ResourceDeposit::new(amount));
+	}
+
+	pub fn deposit_exists(&self, deposit_entity: Entity) -> bool {
+		self.app.world.get_entity(deposit_entity).is_some()
+	}
+
+	/// Svins are spawned by base building on free spawn locators and immediately start collecting from given deposit
+	pub fn queue_svins(&mut self, batches: &[(usize, Entity)]) {
+		let base_entity = self.base();
+
+		self.app.world.entity_mut(base_entity).insert(NpcSpawnTaskResourceCollection { queue: batches.to_vec() });
+	}
+
+	pub fn locators(&mut self, container_entity: Entity, locator: Locator) -> Vec<(Entity, GlobalTransform)> {
+		let mut q_children = self.app.world.query::<&Children>();
+		let mut q_locator = self.app.world.query::<(&Locator, &GlobalTransform)>();
+
+		let descendants = {
+			let mut descendants = Vec::new();
+			let mut stack = vec![container_entity];
+			while let Some(entity) = stack.pop() {
+				if let Ok(children) = q_children.get(&self.app.world, entity) {
+					stack.extend(children.iter().copied());
+					descendants.extend(children.iter().copied());
+				}
+			}
+			descendants
+		};
+
+		descendants.into_iter()
+			.filter_map(|entity| {
+				let Ok((entity_locator, transform)) = q_locator.get(&self.app.world, entity) else { return None };
+				(*entity_locator == locator).then(|| (entity, *transform))
+			})
+			.collect()
+	}
+
+	/// Static collider with svin dimensions, enough to make `spawn::unit` fail at this position
+	pub fn spawn_blocker(&mut self, position: Vec3) -> Entity {
+		let svin_collider = self.unit_definitions().get(UnitType::Svin).collider;
+
+		self.app.world.spawn((
+			Name::new("Test Blocker"),
+			TransformBundle::from_transform(Transform::from_translation(position + Vec3::Y * (svin_collider.half_height + 0.01))),
+			RigidBody::Fixed,
+			Collider::cylinder(svin_collider.half_height, svin_collider.radius),
+		)).id()
+	}
+
+	pub fn despawn(&mut self, entity: Entity) {
+		self.app.world.entity_mut(entity).despawn_recursive();
+	}
+
+	pub fn svins(&mut self) -> Vec<Entity> {
+		let mut q_svin = self.app.world.query_filtered::<Entity, With<NpcMovable>>();
+
+		q_svin.iter(&self.app.world).collect()
+	}
+
+	pub fn task(&self, svin_entity: Entity) -> Option<&NpcTaskResourceCollection> {
+		self.app.world.get::<NpcTaskResourceCollection>(svin_entity)
+	}
+
+	pub fn unit_definitions(&self) -> &UnitDefinitions {
+		self.app.world.resource::<UnitDefinitions>()
+	}
+
+	/// What player's faction has collected, map bases belong to it unless map says otherwise
+	pub fn collected(&self) -> CollectedResources {
+		self.app.world.resource::<FactionResources>().get(Faction::default())
+	}
+
+	pub fn record(&self, svin_entity: Entity) -> &SvinRecord {
+		let Some(record) = self.app.world.resource::<SvinTracker>().0.get(&svin_entity) else { panic!("svin {:?} was never tracked!", svin_entity) };
+
+		record
+	}
+
+	pub fn total_deliveries(&self) -> usize {
+		self.app.world.resource::<SvinTracker>().0.values().map(|record| record.deliveries).sum()
+	}
+
+	/// Checks that svin went through a full collection cycle: to resource, collecting, to base and back to resource
+	pub fn assert_full_cycle(&self, svin_entity: Entity) {
+		use ResourceCollectionStage :: *;
+
+		let record = self.record(svin_entity);
+
+		let cycle = [MovingToResource, CollectingResource, MovingToBase, MovingToResource];
+
+		let full_cycle_found = record.stages.windows(cycle.len()).any(|window| *window == cycle);
+
+		assert!(full_cycle_found, "svin {:?} didn't go through full collection cycle, stages: {:?}", svin_entity, record.stages);
+	}
+
+	/// Svin is considered stuck if it spent more than `max_seconds` in one stage of resource collection
+	pub fn assert_no_svin_stuck(&self, max_seconds: f32) {
+		let max_ticks = seconds_to_ticks(max_seconds);
+
+		for (svin_entity, record) in self.app.world.resource::<SvinTracker>().0.iter() {
+			assert!(
+				record.max_ticks_in_stage <= max_ticks,
+				"svin {:?} was stuck for {:.1} seconds, stages: {:?}",
+				svin_entity,
+				record.max_ticks_in_stage as f32 * TIMESTEP,
+				record.stages
+			);
+		}
+	}
+}
+
+pub fn seconds_to_ticks(seconds: f32) -> u32 {
+	(seconds / TIMESTEP).ceil() as u32
+}
+
+fn spawn_queued_deposits(
+		game_assets	: Res<GameAssets>,
+	mut queue		: ResMut<DepositSpawnQueue>,
+	mut commands	: Commands,
+) {
+	for (resource_type, position, amount) in queue.0.drain(..) {
+		let transform = Transform::from_translation(position);
+
+		match resource_type {
+			CollectableResource::Tealite => game_spawn::tealite(transform, amount, &game_assets, &mut commands),
+			CollectableResource::Purplite => game_spawn::purplite(transform, amount, &game_assets, &mut commands),
+		};
+	}
+}
+
+fn track_svins(
+		q_svin		: Query<(Entity, Option<&NpcTaskResourceCollection>), With<NpcMovable>>,
+	mut tracker		: ResMut<SvinTracker>,
+) {
+	for (svin_entity, task) in q_svin.iter() {
+		let record = tracker.0.entry(svin_entity).or_default();
+
+		// idle svins are not stuck, they just have nothing to do
+		let Some(task) = task else {
+			record.ticks_in_stage = 0;
+			continue
+		};
+
+		if record.stages.last() == Some(&task.stage) {
+			record.ticks_in_stage += 1;
+			record.max_ticks_in_stage = record.max_ticks_in_stage.max(record.ticks_in_stage);
+
+			continue;
+		}
+
+		if record.stages.last() == Some(&ResourceCollectionStage::MovingToBase) && task.stage == ResourceCollectionStage::MovingToResource {
+			record.deliveries += 1;
+		}
+
+		record.stages.push(task.stage);
+		record.ticks_in_stage = 0;
+	}
+}
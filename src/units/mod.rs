@@ -0,0 +1,135 @@
+use bevy :: {
+	prelude :: *,
+	utils :: HashMap,
+};
+
+use serde :: Deserialize;
+
+use super :: game :: {
+	GameState,
+	BatchOfResources,
+};
+
+use super :: assets :: RonAssetLoader;
+
+mod systems;
+
+#[cfg(test)]
+mod tests;
+
+pub struct UnitsPlugin;
+
+impl Plugin for UnitsPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.register_type::<UnitType>()
+
+			.init_asset::<UnitDefinitions>()
+			.register_asset_loader(RonAssetLoader::<UnitDefinitions>::new(&["units.ron"]))
+
+			.add_systems(Update, (
+				systems::unit_definitions_reload,
+			).run_if(in_state(GameState::Main)))
+		;
+	}
+}
+
+pub const UNIT_DEFINITIONS_PATH : &str = "units/default.units.ron";
+
+/// All units share `NpcMovable`, `Selectable` and pathing, type only decides which `UnitDefinition` they use
+#[derive(Component, Reflect, Deserialize, Default, PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub enum UnitType {
+	#[default]
+	Svin,
+	Scout,
+	Hauler,
+}
+
+impl UnitType {
+	/// also the order of units in build menu
+	pub const ALL : [UnitType; 3] = [UnitType::Svin, UnitType::Scout, UnitType::Hauler];
+}
+
+/// Unit stats from `assets/units/default.units.ron`. Loaded together with other assets and copied into a resource
+/// so systems can simply use `Res<UnitDefinitions>`, the resource is updated whenever the file changes on disk
+#[derive(Asset, Resource, TypePath, Deserialize, Clone, Debug)]
+pub struct UnitDefinitions {
+	pub units			: HashMap<UnitType, UnitDefinition>,
+	pub player			: PlayerDefinition,
+}
+
+impl UnitDefinitions {
+	pub fn get(&self, unit_type: UnitType) -> &UnitDefinition {
+		let Some(definition) = self.units.get(&unit_type) else { panic!("there is no definition for unit type {:?}!", unit_type) };
+
+		definition
+	}
+
+	/// Every unit type has to be defined, otherwise definitions are rejected
+	pub fn missing(&self) -> Vec<UnitType> {
+		UnitType::ALL.into_iter()
+			.filter(|unit_type| !self.units.contains_key(unit_type))
+			.collect()
+	}
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct UnitDefinition {
+	pub name			: String,
+	/// path to gltf scene relative to assets folder
+	pub scene			: String,
+	/// scale of the scene, collider size is not affected by it
+	pub scale			: f32,
+	/// units without drill can't collect resources
+	pub drill			: bool,
+	/// meters per second
+	pub speed			: f32,
+	/// how many shards unit can carry
+	pub capacity		: usize,
+	/// seconds it takes to drill out one shard
+	pub mining_interval	: f32,
+	pub cost			: BatchOfResources,
+	/// seconds unit spends in production queue of base building
+	pub build_time		: f32,
+	pub collider		: CylinderSize,
+	#[serde(default)]
+	pub avoidance		: AvoidanceDefinition,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct PlayerDefinition {
+	pub capacity		: usize,
+	pub mining_interval	: f32,
+}
+
+/// Local avoidance tunables, see `ai::avoidance`
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct AvoidanceDefinition {
+	/// units further than this many meters are ignored
+	pub neighbour_distance	: f32,
+	/// only this many closest units are avoided
+	pub max_neighbours	: usize,
+	/// seconds ahead collisions are predicted, longer horizon makes unit turn away earlier but also more timidly
+	pub time_horizon	: f32,
+	/// meters kept between colliders on top of their radii
+	pub separation		: f32,
+}
+
+impl Default for AvoidanceDefinition {
+	fn default() -> Self {
+		Self {
+			neighbour_distance	: 6.0,
+			max_neighbours		: 10,
+			time_horizon		: 1.5,
+			separation			: 0.1,
+		}
+	}
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct CylinderSize {
+	pub half_height		: f32,
+	pub radius			: f32,
+}
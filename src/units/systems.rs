@@ -0,0 +1,57 @@
+use bevy :: prelude :: *;
+
+use super :: *;
+
+use crate :: assets :: GameAssets;
+
+use crate :: resource_collection :: ResourceCollector;
+
+/// Hot reload: capacity and mining rate are copied into `ResourceCollector` on spawn so they are updated here,
+/// everything else is read from `UnitDefinitions` when it's needed and changes with the next path, shard or purchase
+pub fn unit_definitions_reload(
+	mut asset_events		: EventReader<AssetEvent<UnitDefinitions>>,
+		definitions_assets	: Res<Assets<UnitDefinitions>>,
+		asset_server		: Res<AssetServer>,
+	mut game_assets			: ResMut<GameAssets>,
+	mut unit_definitions	: ResMut<UnitDefinitions>,
+	mut q_collector			: Query<(&mut ResourceCollector, Option<&UnitType>)>,
+) {
+	for event in asset_events.read() {
+		let AssetEvent::Modified { id } = event else { continue };
+
+		if *id != game_assets.unit_definitions.id() { continue }
+
+		let Some(definitions) = definitions_assets.get(*id) else { continue };
+
+		let missing = definitions.missing();
+		if !missing.is_empty() {
+			println!("ignoring reloaded unit definitions, these unit types are missing: {:?}", missing);
+			continue;
+		}
+
+		// already spawned units keep their scene, new ones get the new one
+		for (unit_type, definition) in definitions.units.iter() {
+			if unit_definitions.units.get(unit_type).map(|old| &old.scene) != Some(&definition.scene) {
+				game_assets.unit_scenes.insert(*unit_type, asset_server.load(definition.scene.clone()));
+			}
+		}
+
+		*unit_definitions = definitions.clone();
+
+		for (mut collector, unit_type) in q_collector.iter_mut() {
+			// player is the only collector that is not a unit
+			let (capacity, mining_interval) = match unit_type {
+				Some(unit_type) => {
+					let definition = unit_definitions.get(*unit_type);
+					(definition.capacity, definition.mining_interval)
+				},
+				None => (unit_definitions.player.capacity, unit_definitions.player.mining_interval),
+			};
+
+			collector.limit = capacity;
+			collector.mining_interval = mining_interval;
+		}
+
+		println!("unit definitions reloaded");
+	}
+}
@@ -0,0 +1,35 @@
+use bevy :: scene :: ron;
+
+use super :: *;
+
+#[test]
+fn default_unit_definitions_are_valid() {
+	let definitions = match ron::de::from_str::<UnitDefinitions>(include_str!("../../assets/units/default.units.ron")) {
+		Ok(definitions) => definitions,
+		Err(error) => panic!("failed to parse unit definitions: {}", error),
+	};
+
+	assert!(definitions.missing().is_empty(), "unit types without definition: {:?}", definitions.missing());
+
+	assert!(definitions.player.capacity > 0 && definitions.player.mining_interval > 0.0);
+
+	for (unit_type, definition) in definitions.units.iter() {
+		assert!(definition.speed > 0.0, "{:?}", unit_type);
+		assert!(definition.scale > 0.0, "{:?}", unit_type);
+		assert!(definition.build_time > 0.0, "{:?}", unit_type);
+		assert!(definition.collider.half_height > 0.0 && definition.collider.radius > 0.0, "{:?}", unit_type);
+		assert!(definition.scene.ends_with("#Scene0"), "{:?}", unit_type);
+
+		// units that can drill have to be able to carry something
+		if definition.drill {
+			assert!(definition.capacity > 0 && definition.mining_interval > 0.0, "{:?}", unit_type);
+		}
+	}
+
+	let svin = definitions.get(UnitType::Svin);
+	let scout = definitions.get(UnitType::Scout);
+	let hauler = definitions.get(UnitType::Hauler);
+
+	assert!(!scout.drill && scout.speed > svin.speed);
+	assert!(hauler.speed < svin.speed && hauler.capacity > svin.capacity && hauler.collider.radius > svin.collider.radius);
+}